@@ -52,7 +52,10 @@ pub fn dungeon_run<Host: RawRollupCore>(host: &mut Host) {
                 debug_msg!(Host, "Error processing dungeon {}", err);
             }
         }
-        Some(Input::Slot(_message)) => todo!("handle slot message"),
+        Some(Input::Slot(slot)) => {
+            // the dungeon is only driven by layer 1 messages
+            debug_msg!(Host, "Ignoring SlotData {} at level {}", slot.id, slot.level);
+        }
         None => (),
     }
 
@@ -3,7 +3,7 @@
 //! The host exposes 'safe capabilities' as a set of **C-style APIs**.  The `host`
 //! crate defines these as `extern` functions (see [rollup_core]) and is
 //! responsible for providing safe wrappers which can be called from **safe rust**.
-#![cfg_attr(not(feature = "testing"), no_std)]
+#![cfg_attr(not(any(feature = "testing", feature = "std")), no_std)]
 #![deny(missing_docs)]
 #![deny(rustdoc::all)]
 
@@ -14,4 +14,6 @@ pub mod input;
 pub mod path;
 pub mod rollup_core;
 pub mod runtime;
+#[cfg(feature = "alloc")]
+pub mod storage;
 pub mod wasm_host;
@@ -69,6 +69,93 @@ pub unsafe trait Path {
             .filter(|b| b == &&PATH_SEPARATOR)
             .count()
     }
+
+    /// Returns an iterator over the *steps* of the path, excluding separators.
+    ///
+    /// ```
+    /// # use host::path::{Path, RefPath};
+    /// let path = RefPath::assert_from(b"/tx/accounts/balance");
+    /// let steps: Vec<&[u8]> = path.steps().collect();
+    ///
+    /// assert_eq!(vec![&b"tx"[..], b"accounts", b"balance"], steps);
+    /// ```
+    fn steps(&self) -> Steps<'_> {
+        Steps {
+            remaining: self.as_bytes(),
+        }
+    }
+
+    /// Returns whether the path is either `prefix` itself, or a path under `prefix`.
+    ///
+    /// Paths are compared by whole steps, so `/a/bc` does *not* start with `/a/b`.
+    fn starts_with(&self, prefix: &impl Path) -> bool {
+        matches!(
+            self.as_bytes().strip_prefix(prefix.as_bytes()),
+            Some([] | [PATH_SEPARATOR, ..])
+        )
+    }
+
+    /// Returns the path relative to `prefix`, if the path is *under* `prefix`.
+    ///
+    /// Returns `None` if the path does not start with `prefix`, or is `prefix` itself.
+    ///
+    /// ```
+    /// # use host::path::{Path, RefPath};
+    /// let path = RefPath::assert_from(b"/tx/accounts/balance");
+    /// let prefix = RefPath::assert_from(b"/tx");
+    ///
+    /// assert_eq!(
+    ///     Some(RefPath::assert_from(b"/accounts/balance")),
+    ///     path.strip_prefix(&prefix)
+    /// );
+    /// assert_eq!(None, prefix.strip_prefix(&prefix));
+    /// ```
+    fn strip_prefix(&self, prefix: &impl Path) -> Option<RefPath<'_>> {
+        match self.as_bytes().strip_prefix(prefix.as_bytes()) {
+            Some(suffix @ [PATH_SEPARATOR, ..]) => Some(RefPath {
+                // SAFETY: a suffix of a path, beginning at a separator, is a path.
+                inner: unsafe { core::str::from_utf8_unchecked(suffix) },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the path without its last step, or `None` if the path has a single
+    /// step.
+    fn parent(&self) -> Option<RefPath<'_>> {
+        let path = self.as_bytes();
+
+        match path.iter().rposition(|b| b == &PATH_SEPARATOR) {
+            Some(0) | None => None,
+            Some(end) => Some(RefPath {
+                // SAFETY: a path, ending before one of its separators, is a path.
+                inner: unsafe { core::str::from_utf8_unchecked(&path[..end]) },
+            }),
+        }
+    }
+}
+
+/// Iterator over the steps of a [`Path`] - see [`Path::steps`].
+#[derive(Debug, Clone)]
+pub struct Steps<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Iterator for Steps<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_separator, path) = self.remaining.split_first()?;
+
+        let end = path
+            .iter()
+            .position(|b| b == &PATH_SEPARATOR)
+            .unwrap_or(path.len());
+        let (step, remaining) = path.split_at(end);
+
+        self.remaining = remaining;
+        Some(step)
+    }
 }
 
 /// Possible path validation errors.
@@ -161,6 +248,34 @@ const fn assert_is_valid_path(path: &[u8]) {
     }
 }
 
+#[doc(hidden)]
+pub const fn assert_is_valid_step(step: &[u8]) {
+    match validate_step(step) {
+        Err(PathError::PathTooLong) => panic!("Path step contained too many bytes"),
+        Err(PathError::InvalidEmptyStep) => panic!("Path steps must be non empty"),
+        Err(_) => panic!("Path step bytes must be ascii_alphanumeric or \"= b'.'\""),
+        Ok(()) => (),
+    }
+}
+
+const fn validate_step(step: &[u8]) -> Result<(), PathError> {
+    if step.is_empty() {
+        return Err(PathError::InvalidEmptyStep);
+    } else if step.len() >= PATH_MAX_SIZE {
+        return Err(PathError::PathTooLong);
+    }
+
+    let mut i = 0;
+
+    while i < step.len() {
+        if !is_allowed_step_byte(step[i]) {
+            return Err(PathError::InvalidByteInStep);
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
 const fn validate_path(path: &[u8]) -> Result<(), PathError> {
     match path {
         [] => Err(PathError::PathEmpty),
@@ -206,7 +321,10 @@ impl<'a> TryFrom<&'a [u8]> for RefPath<'a> {
 pub use owned::*;
 #[cfg(feature = "alloc")]
 mod owned {
-    use super::{validate_path, Path, PathError, RefPath};
+    use super::{
+        validate_path, validate_step, Path, PathError, RefPath, PATH_MAX_SIZE,
+        PATH_SEPARATOR,
+    };
     use alloc::string::{String, ToString};
     use alloc::vec::Vec;
 
@@ -214,7 +332,7 @@ mod owned {
     ///
     /// Useful when a new path is being constructed at runtime, which is not a sub-path of an
     /// already existing path (in which case you may use [RefPath]).
-    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
     pub struct OwnedPath {
         inner: String,
     }
@@ -232,6 +350,85 @@ mod owned {
         }
     }
 
+    impl OwnedPath {
+        /// Constructs an [`OwnedPath`] from a sequence of *steps*.
+        ///
+        /// ```
+        /// # use host::path::{OwnedPath, Path};
+        /// let path = OwnedPath::from_steps(&[b"tx", b"accounts"]).unwrap();
+        ///
+        /// assert_eq!(b"/tx/accounts", path.as_bytes());
+        /// ```
+        pub fn from_steps(steps: &[&[u8]]) -> Result<Self, PathError> {
+            let (first, rest) = steps.split_first().ok_or(PathError::PathEmpty)?;
+
+            let _: () = validate_step(first)?;
+
+            let mut inner = String::with_capacity(PATH_MAX_SIZE);
+            inner.push(PATH_SEPARATOR as char);
+            // SAFETY: we've validated that every byte is either alphanumeric or '.'
+            inner.push_str(unsafe { core::str::from_utf8_unchecked(first) });
+
+            let mut path = Self { inner };
+
+            for step in rest {
+                path.push_step(step)?;
+            }
+
+            Ok(path)
+        }
+
+        /// Appends `step` to the end of the path.
+        ///
+        /// The path is left unchanged if `step` is not a valid step, or if the path
+        /// would become longer than [PATH_MAX_SIZE].
+        ///
+        /// [PATH_MAX_SIZE]: super::PATH_MAX_SIZE
+        pub fn push_step(&mut self, step: &[u8]) -> Result<(), PathError> {
+            let _: () = validate_step(step)?;
+
+            if self.inner.len() + 1 + step.len() > PATH_MAX_SIZE {
+                return Err(PathError::PathTooLong);
+            }
+
+            self.inner.push(PATH_SEPARATOR as char);
+            // SAFETY: we've validated that every byte is either alphanumeric or '.'
+            self.inner
+                .push_str(unsafe { core::str::from_utf8_unchecked(step) });
+
+            Ok(())
+        }
+    }
+
+    /// Concatenates `prefix` & `suffix` into a single path.
+    ///
+    /// ```
+    /// # use host::path::{concat, Path, RefPath};
+    /// let prefix = RefPath::assert_from(b"/tx/accounts");
+    /// let suffix = RefPath::assert_from(b"/tz1/balance");
+    ///
+    /// let path = concat(&prefix, &suffix).unwrap();
+    ///
+    /// assert_eq!(b"/tx/accounts/tz1/balance", path.as_bytes());
+    /// ```
+    pub fn concat(
+        prefix: &impl Path,
+        suffix: &impl Path,
+    ) -> Result<OwnedPath, PathError> {
+        let size = prefix.size() + suffix.size();
+
+        if size > PATH_MAX_SIZE {
+            return Err(PathError::PathTooLong);
+        }
+
+        let mut bytes = Vec::with_capacity(size);
+        bytes.extend_from_slice(prefix.as_bytes());
+        bytes.extend_from_slice(suffix.as_bytes());
+
+        // SAFETY: both `prefix` & `suffix` are path-encoded, and fit in PATH_MAX_SIZE.
+        Ok(unsafe { OwnedPath::from_bytes_unchecked(bytes) })
+    }
+
     unsafe impl Path for OwnedPath {
         fn as_bytes(&self) -> &[u8] {
             self.inner.as_bytes()
@@ -274,6 +471,45 @@ mod owned {
     }
 }
 
+/// Constructs an [`OwnedPath`] from a sequence of *steps*, returning
+/// `Result<OwnedPath, PathError>`.
+///
+/// Steps given as string literals are validated at compile time. Any other step -
+/// either an identifier, or an expression in parentheses - may be anything that
+/// implements `AsRef<[u8]>`, and is validated at runtime.
+///
+/// ```
+/// # use host::path;
+/// # use host::path::Path;
+/// let address = "tz1";
+/// let path = path!("tx", "accounts", address, "balance").unwrap();
+///
+/// assert_eq!(b"/tx/accounts/tz1/balance", path.as_bytes());
+///
+/// assert!(path!("tx", (format!("{}/", address))).is_err());
+/// ```
+///
+/// But the following would fail to compile:
+/// ```compile_fail
+/// # use host::path;
+/// let path = path!("tx", "accounts!");
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! path {
+    (@step $step: literal) => {{
+        const STEP: &[u8] = $step.as_bytes();
+        const _: () = $crate::path::assert_is_valid_step(STEP);
+        STEP
+    }};
+    (@step $step: expr) => {
+        core::convert::AsRef::<[u8]>::as_ref(&$step)
+    };
+    ($($step: tt),+ $(,)?) => {
+        $crate::path::OwnedPath::from_steps(&[$($crate::path!(@step $step)),+])
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -350,4 +586,80 @@ mod tests {
 
         assert_eq!(8, result.len_steps());
     }
+
+    #[test]
+    fn steps_of_path() {
+        let path = RefPath::assert_from(b"/a/bc/d.e");
+        let steps: Vec<&[u8]> = path.steps().collect();
+
+        assert_eq!(vec![&b"a"[..], b"bc", b"d.e"], steps);
+        assert_eq!(path.len_steps(), path.steps().count());
+    }
+
+    #[test]
+    fn starts_with_whole_steps() {
+        let path = RefPath::assert_from(b"/a/bc/d");
+
+        assert!(path.starts_with(&path));
+        assert!(path.starts_with(&RefPath::assert_from(b"/a/bc")));
+        assert!(!path.starts_with(&RefPath::assert_from(b"/a/b")));
+        assert!(!path.starts_with(&RefPath::assert_from(b"/a/bc/d/e")));
+    }
+
+    #[test]
+    fn strip_prefix_and_parent() {
+        let path = RefPath::assert_from(b"/a/bc/d");
+
+        assert_eq!(
+            Some(RefPath::assert_from(b"/d")),
+            path.strip_prefix(&RefPath::assert_from(b"/a/bc"))
+        );
+        assert_eq!(None, path.strip_prefix(&RefPath::assert_from(b"/a/b")));
+        assert_eq!(Some(RefPath::assert_from(b"/a/bc")), path.parent());
+        assert_eq!(None, RefPath::assert_from(b"/a").parent());
+    }
+
+    #[test]
+    fn push_step_validates_step() {
+        let mut path = OwnedPath::from_steps(&[b"a"]).unwrap();
+
+        assert_eq!(Err(PathError::InvalidEmptyStep), path.push_step(b""));
+        assert_eq!(Err(PathError::InvalidByteInStep), path.push_step(b"b/c"));
+        assert_eq!(Ok(()), path.push_step(b"b.c"));
+        assert_eq!(b"/a/b.c", path.as_bytes());
+    }
+
+    #[test]
+    fn push_step_too_long() {
+        let step = [b'i'; PATH_MAX_SIZE / 2 - 1];
+        let mut path = OwnedPath::from_steps(&[&step]).unwrap();
+
+        assert_eq!(Ok(()), path.push_step(&step));
+        assert_eq!(PATH_MAX_SIZE, path.size());
+        assert_eq!(Err(PathError::PathTooLong), path.push_step(b"a"));
+        assert_eq!(PATH_MAX_SIZE, path.size());
+    }
+
+    #[test]
+    fn concat_paths() {
+        let prefix = RefPath::assert_from(b"/a/b");
+        let long = OwnedPath::from_steps(&[&[b'i'; PATH_MAX_SIZE - 2]]).unwrap();
+
+        assert_eq!(
+            Ok(RefPath::assert_from(b"/a/b/a/b").into()),
+            concat(&prefix, &prefix)
+        );
+        assert_eq!(Err(PathError::PathTooLong), concat(&prefix, &long));
+    }
+
+    #[test]
+    fn path_macro() {
+        let step = String::from("c");
+
+        assert_eq!(
+            Ok(RefPath::assert_from(b"/a/b/c/d").into()),
+            path!("a", "b", step, ("d"))
+        );
+        assert_eq!(Err(PathError::InvalidByteInStep), path!("a", ("b!")));
+    }
 }
@@ -4,6 +4,8 @@
 //!
 //! Includes blanket implementation for all types implementing [RawRollupCore].
 #[cfg(feature = "alloc")]
+use alloc::collections::BTreeSet;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
 #[cfg(feature = "alloc")]
@@ -12,6 +14,13 @@ use crate::path::Path;
 #[cfg(feature = "alloc")]
 use crate::path::{OwnedPath, PATH_MAX_SIZE};
 use crate::rollup_core::{RawRollupCore, WriteResult, PREIMAGE_HASH_SIZE};
+#[cfg(feature = "alloc")]
+use crate::storage::{StorageError, StorageValue};
+
+#[cfg(feature = "alloc")]
+pub mod stream;
+#[cfg(feature = "alloc")]
+pub mod transaction;
 
 #[derive(Copy, Eq, PartialEq, Clone, Debug)]
 /// List of errors that may be returned when called [Runtime] methods.
@@ -25,6 +34,10 @@ pub enum RuntimeError {
     PathNotFound,
     /// Attempted to get a subkey at an out-of-bounds index.
     StoreListIndexOutOfBounds,
+    /// Attempted to move to an offset outside of a value.
+    OffsetOutOfBounds,
+    /// Attempted to replace a value by a shorter one, at a path with values under it.
+    TruncateWithSubtree,
 }
 
 /// Returned by [`Runtime::store_has`] - specifies whether a path has a value or is a prefix.
@@ -38,6 +51,59 @@ pub enum ValueType {
     ValueWithSubtree,
 }
 
+/// Returned by the visitor of [`Runtime::store_walk`] - specifies how the walk continues.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Walk {
+    /// Continue the walk, including the values under the visited path.
+    Continue,
+    /// Continue the walk, skipping the values under the visited path.
+    SkipSubtree,
+    /// End the walk.
+    Stop,
+}
+
+/// Iterator over the paths of the values under a prefix - see [`Runtime::store_subkeys`].
+///
+/// Storage cannot change while the iterator borrows the runtime, so every index below
+/// the count of subkeys is valid - getting a subkey does not fail.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct Subkeys<'a, R: ?Sized, T> {
+    runtime: &'a R,
+    prefix: &'a T,
+    index: i64,
+    count: i64,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, R: Runtime + ?Sized, T: Path> Iterator for Subkeys<'a, R, T> {
+    type Item = OwnedPath;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let subkey = self
+            .runtime
+            .store_get_subkey(self.prefix, self.index)
+            .expect("Subkey index is below the count of subkeys");
+        self.index += 1;
+
+        let mut path = self.prefix.as_bytes().to_vec();
+        path.extend_from_slice(subkey.as_bytes());
+
+        // SAFETY: the subkey is either empty - for the value at `prefix` itself - or
+        //         path-encoded, and `prefix ++ subkey` is the path of a value in storage.
+        Some(unsafe { OwnedPath::from_bytes_unchecked(path) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.count - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
 impl From<WriteResult> for Result<(), RuntimeError> {
     fn from(write_result: WriteResult) -> Self {
         match write_result {
@@ -76,6 +142,15 @@ pub trait Runtime {
         max_bytes: usize,
     ) -> Result<Vec<u8>, RuntimeError>;
 
+    /// Read up to `buffer.len()` bytes from the given path in storage, starting
+    /// `from_offset`, into `buffer` - returning the number of bytes read.
+    fn store_read_slice<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RuntimeError>;
+
     /// Write the bytes given by `src` to storage at `path`, starting `at_offset`.
     fn store_write<T: Path>(
         &mut self,
@@ -87,6 +162,57 @@ pub trait Runtime {
     /// Delete `path` from storage.
     fn store_delete<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError>;
 
+    /// Read the value at `path` in storage, and decode it as a `V`.
+    ///
+    /// The value is read in full, however large.
+    #[cfg(feature = "alloc")]
+    fn store_get<T: Path, V: StorageValue>(&self, path: &T) -> Result<V, StorageError> {
+        use crate::rollup_core::MAX_FILE_CHUNK_SIZE;
+
+        let mut bytes = Vec::new();
+
+        loop {
+            let chunk = self.store_read(path, bytes.len(), MAX_FILE_CHUNK_SIZE)?;
+            bytes.extend_from_slice(chunk.as_slice());
+
+            if chunk.len() < MAX_FILE_CHUNK_SIZE {
+                break;
+            }
+        }
+
+        Ok(V::decode(bytes.as_slice())?)
+    }
+
+    /// Encode `value`, replacing the value at `path` in storage.
+    ///
+    /// The value is written in chunks of at most [MAX_FILE_CHUNK_SIZE] bytes. Values
+    /// under `path` are left untouched: when there are any, the previous value is
+    /// overwritten in place - and replacing it by a shorter value is an error.
+    ///
+    /// [MAX_FILE_CHUNK_SIZE]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+    #[cfg(feature = "alloc")]
+    fn store_put<T: Path, V: StorageValue>(
+        &mut self,
+        path: &T,
+        value: &V,
+    ) -> Result<(), RuntimeError> {
+        let bytes = value.encode();
+
+        match self.store_has(path) {
+            // a shorter value must not keep the tail of the previous one
+            Some(ValueType::Value) => self.store_delete(path)?,
+            // deleting the value would delete the values under it too
+            Some(ValueType::ValueWithSubtree) => {
+                if value_longer_than(self, path, bytes.len())? {
+                    return Err(RuntimeError::TruncateWithSubtree);
+                }
+            }
+            Some(ValueType::Subtree) | None => (),
+        }
+
+        store_write_chunked(self, path, bytes.as_slice())
+    }
+
     /// Count the number of subkeys under `prefix`.
     ///
     /// See [RawRollupCore::store_list_size].
@@ -103,6 +229,40 @@ pub trait Runtime {
         index: i64,
     ) -> Result<OwnedPath, RuntimeError>;
 
+    /// Returns an iterator over the paths of the values at, or under, `prefix`.
+    ///
+    /// Unlike [Runtime::store_get_subkey], each path *includes* `prefix` - where the
+    /// value at `prefix` itself, if any, is returned as `prefix`. Paths are returned
+    /// in the order given by [Runtime::store_get_subkey].
+    #[cfg(feature = "alloc")]
+    fn store_subkeys<'a, T: Path>(&'a self, prefix: &'a T) -> Subkeys<'a, Self, T> {
+        // the host traps when counting the subkeys of a path that doesn't exist
+        let count = match self.store_has(prefix) {
+            Some(_) => self.store_count_subkeys(prefix).unwrap_or(0),
+            None => 0,
+        };
+
+        Subkeys {
+            runtime: self,
+            prefix,
+            index: 0,
+            count,
+        }
+    }
+
+    /// Visit the paths of the values at, or under, `prefix`, depth-first.
+    ///
+    /// A path is visited before any paths under it, and paths sharing a parent are
+    /// visited in order of their last step. The [Walk] returned by `visit` decides
+    /// whether paths under the visited path are visited.
+    ///
+    /// Storage is listed one level at a time, so that the subkeys of a skipped path -
+    /// or of any path after the walk is stopped - are never listed.
+    #[cfg(feature = "alloc")]
+    fn store_walk<T: Path>(&self, prefix: &T, mut visit: impl FnMut(&OwnedPath) -> Walk) {
+        let _ = walk_subtree(self, prefix, &mut visit);
+    }
+
     /// Move one part of durable storage to a different location
     ///
     /// See [RawRollupCore::store_move].
@@ -225,6 +385,18 @@ where
         Ok(buffer)
     }
 
+    fn store_read_slice<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        check_path_exists(self, path)?;
+
+        // safe as `buffer` is initialised
+        Ok(unsafe { store_read_slice(self, path, from_offset, buffer) })
+    }
+
     fn store_write<T: Path>(
         &mut self,
         path: &T,
@@ -333,7 +505,6 @@ where
 ///   and return the actual number of bytes written. It is the caller's
 ///   responsibility to ensure that `buffer` is otherwise initialised.
 #[must_use]
-#[cfg(feature = "alloc")]
 unsafe fn store_read_slice<Host: RawRollupCore, T: Path>(
     host: &Host,
     path: &T,
@@ -423,6 +594,105 @@ pub fn save_value_sized<T: Path>(host: &mut impl RawRollupCore, path: &T, value:
     }
 }
 
+// Walk the values at, or under, `prefix` - see [Runtime::store_walk]. Returns
+// [Walk::Stop] if the walk was stopped.
+#[cfg(feature = "alloc")]
+fn walk_subtree<R: Runtime + ?Sized, T: Path>(
+    runtime: &R,
+    prefix: &T,
+    visit: &mut impl FnMut(&OwnedPath) -> Walk,
+) -> Walk {
+    let value_type = match runtime.store_has(prefix) {
+        Some(value_type) => value_type,
+        None => return Walk::Continue,
+    };
+
+    if let ValueType::Value | ValueType::ValueWithSubtree = value_type {
+        // SAFETY: `prefix` is path-encoded.
+        let path = unsafe { OwnedPath::from_bytes_unchecked(prefix.as_bytes().to_vec()) };
+
+        match visit(&path) {
+            Walk::Continue => (),
+            Walk::SkipSubtree => return Walk::Continue,
+            Walk::Stop => return Walk::Stop,
+        }
+    }
+
+    if let ValueType::Value = value_type {
+        return Walk::Continue;
+    }
+
+    // the paths one step under `prefix`, ordered by that step
+    let children: BTreeSet<OwnedPath> = runtime
+        .store_subkeys(prefix)
+        .filter_map(|path| {
+            let suffix = path.strip_prefix(prefix)?;
+            let step = suffix.steps().next()?;
+            let child = path.as_bytes()[..prefix.size() + 1 + step.len()].to_vec();
+
+            // SAFETY: a path, ending before one of its separators, is a path.
+            Some(unsafe { OwnedPath::from_bytes_unchecked(child) })
+        })
+        .collect();
+
+    for child in children.iter() {
+        if walk_subtree(runtime, child, visit) == Walk::Stop {
+            return Walk::Stop;
+        }
+    }
+
+    Walk::Continue
+}
+
+// Whether the value at `path` is longer than `len` bytes.
+#[cfg(feature = "alloc")]
+fn value_longer_than<R: Runtime + ?Sized, T: Path>(
+    runtime: &R,
+    path: &T,
+    len: usize,
+) -> Result<bool, RuntimeError> {
+    use crate::rollup_core::MAX_FILE_CHUNK_SIZE;
+
+    let mut chunk = [0; MAX_FILE_CHUNK_SIZE];
+    let mut offset = 0;
+
+    // the host traps when reading from past the end of a value, so only read from
+    // offsets known to be within it
+    while offset <= len {
+        let read = runtime.store_read_slice(path, offset, &mut chunk)?;
+
+        if read < MAX_FILE_CHUNK_SIZE {
+            return Ok(offset + read > len);
+        }
+        offset += read;
+    }
+
+    Ok(true)
+}
+
+/// Write `value` at `path` in storage, in chunks of at most [MAX_FILE_CHUNK_SIZE] bytes.
+///
+/// Any previous value at `path` must be no longer than `value`.
+///
+/// [MAX_FILE_CHUNK_SIZE]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+#[cfg(feature = "alloc")]
+pub(crate) fn store_write_chunked<R: Runtime + ?Sized, T: Path>(
+    runtime: &mut R,
+    path: &T,
+    value: &[u8],
+) -> Result<(), RuntimeError> {
+    use crate::rollup_core::MAX_FILE_CHUNK_SIZE;
+
+    // an empty value is still written, so that `path` exists
+    runtime.store_write(path, &[], 0)?;
+
+    for (index, chunk) in value.chunks(MAX_FILE_CHUNK_SIZE).enumerate() {
+        runtime.store_write(path, chunk, index * MAX_FILE_CHUNK_SIZE)?;
+    }
+
+    Ok(())
+}
+
 fn check_path_exists<T: Path>(
     runtime: &impl Runtime,
     path: &T,
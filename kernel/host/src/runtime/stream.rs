@@ -0,0 +1,251 @@
+//! Streaming access to values in durable storage.
+//!
+//! [`load_value_sized`] & [`save_value_sized`] hold a whole value in memory. A
+//! [`StoreReader`] or [`StoreWriter`] instead reads or writes a value at most
+//! [`MAX_FILE_CHUNK_SIZE`] bytes at a time, from an offset into the value that may be
+//! moved with `seek`.
+//!
+//! With the `std` feature, both implement the corresponding [`std::io`] traits.
+//!
+//! [`load_value_sized`]: super::load_value_sized
+//! [`save_value_sized`]: super::save_value_sized
+//! [`MAX_FILE_CHUNK_SIZE`]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+//! [`std::io`]: https://doc.rust-lang.org/std/io/index.html
+use super::{Runtime, RuntimeError, ValueType};
+use crate::path::Path;
+use crate::rollup_core::MAX_FILE_CHUNK_SIZE;
+use alloc::vec::Vec;
+
+/// Offset to seek to in a value - as `std::io::SeekFrom`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Offset from the start of the value.
+    Start(u64),
+    /// Offset from the end of the value.
+    End(i64),
+    /// Offset from the current offset.
+    Current(i64),
+}
+
+/// Reads the value at a path in durable storage, a chunk at a time.
+#[derive(Debug)]
+pub struct StoreReader<'a, R, T> {
+    runtime: &'a R,
+    path: &'a T,
+    offset: usize,
+    size: Option<usize>,
+}
+
+impl<'a, R: Runtime, T: Path> StoreReader<'a, R, T> {
+    /// Start reading the value at `path`, from its start.
+    pub fn new(runtime: &'a R, path: &'a T) -> Result<Self, RuntimeError> {
+        match runtime.store_has(path) {
+            Some(ValueType::Value | ValueType::ValueWithSubtree) => Ok(Self {
+                runtime,
+                path,
+                offset: 0,
+                size: None,
+            }),
+            _ => Err(RuntimeError::PathNotFound),
+        }
+    }
+
+    /// Read up to `buffer.len()` bytes - and at most [`MAX_FILE_CHUNK_SIZE`] - from
+    /// the current offset, returning the number of bytes read.
+    ///
+    /// Returns `0` once the end of the value is reached.
+    ///
+    /// [`MAX_FILE_CHUNK_SIZE`]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, RuntimeError> {
+        if matches!(self.size, Some(size) if self.offset >= size) {
+            return Ok(0);
+        }
+
+        let max_bytes = usize::min(buffer.len(), MAX_FILE_CHUNK_SIZE);
+        let read = self.runtime.store_read_slice(
+            self.path,
+            self.offset,
+            &mut buffer[..max_bytes],
+        )?;
+
+        // a short read ends at the end of the value
+        if read < max_bytes {
+            self.size = Some(self.offset + read);
+        }
+
+        self.offset += read;
+        Ok(read)
+    }
+
+    /// Move the current offset, returning the new offset from the start of the value.
+    ///
+    /// Seeking past the end of the value is allowed, after which nothing is read.
+    /// Seeking forwards - or from the end - reads through the rest of the value to
+    /// find its size, the first time.
+    pub fn seek(&mut self, position: SeekFrom) -> Result<u64, RuntimeError> {
+        let offset = match position {
+            SeekFrom::Start(offset) => usize::try_from(offset).ok(),
+            SeekFrom::End(delta) => offset_by(self.size()?, delta),
+            SeekFrom::Current(delta) => offset_by(self.offset, delta),
+        }
+        .ok_or(RuntimeError::OffsetOutOfBounds)?;
+
+        // the host traps when reading from past the end of a value
+        if offset > self.offset {
+            let _ = self.size()?;
+        }
+
+        self.offset = offset;
+        Ok(offset as u64)
+    }
+
+    /// The size of the value, in bytes.
+    pub fn size(&mut self) -> Result<usize, RuntimeError> {
+        if let Some(size) = self.size {
+            return Ok(size);
+        }
+
+        let mut size = self.offset;
+        let mut chunk = [0; MAX_FILE_CHUNK_SIZE];
+
+        loop {
+            let read = self.runtime.store_read_slice(self.path, size, &mut chunk)?;
+            size += read;
+
+            if read < MAX_FILE_CHUNK_SIZE {
+                self.size = Some(size);
+                return Ok(size);
+            }
+        }
+    }
+}
+
+/// Writes a value at a path in durable storage, a chunk at a time.
+#[derive(Debug)]
+pub struct StoreWriter<'a, R, T> {
+    runtime: &'a mut R,
+    path: &'a T,
+    offset: usize,
+    size: usize,
+}
+
+impl<'a, R: Runtime, T: Path> StoreWriter<'a, R, T> {
+    /// Start writing a new value at `path`.
+    ///
+    /// An empty value is first put at `path` with [`Runtime::store_put`], replacing
+    /// any previous value. Values under `path` are kept - so when there are any, a
+    /// previous non-empty value is an error, as it cannot be truncated.
+    pub fn new(runtime: &'a mut R, path: &'a T) -> Result<Self, RuntimeError> {
+        runtime.store_put(path, &Vec::<u8>::new())?;
+
+        Ok(Self {
+            runtime,
+            path,
+            offset: 0,
+            size: 0,
+        })
+    }
+
+    /// Write up to `buffer.len()` bytes - and at most [`MAX_FILE_CHUNK_SIZE`] - at the
+    /// current offset, returning the number of bytes written.
+    ///
+    /// [`MAX_FILE_CHUNK_SIZE`]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+    pub fn write(&mut self, buffer: &[u8]) -> Result<usize, RuntimeError> {
+        let len = usize::min(buffer.len(), MAX_FILE_CHUNK_SIZE);
+
+        self.runtime
+            .store_write(self.path, &buffer[..len], self.offset)?;
+
+        self.offset += len;
+        self.size = usize::max(self.size, self.offset);
+        Ok(len)
+    }
+
+    /// Move the current offset, returning the new offset from the start of the value.
+    ///
+    /// Values may not contain gaps, so seeking past the end of the value is an error.
+    pub fn seek(&mut self, position: SeekFrom) -> Result<u64, RuntimeError> {
+        let offset = match position {
+            SeekFrom::Start(offset) => usize::try_from(offset).ok(),
+            SeekFrom::End(delta) => offset_by(self.size, delta),
+            SeekFrom::Current(delta) => offset_by(self.offset, delta),
+        }
+        .filter(|offset| *offset <= self.size)
+        .ok_or(RuntimeError::OffsetOutOfBounds)?;
+
+        self.offset = offset;
+        Ok(offset as u64)
+    }
+
+    /// The size of the value written so far, in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+fn offset_by(offset: usize, delta: i64) -> Option<usize> {
+    let magnitude = usize::try_from(delta.unsigned_abs()).ok()?;
+
+    if delta < 0 {
+        offset.checked_sub(magnitude)
+    } else {
+        offset.checked_add(magnitude)
+    }
+}
+
+#[cfg(feature = "std")]
+mod io {
+    use super::{SeekFrom, StoreReader, StoreWriter};
+    use crate::path::Path;
+    use crate::runtime::{Runtime, RuntimeError};
+    use std::io;
+
+    impl From<io::SeekFrom> for SeekFrom {
+        fn from(position: io::SeekFrom) -> Self {
+            match position {
+                io::SeekFrom::Start(offset) => Self::Start(offset),
+                io::SeekFrom::End(delta) => Self::End(delta),
+                io::SeekFrom::Current(delta) => Self::Current(delta),
+            }
+        }
+    }
+
+    impl From<RuntimeError> for io::Error {
+        fn from(error: RuntimeError) -> Self {
+            let kind = match error {
+                RuntimeError::PathNotFound => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::InvalidInput,
+            };
+
+            io::Error::new(kind, format!("{:?}", error))
+        }
+    }
+
+    impl<'a, R: Runtime, T: Path> io::Read for StoreReader<'a, R, T> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            Ok(StoreReader::read(self, buffer)?)
+        }
+    }
+
+    impl<'a, R: Runtime, T: Path> io::Seek for StoreReader<'a, R, T> {
+        fn seek(&mut self, position: io::SeekFrom) -> io::Result<u64> {
+            Ok(StoreReader::seek(self, position.into())?)
+        }
+    }
+
+    impl<'a, R: Runtime, T: Path> io::Write for StoreWriter<'a, R, T> {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            Ok(StoreWriter::write(self, buffer)?)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a, R: Runtime, T: Path> io::Seek for StoreWriter<'a, R, T> {
+        fn seek(&mut self, position: io::SeekFrom) -> io::Result<u64> {
+            Ok(StoreWriter::seek(self, position.into())?)
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! Atomic changes to durable storage.
+//!
+//! A [`Transaction`] wraps a [`Runtime`], and buffers every change made to durable
+//! storage through it - writes, deletes, moves & copies - in memory. Reads through the
+//! transaction see its pending changes, before those of the wrapped runtime.
+//!
+//! Pending changes are only flushed to storage by [`Transaction::commit`]. A
+//! transaction that is rolled back - or dropped - leaves storage untouched, so that a
+//! kernel failing halfway through a message does not leave partial writes behind.
+//!
+//! A [`Savepoint`] marks the pending changes at a point in the transaction, allowing
+//! later changes to be rolled back while keeping earlier ones. Savepoints may be
+//! nested.
+//!
+//! *N.B.* values that are written, moved or copied by a transaction are held in memory
+//! in full until the transaction ends.
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use super::{store_write_chunked, Runtime, RuntimeError, ValueType};
+use crate::input::Input;
+use crate::path::{OwnedPath, Path};
+use crate::rollup_core::{MAX_FILE_CHUNK_SIZE, PREIMAGE_HASH_SIZE};
+
+// Values at, or under, a prefix - by their path relative to the prefix.
+type SubtreeValues = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Changes to durable storage, that have not yet been committed.
+#[derive(Debug, Clone, Default)]
+struct Overlay {
+    /// Values written by the transaction.
+    values: BTreeMap<OwnedPath, Vec<u8>>,
+    /// Prefixes deleted by the transaction - hiding any values under them in storage.
+    deleted: Vec<OwnedPath>,
+}
+
+impl Overlay {
+    fn is_deleted(&self, path: &impl Path) -> bool {
+        self.deleted.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    // Whether the overlay changes any value at, or under, `path`.
+    fn touches(&self, path: &impl Path) -> bool {
+        self.deleted
+            .iter()
+            .any(|prefix| path.starts_with(prefix) || prefix.starts_with(path))
+            || self.values.keys().any(|key| key.starts_with(path))
+    }
+
+    fn delete(&mut self, prefix: &impl Path) {
+        self.values.retain(|key, _| !key.starts_with(prefix));
+
+        if !self.is_deleted(prefix) {
+            self.deleted.retain(|deleted| !deleted.starts_with(prefix));
+            self.deleted.push(owned(prefix));
+        }
+    }
+}
+
+/// Marks the pending changes of a [`Transaction`] - see [`Transaction::savepoint`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Savepoint {
+    index: usize,
+    // unique within the transaction, so that a savepoint no longer on the stack is
+    // never mistaken for one taken later at the same index
+    id: u64,
+}
+
+/// Buffers changes to durable storage, until they are committed.
+///
+/// See the [module-level documentation](self).
+pub struct Transaction<'a, Host: Runtime> {
+    host: &'a mut Host,
+    overlay: Overlay,
+    savepoints: Vec<(u64, Overlay)>,
+    next_savepoint_id: u64,
+    // the paths under the prefix listed last, until the overlay next changes - so
+    // that listing subkeys one index at a time only walks the host once
+    listing: RefCell<Option<(OwnedPath, Vec<OwnedPath>)>>,
+}
+
+impl<'a, Host: Runtime> Transaction<'a, Host> {
+    /// Start a transaction over the durable storage of `host`.
+    pub fn new(host: &'a mut Host) -> Self {
+        Self {
+            host,
+            overlay: Overlay::default(),
+            savepoints: Vec::new(),
+            next_savepoint_id: 0,
+            listing: RefCell::new(None),
+        }
+    }
+
+    /// Flush every pending change to durable storage.
+    ///
+    /// Prefixes deleted by the transaction are deleted first, after which values
+    /// written by the transaction are written in full.
+    pub fn commit(self) -> Result<(), RuntimeError> {
+        let Self { host, overlay, .. } = self;
+
+        for prefix in overlay.deleted.iter() {
+            delete_subtree(host, prefix)?;
+        }
+
+        for (path, value) in overlay.values.iter() {
+            store_write_chunked(host, path, value.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+    /// Discard every pending change.
+    ///
+    /// Equivalent to dropping the transaction.
+    pub fn rollback(self) {}
+
+    /// Mark the pending changes, so that any later changes may be rolled back with
+    /// [`Transaction::rollback_to`].
+    pub fn savepoint(&mut self) -> Savepoint {
+        let id = self.next_savepoint_id;
+        self.next_savepoint_id += 1;
+
+        self.savepoints.push((id, self.overlay.clone()));
+        Savepoint {
+            index: self.savepoints.len() - 1,
+            id,
+        }
+    }
+
+    /// Discard every change made since `savepoint`, along with any savepoints nested
+    /// within it.
+    ///
+    /// # Panics
+    /// Panics if `savepoint` was released, or nested within a savepoint that was
+    /// rolled back to or released.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        self.assert_exists(&savepoint);
+
+        self.savepoints.truncate(savepoint.index + 1);
+        *self.overlay_mut() = self.savepoints.pop().unwrap().1;
+    }
+
+    /// Release `savepoint`, along with any savepoints nested within it, keeping the
+    /// changes made since.
+    ///
+    /// # Panics
+    /// Panics if `savepoint` was released, or nested within a savepoint that was
+    /// rolled back to or released.
+    pub fn release(&mut self, savepoint: Savepoint) {
+        self.assert_exists(&savepoint);
+
+        self.savepoints.truncate(savepoint.index);
+    }
+
+    fn assert_exists(&self, savepoint: &Savepoint) {
+        assert!(
+            matches!(
+                self.savepoints.get(savepoint.index),
+                Some((id, _)) if *id == savepoint.id
+            ),
+            "Savepoint no longer exists"
+        );
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay {
+        *self.listing.get_mut() = None;
+        &mut self.overlay
+    }
+
+    fn has_value(&self, path: &impl Path) -> bool {
+        self.overlay.values.contains_key(&owned(path))
+            || !self.overlay.is_deleted(path)
+                && matches!(
+                    self.host.store_has(path),
+                    Some(ValueType::Value | ValueType::ValueWithSubtree)
+                )
+    }
+
+    // Apply `f` to every path with a value at, or under, `prefix` - in alphabetical
+    // order.
+    fn with_paths_under<R>(
+        &self,
+        prefix: &impl Path,
+        f: impl FnOnce(&[OwnedPath]) -> R,
+    ) -> R {
+        let mut listing = self.listing.borrow_mut();
+
+        match listing.as_ref() {
+            Some((listed, paths)) if listed.as_bytes() == prefix.as_bytes() => f(paths),
+            _ => {
+                let (_, paths) =
+                    listing.insert((owned(prefix), self.paths_under(prefix)));
+                f(paths)
+            }
+        }
+    }
+
+    fn paths_under(&self, prefix: &impl Path) -> Vec<OwnedPath> {
+        let mut paths: BTreeSet<OwnedPath> = self
+            .host
+            .store_subkeys(prefix)
+            .filter(|path| !self.overlay.is_deleted(path))
+            .collect();
+
+        let overlay_paths = self
+            .overlay
+            .values
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned();
+
+        paths.extend(overlay_paths);
+        paths.into_iter().collect()
+    }
+
+    fn read_value(&self, path: &impl Path) -> Result<Vec<u8>, RuntimeError> {
+        if let Some(value) = self.overlay.values.get(&owned(path)) {
+            return Ok(value.clone());
+        }
+
+        if !self.has_value(path) {
+            return Err(RuntimeError::PathNotFound);
+        }
+
+        let mut value = Vec::new();
+
+        loop {
+            let chunk = self
+                .host
+                .store_read(path, value.len(), MAX_FILE_CHUNK_SIZE)?;
+            value.extend_from_slice(chunk.as_slice());
+
+            if chunk.len() < MAX_FILE_CHUNK_SIZE {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn read_subtree(&self, prefix: &impl Path) -> Result<SubtreeValues, RuntimeError> {
+        self.paths_under(prefix)
+            .iter()
+            .map(|path| {
+                let suffix = path.as_bytes()[prefix.size()..].to_vec();
+                Ok((suffix, self.read_value(path)?))
+            })
+            .collect()
+    }
+
+    fn write_subtree(&mut self, prefix: &impl Path, subtree: SubtreeValues) {
+        for (suffix, value) in subtree {
+            self.overlay_mut()
+                .values
+                .insert(concat(prefix, suffix.as_slice()), value);
+        }
+    }
+}
+
+impl<'a, Host: Runtime> Runtime for Transaction<'a, Host> {
+    fn write_output(&mut self, from: &[u8]) -> Result<(), RuntimeError> {
+        self.host.write_output(from)
+    }
+
+    fn read_input(&mut self, max_bytes: usize) -> Option<Input> {
+        self.host.read_input(max_bytes)
+    }
+
+    fn store_has<T: Path>(&self, path: &T) -> Option<ValueType> {
+        if !self.overlay.touches(path) {
+            return self.host.store_has(path);
+        }
+
+        let has_value = self.has_value(path);
+        let has_subtree = self.with_paths_under(path, |paths| {
+            paths.iter().any(|subpath| subpath.size() > path.size())
+        });
+
+        match (has_value, has_subtree) {
+            (false, false) => None,
+            (true, false) => Some(ValueType::Value),
+            (false, true) => Some(ValueType::Subtree),
+            (true, true) => Some(ValueType::ValueWithSubtree),
+        }
+    }
+
+    fn store_read<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        let value = match self.overlay.values.get(&owned(path)) {
+            Some(value) => value,
+            None if self.overlay.is_deleted(path) => {
+                return Err(RuntimeError::PathNotFound)
+            }
+            None => return self.host.store_read(path, from_offset, max_bytes),
+        };
+
+        Ok(chunk_of(value, from_offset, max_bytes).to_vec())
+    }
+
+    fn store_read_slice<T: Path>(
+        &self,
+        path: &T,
+        from_offset: usize,
+        buffer: &mut [u8],
+    ) -> Result<usize, RuntimeError> {
+        let value = match self.overlay.values.get(&owned(path)) {
+            Some(value) => value,
+            None if self.overlay.is_deleted(path) => {
+                return Err(RuntimeError::PathNotFound)
+            }
+            None => return self.host.store_read_slice(path, from_offset, buffer),
+        };
+
+        let chunk = chunk_of(value, from_offset, buffer.len());
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+
+    fn store_write<T: Path>(
+        &mut self,
+        path: &T,
+        src: &[u8],
+        at_offset: usize,
+    ) -> Result<(), RuntimeError> {
+        if src.len() > MAX_FILE_CHUNK_SIZE {
+            return Err(RuntimeError::WriteTooLarge);
+        }
+
+        let mut value = match self.read_value(path) {
+            Ok(value) => value,
+            Err(_) => Vec::with_capacity(src.len()),
+        };
+
+        assert!(
+            at_offset <= value.len(),
+            "Offset {} out of bounds of value of {} bytes",
+            at_offset,
+            value.len()
+        );
+
+        let to_offset = at_offset + src.len();
+
+        if to_offset > value.len() {
+            value.resize(to_offset, 0);
+        }
+        value[at_offset..to_offset].copy_from_slice(src);
+
+        self.overlay_mut().values.insert(owned(path), value);
+        Ok(())
+    }
+
+    fn store_delete<T: Path>(&mut self, path: &T) -> Result<(), RuntimeError> {
+        if !self.has_value(path) {
+            return Err(RuntimeError::PathNotFound);
+        }
+
+        self.overlay_mut().delete(path);
+        Ok(())
+    }
+
+    fn store_count_subkeys<T: Path>(&self, prefix: &T) -> Result<i64, RuntimeError> {
+        Ok(self.with_paths_under(prefix, |paths| paths.len() as i64))
+    }
+
+    fn store_get_subkey<T: Path>(
+        &self,
+        prefix: &T,
+        index: i64,
+    ) -> Result<OwnedPath, RuntimeError> {
+        let subkey = usize::try_from(index)
+            .ok()
+            .and_then(|index| {
+                self.with_paths_under(prefix, |paths| {
+                    paths
+                        .get(index)
+                        .map(|path| path.as_bytes()[prefix.size()..].to_vec())
+                })
+            })
+            .ok_or(RuntimeError::StoreListIndexOutOfBounds)?;
+
+        // SAFETY: a path with its prefix removed is either empty - as returned by the
+        //         host for the value at the prefix itself - or path-encoded.
+        Ok(unsafe { OwnedPath::from_bytes_unchecked(subkey) })
+    }
+
+    fn store_move(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        if !self.has_value(from_path) {
+            return Err(RuntimeError::PathNotFound);
+        }
+
+        let subtree = self.read_subtree(from_path)?;
+
+        self.overlay_mut().delete(from_path);
+        self.overlay_mut().delete(to_path);
+        self.write_subtree(to_path, subtree);
+
+        Ok(())
+    }
+
+    fn store_copy(
+        &mut self,
+        from_path: &impl Path,
+        to_path: &impl Path,
+    ) -> Result<(), RuntimeError> {
+        if !self.has_value(from_path) {
+            return Err(RuntimeError::PathNotFound);
+        }
+
+        let subtree = self.read_subtree(from_path)?;
+
+        self.overlay_mut().delete(to_path);
+        self.write_subtree(to_path, subtree);
+
+        Ok(())
+    }
+
+    fn reveal_preimage(
+        &self,
+        hash: &[u8; PREIMAGE_HASH_SIZE],
+        destination: &mut [u8],
+    ) -> usize {
+        self.host.reveal_preimage(hash, destination)
+    }
+}
+
+fn owned(path: &impl Path) -> OwnedPath {
+    // SAFETY: `path` is path-encoded.
+    unsafe { OwnedPath::from_bytes_unchecked(path.as_bytes().to_vec()) }
+}
+
+// At most `max_bytes` - and at most [`MAX_FILE_CHUNK_SIZE`] - of `value`, from
+// `from_offset`, as read from storage.
+fn chunk_of(value: &[u8], from_offset: usize, max_bytes: usize) -> &[u8] {
+    assert!(
+        from_offset <= value.len(),
+        "Offset {} out of bounds of value of {} bytes",
+        from_offset,
+        value.len()
+    );
+
+    let max_bytes = usize::min(max_bytes, MAX_FILE_CHUNK_SIZE);
+    let to_offset = usize::min(value.len(), from_offset + max_bytes);
+
+    &value[from_offset..to_offset]
+}
+
+fn concat(prefix: &impl Path, suffix: &[u8]) -> OwnedPath {
+    let mut path = prefix.as_bytes().to_vec();
+    path.extend_from_slice(suffix);
+
+    OwnedPath::try_from(path).expect("Subtree moved to a path that is too long")
+}
+
+// Delete everything at, or under, `prefix` - even when there is no value at `prefix`.
+fn delete_subtree(
+    host: &mut impl Runtime,
+    prefix: &impl Path,
+) -> Result<(), RuntimeError> {
+    match host.store_has(prefix) {
+        None => Ok(()),
+        Some(ValueType::Subtree) => {
+            host.store_write(prefix, &[], 0)?;
+            host.store_delete(prefix)
+        }
+        Some(ValueType::Value | ValueType::ValueWithSubtree) => host.store_delete(prefix),
+    }
+}
@@ -0,0 +1,189 @@
+//! Typed values in durable storage.
+//!
+//! A [`StorageValue`] is encoded to - and decoded from - the bytes held at a path in
+//! storage. It is read & written with [`Runtime::store_get`] & [`Runtime::store_put`],
+//! which take care of values larger than [`MAX_FILE_CHUNK_SIZE`].
+//!
+//! Integers are encoded *little-endian*, with `usize` & `isize` encoded as 64-bit
+//! integers, so that values are read the same by the kernel and by native tooling.
+//!
+//! [`Runtime::store_get`]: crate::runtime::Runtime::store_get
+//! [`Runtime::store_put`]: crate::runtime::Runtime::store_put
+//! [`MAX_FILE_CHUNK_SIZE`]: crate::rollup_core::MAX_FILE_CHUNK_SIZE
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::path::{OwnedPath, PathError};
+use crate::runtime::RuntimeError;
+
+/// Errors that may occur when decoding a value read from storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The value does not have the size of the type being decoded.
+    InvalidSize {
+        /// The size of the type being decoded.
+        expected: usize,
+        /// The size of the value in storage.
+        actual: usize,
+    },
+    /// The value does not fit in the type being decoded.
+    OutOfRange,
+    /// A string value is not valid utf8.
+    InvalidUtf8,
+    /// A path value is not path-encoded.
+    InvalidPath(PathError),
+}
+
+/// Errors that may occur when reading a typed value from storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// The value could not be read.
+    Runtime(RuntimeError),
+    /// The value was read, but could not be decoded.
+    Decode(DecodeError),
+}
+
+impl From<RuntimeError> for StorageError {
+    fn from(error: RuntimeError) -> Self {
+        Self::Runtime(error)
+    }
+}
+
+impl From<DecodeError> for StorageError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// A value that may be held in durable storage.
+pub trait StorageValue: Sized {
+    /// The bytes held in storage for the value.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decode the value from the bytes held in storage.
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+macro_rules! storage_value_int {
+    ($($int:ty),*) => {
+        $(
+            impl StorageValue for $int {
+                fn encode(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+                    let bytes = bytes.try_into().map_err(|_| DecodeError::InvalidSize {
+                        expected: core::mem::size_of::<Self>(),
+                        actual: bytes.len(),
+                    })?;
+
+                    Ok(Self::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+storage_value_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl StorageValue for usize {
+    fn encode(&self) -> Vec<u8> {
+        (*self as u64).encode()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        u64::decode(bytes)?
+            .try_into()
+            .map_err(|_| DecodeError::OutOfRange)
+    }
+}
+
+impl StorageValue for isize {
+    fn encode(&self) -> Vec<u8> {
+        (*self as i64).encode()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        i64::decode(bytes)?
+            .try_into()
+            .map_err(|_| DecodeError::OutOfRange)
+    }
+}
+
+impl StorageValue for Vec<u8> {
+    fn encode(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl StorageValue for String {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
+
+impl StorageValue for OwnedPath {
+    fn encode(&self) -> Vec<u8> {
+        use crate::path::Path;
+
+        self.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        OwnedPath::try_from(bytes.to_vec()).map_err(DecodeError::InvalidPath)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::RefPath;
+
+    fn roundtrip<T: StorageValue + PartialEq + core::fmt::Debug>(value: T) {
+        assert_eq!(Ok(&value), T::decode(&value.encode()).as_ref());
+    }
+
+    #[test]
+    fn storage_value_roundtrip() {
+        roundtrip(u8::MAX);
+        roundtrip(i16::MIN);
+        roundtrip(u32::MAX);
+        roundtrip(-5_i64);
+        roundtrip(usize::MAX);
+        roundtrip(isize::MIN);
+        roundtrip(vec![1, 2, 3]);
+        roundtrip(String::from("Hello, Ticket!"));
+        roundtrip(OwnedPath::from(&RefPath::assert_from(b"/kernel/boot.wasm")));
+    }
+
+    #[test]
+    fn integers_encoded_le() {
+        assert_eq!(vec![1, 0, 0, 0], 1_u32.encode());
+        assert_eq!(vec![2, 0, 0, 0, 0, 0, 0, 0], 2_usize.encode());
+    }
+
+    #[test]
+    fn decode_invalid_value() {
+        assert_eq!(
+            Err(DecodeError::InvalidSize {
+                expected: 4,
+                actual: 3
+            }),
+            i32::decode(&[0; 3])
+        );
+        assert_eq!(Err(DecodeError::InvalidUtf8), String::decode(&[0xff]));
+        assert_eq!(
+            Err(DecodeError::InvalidPath(PathError::InvalidStart)),
+            OwnedPath::decode(b"kernel")
+        );
+    }
+}
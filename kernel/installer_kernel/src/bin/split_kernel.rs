@@ -0,0 +1,58 @@
+//! Splits a kernel into the preimage pages revealed by the installer kernel.
+//!
+//! ```text
+//! split-kernel <kernel.wasm> <output-dir>
+//! ```
+//!
+//! Each page is written to `<output-dir>/<hex hash of the page>`, ready to be made
+//! available to the rollup node. The hash of the root page - to build the installer
+//! with - is printed.
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use crypto::blake2b::digest_256;
+use installer_kernel::pages::{split, PageHash};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (kernel, output_dir) = match args.as_slice() {
+        [kernel, output_dir] => (kernel, Path::new(output_dir)),
+        _ => {
+            eprintln!("Usage: split-kernel <kernel.wasm> <output-dir>");
+            process::exit(2);
+        }
+    };
+
+    let kernel = fs::read(kernel).unwrap_or_else(|err| {
+        eprintln!("Unable to read kernel {}: {}", kernel, err);
+        process::exit(1);
+    });
+    fs::create_dir_all(output_dir).unwrap_or_else(|err| {
+        eprintln!("Unable to create {}: {}", output_dir.display(), err);
+        process::exit(1);
+    });
+
+    let root_hash = split(kernel.as_slice(), |page| {
+        let hash: PageHash = digest_256(page.as_slice())
+            .expect("hashing failed")
+            .try_into()
+            .expect("hash is incorrect length");
+
+        let path = output_dir.join(to_hex(&hash));
+        fs::write(&path, page).unwrap_or_else(|err| {
+            eprintln!("Unable to write page {}: {}", path.display(), err);
+            process::exit(1);
+        });
+
+        hash
+    });
+
+    println!("{}", to_hex(&root_hash));
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,163 @@
+//! Installer kernel - upgrades the rollup to a kernel revealed through preimages.
+//!
+//! A kernel too large to be originated in a single operation is split into preimage
+//! pages by the `split-kernel` tool, which prints the hash of the root page. The
+//! installer is built with that hash, and originated instead:
+//!
+//! ```text
+//! split-kernel kernel.wasm preimages/
+//! INSTALLER_ROOT_HASH=<root hash> cargo build --target wasm32-unknown-unknown
+//! ```
+//!
+//! When called, the installer reveals every page of the kernel, reassembles it, saves
+//! it to [`PATH_KERNEL_BOOT`], and requests a reboot through [`PATH_KERNEL_NEXT`] - so
+//! that the installed kernel is run from then on.
+#![deny(missing_docs)]
+#![deny(rustdoc::all)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+pub mod pages;
+
+use alloc::vec::Vec;
+
+use debug::debug_msg;
+use host::path::{Path, PATH_KERNEL_BOOT, PATH_KERNEL_NEXT};
+use host::rollup_core::{RawRollupCore, PREIMAGE_HASH_SIZE};
+use host::runtime::{save_value_sized, Runtime, RuntimeError};
+
+use pages::{Page, PageError, PageHash, PAGE_SIZE};
+
+/// Errors that may occur when installing a kernel.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstallError {
+    /// The installer was built without a valid `INSTALLER_ROOT_HASH`.
+    InvalidRootHash,
+    /// A revealed page could not be parsed.
+    Page(PageError),
+    /// The reboot into the installed kernel could not be requested.
+    Runtime(RuntimeError),
+}
+
+impl From<PageError> for InstallError {
+    fn from(error: PageError) -> Self {
+        Self::Page(error)
+    }
+}
+
+impl From<RuntimeError> for InstallError {
+    fn from(error: RuntimeError) -> Self {
+        Self::Runtime(error)
+    }
+}
+
+/// Entrypoint of the installer kernel - installs the kernel of the root hash given
+/// at build time by `INSTALLER_ROOT_HASH`.
+pub fn installer_run<Host: RawRollupCore>(host: &mut Host) {
+    let result = root_hash(option_env!("INSTALLER_ROOT_HASH").unwrap_or_default())
+        .ok_or(InstallError::InvalidRootHash)
+        .and_then(|root_hash| install(host, &root_hash));
+
+    if let Err(err) = result {
+        debug_msg!(Host, "Unable to install kernel: {:?}", err);
+    }
+}
+
+/// Reveal the kernel whose root page has hash `root_hash`, save it as the kernel to
+/// boot, and request a reboot.
+///
+/// # Panics
+/// Panics if a page - or the root page - has not been made available to the host.
+pub fn install<Host: RawRollupCore>(
+    host: &mut Host,
+    root_hash: &PageHash,
+) -> Result<(), InstallError> {
+    let mut kernel = Vec::new();
+    reveal(host, root_hash, &mut kernel)?;
+
+    save_value_sized(host, &PATH_KERNEL_BOOT, kernel.as_slice());
+    Runtime::store_write(host, &PATH_KERNEL_NEXT, PATH_KERNEL_BOOT.as_bytes(), 0)?;
+
+    Ok(())
+}
+
+// Reveal the page of `hash`, appending the data pages under it to `kernel`.
+fn reveal<Host: RawRollupCore>(
+    host: &Host,
+    hash: &PageHash,
+    kernel: &mut Vec<u8>,
+) -> Result<(), InstallError> {
+    let mut buffer = [0; PAGE_SIZE];
+    let size = Runtime::reveal_preimage(host, hash, &mut buffer);
+
+    match Page::parse(&buffer[..size])? {
+        Page::Data(data) => kernel.extend_from_slice(data),
+        Page::Hashes(hashes) => {
+            for hash in hashes.iter() {
+                reveal(host, hash, kernel)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Decode the hex-encoded root hash.
+fn root_hash(hex: &str) -> Option<PageHash> {
+    if hex.len() != 2 * PREIMAGE_HASH_SIZE {
+        return None;
+    }
+
+    let mut hash = [0; PREIMAGE_HASH_SIZE];
+    for (byte, digits) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = core::str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+
+    Some(hash)
+}
+
+#[cfg(feature = "installer-kernel")]
+pub mod installer_kernel {
+    //! Entrypoint of the installer kernel.
+    use crate::installer_run;
+    use kernel::kernel_entry;
+    kernel_entry!(installer_run);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use host::runtime::load_value_sized;
+    use mock_runtime::host::MockHost;
+    use mock_runtime::state::HostState;
+
+    #[test]
+    fn install_reveals_kernel_and_requests_reboot() {
+        // Arrange
+        let kernel: Vec<u8> = (0..3 * PAGE_SIZE).map(|i| i as u8).collect();
+
+        let mut state = HostState::default();
+        let root_hash = pages::split(&kernel, |page| state.set_preimage(page));
+        let mut host = MockHost::from(state);
+
+        // Act
+        let result = install(&mut host, &root_hash);
+
+        // Assert
+        assert_eq!(Ok(()), result);
+        assert_eq!(Ok(kernel), load_value_sized(&host, &PATH_KERNEL_BOOT));
+        assert!(Runtime::store_has(&host, &PATH_KERNEL_NEXT).is_some());
+    }
+
+    #[test]
+    fn root_hash_from_hex() {
+        assert_eq!(
+            Some([0xab; PREIMAGE_HASH_SIZE]),
+            root_hash(&"ab".repeat(32))
+        );
+        assert_eq!(None, root_hash("ab"));
+        assert_eq!(None, root_hash(&"zz".repeat(32)));
+    }
+}
@@ -0,0 +1,163 @@
+//! Preimage pages of a kernel.
+//!
+//! A kernel too large to be originated is split into pages of at most
+//! [`PAGE_SIZE`] bytes, each revealed by its hash. The pages form a tree:
+//!
+//! ```markdown
+//! data page   := DATA_TAG ++ <up to MAX_DATA_SIZE bytes of the kernel>
+//! hashes page := HASHES_TAG ++ <up to MAX_HASHES hashes of pages>
+//! ```
+//!
+//! The kernel is the concatenation of the data pages, in the order they are reached
+//! from the root page.
+use alloc::vec::Vec;
+
+use host::rollup_core::PREIMAGE_HASH_SIZE;
+
+/// Maximum size of a preimage page.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Tag of a page holding part of the kernel.
+pub const DATA_TAG: u8 = 0;
+
+/// Tag of a page holding the hashes of further pages.
+pub const HASHES_TAG: u8 = 1;
+
+/// Maximum number of bytes of the kernel held by a data page.
+pub const MAX_DATA_SIZE: usize = PAGE_SIZE - 1;
+
+/// Maximum number of hashes held by a hashes page.
+pub const MAX_HASHES: usize = (PAGE_SIZE - 1) / PREIMAGE_HASH_SIZE;
+
+/// Hash of a preimage page.
+pub type PageHash = [u8; PREIMAGE_HASH_SIZE];
+
+/// Errors that may occur when parsing a page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PageError {
+    /// A page must at least contain its tag.
+    EmptyPage,
+    /// The tag of the page is neither [`DATA_TAG`] nor [`HASHES_TAG`].
+    UnknownTag(u8),
+    /// The hashes of a hashes page must each be [`PREIMAGE_HASH_SIZE`] bytes.
+    InvalidHashesSize(usize),
+}
+
+/// A parsed preimage page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Page<'a> {
+    /// Part of the kernel.
+    Data(&'a [u8]),
+    /// Hashes of further pages.
+    Hashes(Vec<PageHash>),
+}
+
+impl<'a> Page<'a> {
+    /// Parse a page revealed by the host.
+    pub fn parse(page: &'a [u8]) -> Result<Self, PageError> {
+        match page.split_first() {
+            None => Err(PageError::EmptyPage),
+            Some((&DATA_TAG, data)) => Ok(Page::Data(data)),
+            Some((&HASHES_TAG, hashes)) if hashes.len() % PREIMAGE_HASH_SIZE == 0 => {
+                let hashes = hashes
+                    .chunks_exact(PREIMAGE_HASH_SIZE)
+                    .map(|hash| hash.try_into().expect("chunk is hash sized"))
+                    .collect();
+                Ok(Page::Hashes(hashes))
+            }
+            Some((&HASHES_TAG, hashes)) => {
+                Err(PageError::InvalidHashesSize(hashes.len()))
+            }
+            Some((tag, _)) => Err(PageError::UnknownTag(*tag)),
+        }
+    }
+}
+
+/// Split `kernel` into preimage pages, returning the hash of the root page.
+///
+/// Each page is given to `add_preimage`, which returns its hash - such as
+/// `Store::add_preimage` of the mock runtime.
+pub fn split(
+    kernel: &[u8],
+    mut add_preimage: impl FnMut(Vec<u8>) -> PageHash,
+) -> PageHash {
+    let mut hashes: Vec<PageHash> = if kernel.is_empty() {
+        vec![add_preimage(vec![DATA_TAG])]
+    } else {
+        kernel
+            .chunks(MAX_DATA_SIZE)
+            .map(|data| add_preimage(page(DATA_TAG, data)))
+            .collect()
+    };
+
+    while hashes.len() > 1 {
+        hashes = hashes
+            .chunks(MAX_HASHES)
+            .map(|hashes| add_preimage(page(HASHES_TAG, &hashes.concat())))
+            .collect();
+    }
+
+    hashes[0]
+}
+
+fn page(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut page = Vec::with_capacity(contents.len() + 1);
+    page.push(tag);
+    page.extend_from_slice(contents);
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // Hashes pages by their index, keeping them to be looked up.
+    fn split_into_map(kernel: &[u8]) -> (PageHash, HashMap<PageHash, Vec<u8>>) {
+        let mut pages = HashMap::new();
+
+        let root = split(kernel, |page| {
+            assert!(page.len() <= PAGE_SIZE);
+
+            let mut hash = [0; PREIMAGE_HASH_SIZE];
+            hash[..8].copy_from_slice(&pages.len().to_le_bytes());
+            pages.insert(hash, page);
+            hash
+        });
+
+        (root, pages)
+    }
+
+    fn join(hash: &PageHash, pages: &HashMap<PageHash, Vec<u8>>, kernel: &mut Vec<u8>) {
+        match Page::parse(&pages[hash]).unwrap() {
+            Page::Data(data) => kernel.extend_from_slice(data),
+            Page::Hashes(hashes) => hashes.iter().for_each(|h| join(h, pages, kernel)),
+        }
+    }
+
+    #[test]
+    fn split_join_roundtrip() {
+        for size in [0, 1, MAX_DATA_SIZE, MAX_DATA_SIZE * MAX_HASHES + 1] {
+            // Arrange
+            let kernel: Vec<u8> = (0..size).map(|i| i as u8).collect();
+
+            // Act
+            let (root, pages) = split_into_map(&kernel);
+
+            // Assert
+            let mut joined = Vec::new();
+            join(&root, &pages, &mut joined);
+            assert_eq!(kernel, joined);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_pages() {
+        assert_eq!(Err(PageError::EmptyPage), Page::parse(&[]));
+        assert_eq!(Err(PageError::UnknownTag(2)), Page::parse(&[2, 0]));
+        assert_eq!(
+            Err(PageError::InvalidHashesSize(3)),
+            Page::parse(&[HASHES_TAG, 0, 0, 0])
+        );
+    }
+}
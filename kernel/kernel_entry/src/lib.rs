@@ -75,12 +75,51 @@ macro_rules! kernel_entry {
         }
 
         /// The `mock_kernel_next` is called by the mock host at regular intervals.
+        ///
+        /// Each call is charged its own ticks, from the start of the call.
         #[cfg(not(target_arch = "wasm32"))]
         pub fn mock_kernel_next(host: &mut mock_runtime::host::MockHost) {
             #[cfg(feature = "panic-hook")]
             kernel::set_panic_hook();
 
+            host.start_kernel_call();
             $kernel_next(host)
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate as kernel;
+    use host::rollup_core::RawRollupCore;
+    use host::runtime::Runtime;
+    use mock_runtime::cost::{CostModel, HostCallCost};
+    use mock_runtime::host::MockHost;
+
+    fn write_output<Host: RawRollupCore>(host: &mut Host) {
+        Runtime::write_output(host, &[1; 10]).unwrap();
+    }
+
+    kernel_entry!(write_output);
+
+    #[test]
+    fn mock_kernel_next_resets_ticks() {
+        // Arrange
+        let mut host = MockHost::default();
+        host.set_cost_model(CostModel {
+            tick_limit: 20,
+            kernel_call: 10,
+            write_output: HostCallCost::new(5, 0),
+            ..CostModel::free()
+        });
+
+        // Act
+        mock_kernel_next(&mut host);
+        let first = host.ticks();
+        mock_kernel_next(&mut host);
+
+        // Assert
+        assert_eq!(15, first);
+        assert_eq!(15, host.ticks());
+    }
+}
@@ -0,0 +1,43 @@
+//! Checks kernel modules against the rules of the PVM, before they are deployed.
+//!
+//! ```text
+//! validate-kernel <kernel.wasm>...
+//! ```
+//!
+//! Every rule broken by each kernel is reported - exits with a non-zero status if any
+//! kernel is invalid.
+use std::env;
+use std::process;
+
+use mock_runtime::validate::validate_file;
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+
+    if paths.is_empty() {
+        eprintln!("Usage: validate-kernel <kernel.wasm>...");
+        process::exit(2);
+    }
+
+    let mut valid = true;
+
+    for path in paths.iter() {
+        match validate_file(path) {
+            Ok(issues) if issues.is_empty() => println!("{}: ok", path),
+            Ok(issues) => {
+                valid = false;
+                for issue in issues {
+                    println!("{}: {}", path, issue);
+                }
+            }
+            Err(error) => {
+                valid = false;
+                eprintln!("{}: unable to read kernel: {:?}", path, error);
+            }
+        }
+    }
+
+    if !valid {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Estimation of the ticks used by a kernel.
+//!
+//! The PVM bounds the number of ticks a kernel may use in each call to `kernel_next`.
+//! [`MockHost`] charges ticks against a [`CostModel`] for every host call, and
+//! traps with [`TrapCondition::TickLimitExceeded`] once the limit of the current kernel
+//! call is exceeded.
+//!
+//! The costs given by [`CostModel::default`] are estimates, rather than the exact costs
+//! of the PVM: they are intended to catch kernels that are *far* too slow, before they
+//! are deployed.
+//!
+//! [`MockHost`]: crate::host::MockHost
+//! [`TrapCondition::TickLimitExceeded`]: crate::trap::TrapCondition::TickLimitExceeded
+
+/// Ticks available to each call of `kernel_next`, on the PVM.
+pub const DEFAULT_TICK_LIMIT: u64 = 11_000_000_000;
+
+/// Ticks charged for a host call: a fixed cost per call, and a cost per byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HostCallCost {
+    /// Ticks charged for each call.
+    pub per_call: u64,
+    /// Ticks charged for each byte read or written by the call.
+    pub per_byte: u64,
+}
+
+impl HostCallCost {
+    /// A host call costing `per_call` ticks, and `per_byte` ticks per byte.
+    pub const fn new(per_call: u64, per_byte: u64) -> Self {
+        Self { per_call, per_byte }
+    }
+
+    /// Ticks charged for a call over `bytes` bytes.
+    pub fn ticks(&self, bytes: usize) -> u64 {
+        self.per_call
+            .saturating_add(self.per_byte.saturating_mul(bytes as u64))
+    }
+}
+
+/// Ticks charged for each kernel call, and each host call made by the kernel.
+///
+/// Host calls not listed are charged the `store_read` cost, with the bytes of
+/// their path arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    /// Ticks available to each call of `kernel_next`.
+    pub tick_limit: u64,
+    /// Ticks charged at the start of each call of `kernel_next`.
+    pub kernel_call: u64,
+    /// Cost of `read_input`, per byte of the input read.
+    pub read_input: HostCallCost,
+    /// Cost of `store_read`, per byte of the value read.
+    pub store_read: HostCallCost,
+    /// Cost of `store_write`, per byte of the value written.
+    pub store_write: HostCallCost,
+    /// Cost of `store_delete`, per byte of the path deleted.
+    pub store_delete: HostCallCost,
+    /// Cost of `store_move`, per byte of the paths and values moved.
+    pub store_move: HostCallCost,
+    /// Cost of `store_copy`, per byte of the paths and values copied.
+    pub store_copy: HostCallCost,
+    /// Cost of `write_output`, per byte of the output written.
+    pub write_output: HostCallCost,
+    /// Cost of `reveal_preimage`, per byte of the preimage revealed.
+    pub reveal_preimage: HostCallCost,
+}
+
+impl CostModel {
+    /// A cost model charging no ticks, and so never trapping.
+    pub const fn free() -> Self {
+        Self {
+            tick_limit: u64::MAX,
+            kernel_call: 0,
+            read_input: HostCallCost::new(0, 0),
+            store_read: HostCallCost::new(0, 0),
+            store_write: HostCallCost::new(0, 0),
+            store_delete: HostCallCost::new(0, 0),
+            store_move: HostCallCost::new(0, 0),
+            store_copy: HostCallCost::new(0, 0),
+            write_output: HostCallCost::new(0, 0),
+            reveal_preimage: HostCallCost::new(0, 0),
+        }
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            tick_limit: DEFAULT_TICK_LIMIT,
+            kernel_call: 1_000_000,
+            read_input: HostCallCost::new(10_000, 100),
+            store_read: HostCallCost::new(10_000, 100),
+            store_write: HostCallCost::new(50_000, 500),
+            store_delete: HostCallCost::new(50_000, 100),
+            store_move: HostCallCost::new(50_000, 100),
+            store_copy: HostCallCost::new(50_000, 500),
+            write_output: HostCallCost::new(50_000, 500),
+            reveal_preimage: HostCallCost::new(100_000, 100),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_call_cost_saturates() {
+        // Arrange
+        let cost = HostCallCost::new(10, u64::MAX);
+
+        // Act
+        let ticks = cost.ticks(2);
+
+        // Assert
+        assert_eq!(u64::MAX, ticks);
+        assert_eq!(30, HostCallCost::new(10, 2).ticks(10));
+    }
+}
@@ -2,9 +2,11 @@
 //! kernel standalone for experiements and testing purposes. Used when
 //! _not_ compiling to **wasm**.
 
-use crate::state::{HostState, NextInput};
+use crate::cost::{CostModel, HostCallCost};
+use crate::state::{HostState, InputLevel, NextInput};
+use crate::trap::{trap, TrapCondition};
 use core::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     ptr,
     slice::{from_raw_parts, from_raw_parts_mut},
 };
@@ -37,6 +39,12 @@ pub fn check_debug_log(f: impl Fn(&[String])) {
     DEBUG_LOG.with(|log| log.read_log(f))
 }
 
+/// Returns a copy of the current `DebugLog` of `MockHost`.
+#[cfg(feature = "replay")]
+pub(crate) fn debug_log() -> Vec<String> {
+    DEBUG_LOG.with(|log| log.read_log(|log| log.to_vec()))
+}
+
 /// Reset `MockHost` runtime state to `HostState::default()`.
 pub fn reset_debug_log() {
     DEBUG_LOG.with(|log| log.0.borrow_mut().clear());
@@ -46,6 +54,8 @@ pub fn reset_debug_log() {
 #[derive(Debug, Default)]
 pub struct MockHost {
     state: RefCell<HostState>,
+    cost_model: CostModel,
+    ticks: Cell<u64>,
 }
 
 impl MockHost {
@@ -53,12 +63,59 @@ impl MockHost {
     pub fn into_inner(self) -> HostState {
         self.state.into_inner()
     }
+
+    // The level the host is at.
+    pub(crate) fn input_level(&self) -> InputLevel {
+        self.state.borrow().input_level()
+    }
+
+    /// The [`CostModel`] charged for kernel and host calls.
+    pub fn cost_model(&self) -> &CostModel {
+        &self.cost_model
+    }
+
+    /// Set the [`CostModel`] charged for kernel and host calls.
+    pub fn set_cost_model(&mut self, cost_model: CostModel) {
+        self.cost_model = cost_model;
+    }
+
+    /// The ticks charged since the start of the current kernel call.
+    pub fn ticks(&self) -> u64 {
+        self.ticks.get()
+    }
+
+    /// Start a new call of `kernel_next`, resetting the ticks charged.
+    ///
+    /// # Panics
+    /// Traps if the cost of the kernel call alone exceeds the tick limit.
+    pub fn start_kernel_call(&self) {
+        self.ticks.set(0);
+        self.charge(self.cost_model.kernel_call);
+    }
+
+    // Charge `ticks` to the current kernel call, trapping once over the tick limit.
+    fn charge(&self, ticks: u64) {
+        let ticks = self.ticks.get().saturating_add(ticks);
+        self.ticks.set(ticks);
+
+        if ticks > self.cost_model.tick_limit {
+            trap(TrapCondition::TickLimitExceeded {
+                limit: self.cost_model.tick_limit,
+                ticks,
+            });
+        }
+    }
+
+    fn charge_call(&self, cost: impl FnOnce(&CostModel) -> HostCallCost, bytes: usize) {
+        self.charge(cost(&self.cost_model).ticks(bytes));
+    }
 }
 
 impl From<HostState> for MockHost {
     fn from(state: HostState) -> Self {
         Self {
             state: RefCell::new(state),
+            ..Default::default()
         }
     }
 }
@@ -86,6 +143,8 @@ unsafe impl RawRollupCore for MockHost {
             payload,
         }) = self.state.borrow_mut().handle_read_input(max_bytes)
         {
+            self.charge_call(|cost| cost.read_input, payload.len());
+
             ptr::write(r#type, input_type);
             ptr::write(level, input_level);
             ptr::write(id, input_id);
@@ -96,6 +155,8 @@ unsafe impl RawRollupCore for MockHost {
 
             payload.len()
         } else {
+            self.charge_call(|cost| cost.read_input, 0);
+
             0_usize
         }
     }
@@ -112,12 +173,16 @@ unsafe impl RawRollupCore for MockHost {
     }
 
     unsafe fn write_output(&self, src: *const u8, num_bytes: usize) -> WriteResult {
+        self.charge_call(|cost| cost.write_output, num_bytes);
+
         let output = from_raw_parts(src, num_bytes).to_vec();
 
         self.state.borrow_mut().handle_write_output(output)
     }
 
     unsafe fn store_has(&self, path: *const u8, len: usize) -> ValueType {
+        self.charge_call(|cost| cost.store_read, len);
+
         let path = from_raw_parts(path, len);
         self.state.borrow().handle_store_has(path)
     }
@@ -139,6 +204,8 @@ unsafe impl RawRollupCore for MockHost {
 
         assert!(bytes.len() <= max_bytes);
 
+        self.charge_call(|cost| cost.store_read, len + bytes.len());
+
         let slice = from_raw_parts_mut(dst, bytes.len());
         slice.copy_from_slice(bytes.as_slice());
 
@@ -153,6 +220,8 @@ unsafe impl RawRollupCore for MockHost {
         src: *const u8,
         num_bytes: usize,
     ) -> WriteResult {
+        self.charge_call(|cost| cost.store_write, len + num_bytes);
+
         let path = from_raw_parts(path, len);
         let bytes = from_raw_parts(src, num_bytes);
 
@@ -162,12 +231,16 @@ unsafe impl RawRollupCore for MockHost {
     }
 
     unsafe fn store_delete(&self, path: *const u8, len: usize) {
+        self.charge_call(|cost| cost.store_delete, len);
+
         let path = from_raw_parts(path, len);
 
         self.state.borrow_mut().handle_store_delete(path);
     }
 
     unsafe fn store_list_size(&self, path: *const u8, len: usize) -> i64 {
+        self.charge_call(|cost| cost.store_read, len);
+
         let path = from_raw_parts(path, len);
 
         self.state.borrow().handle_store_list_size(path)
@@ -181,6 +254,8 @@ unsafe impl RawRollupCore for MockHost {
         dst: *mut u8,
         max_size: usize,
     ) -> usize {
+        self.charge_call(|cost| cost.store_read, len);
+
         let path = from_raw_parts(path, len);
 
         let subkey = self
@@ -190,7 +265,7 @@ unsafe impl RawRollupCore for MockHost {
             .as_bytes()
             .to_vec();
 
-        let copy_len = usize::max(max_size, subkey.len());
+        let copy_len = usize::min(max_size, subkey.len());
 
         let slice = from_raw_parts_mut(dst, copy_len);
         slice.copy_from_slice(&subkey[..copy_len]);
@@ -208,6 +283,9 @@ unsafe impl RawRollupCore for MockHost {
         let from_path = from_raw_parts(from_path, from_path_len);
         let to_path = from_raw_parts(to_path, to_path_len);
 
+        let moved = self.state.borrow().subtree_size(from_path);
+        self.charge_call(|cost| cost.store_move, from_path_len + to_path_len + moved);
+
         self.state
             .borrow_mut()
             .handle_store_move(from_path, to_path);
@@ -223,6 +301,9 @@ unsafe impl RawRollupCore for MockHost {
         let from_path = from_raw_parts(from_path, from_path_len);
         let to_path = from_raw_parts(to_path, to_path_len);
 
+        let copied = self.state.borrow().subtree_size(from_path);
+        self.charge_call(|cost| cost.store_copy, from_path_len + to_path_len + copied);
+
         self.state
             .borrow_mut()
             .handle_store_copy(from_path, to_path);
@@ -246,6 +327,8 @@ unsafe impl RawRollupCore for MockHost {
 
         assert!(bytes.len() <= max_bytes);
 
+        self.charge_call(|cost| cost.reveal_preimage, bytes.len());
+
         let slice = from_raw_parts_mut(destination_addr, bytes.len());
         slice.copy_from_slice(bytes.as_slice());
 
@@ -262,9 +345,15 @@ mod tests {
     use crate::state::{self, HostState};
     use host::{
         input::{Input as KernelInput, MessageData},
-        path::RefPath,
+        path::{Path, RefPath},
         rollup_core::{Input, MAX_INPUT_MESSAGE_SIZE},
-        runtime::{load_value_sized, save_value_sized, Runtime},
+        runtime::{
+            load_value_sized, save_value_sized,
+            stream::{SeekFrom, StoreReader, StoreWriter},
+            transaction::Transaction,
+            Runtime, RuntimeError, ValueType, Walk,
+        },
+        storage::{DecodeError, StorageError},
     };
 
     #[test]
@@ -278,7 +367,10 @@ mod tests {
             vec![(Input::MessageData, vec![5; MAX_INPUT_MESSAGE_SIZE / 2])].iter(),
         );
 
-        let mut mock_host = MockHost { state };
+        let mut mock_host = MockHost {
+            state,
+            ..Default::default()
+        };
 
         // Act
         let result = mock_host.read_input(MAX_INPUT_MESSAGE_SIZE);
@@ -302,7 +394,10 @@ mod tests {
 
         let hash = state.borrow_mut().set_preimage(data);
 
-        let mock_host = MockHost { state };
+        let mock_host = MockHost {
+            state,
+            ..Default::default()
+        };
 
         let mut buffer = [0; 300];
         // Act
@@ -336,6 +431,7 @@ mod tests {
 
         let mut host = MockHost {
             state: new_host_state(),
+            ..Default::default()
         };
 
         // Act
@@ -345,4 +441,549 @@ mod tests {
         // Assert
         assert_eq!(result, Ok(value));
     }
+
+    #[test]
+    fn store_put_store_get_roundtrip() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/testing/path");
+        let value = (0..79).cycle().take(8000).collect::<Vec<u8>>();
+        let shorter = vec![1, 2, 3];
+
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+
+        // Act
+        host.store_put(&PATH, &value).unwrap();
+        let result: Result<Vec<u8>, _> = host.store_get(&PATH);
+
+        host.store_put(&PATH, &shorter).unwrap();
+        let overwritten: Result<Vec<u8>, _> = host.store_get(&PATH);
+        let invalid: Result<u64, _> = host.store_get(&PATH);
+
+        // Assert
+        assert_eq!(Ok(value), result);
+        assert_eq!(Ok(shorter), overwritten);
+        assert_eq!(
+            Err(StorageError::Decode(DecodeError::InvalidSize {
+                expected: 8,
+                actual: 3
+            })),
+            invalid
+        );
+    }
+
+    #[test]
+    fn store_put_keeps_values_under_path() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/tx/a");
+        let value = (0..79).cycle().take(5000).collect::<Vec<u8>>();
+        let mut host = host_with_values(&[(b"/tx/a", b"abc"), (b"/tx/a/x", b"x")]);
+
+        // Act
+        let longer = host.store_put(&PATH, &value);
+        let shorter = host.store_put(&PATH, &b"ab".to_vec());
+
+        // Assert
+        assert_eq!(Ok(()), longer);
+        assert_eq!(Err(RuntimeError::TruncateWithSubtree), shorter);
+        assert_eq!(Ok(value), host.store_get::<_, Vec<u8>>(&PATH));
+        assert_eq!(vec!["", "/x"], subkeys(&host, b"/tx/a"));
+    }
+
+    #[test]
+    fn store_list_get_copies_at_most_max_size() {
+        use host::rollup_core::RawRollupCore;
+
+        // Arrange
+        const PREFIX: &[u8] = b"/testing";
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+        Runtime::store_write(
+            &mut host,
+            &RefPath::assert_from(b"/testing/subkey"),
+            b"v",
+            0,
+        )
+        .unwrap();
+
+        let mut buffer = [0; 16];
+
+        // Act
+        let size = unsafe {
+            RawRollupCore::store_list_get(
+                &host,
+                PREFIX.as_ptr(),
+                PREFIX.len(),
+                0,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            )
+        };
+        let subkey = buffer[..size].to_vec();
+
+        let truncated_size = unsafe {
+            RawRollupCore::store_list_get(
+                &host,
+                PREFIX.as_ptr(),
+                PREFIX.len(),
+                0,
+                buffer.as_mut_ptr(),
+                4,
+            )
+        };
+
+        // Assert
+        assert_eq!(b"/subkey", subkey.as_slice());
+        assert_eq!(b"/sub", &buffer[..truncated_size]);
+    }
+
+    fn host_with_values(values: &[(&[u8], &[u8])]) -> MockHost {
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+
+        for (path, value) in values {
+            host.store_write(&RefPath::assert_from(path), value, 0)
+                .unwrap();
+        }
+
+        host
+    }
+
+    fn subkeys(runtime: &impl Runtime, prefix: &[u8]) -> Vec<String> {
+        let prefix = RefPath::assert_from(prefix);
+        let count = runtime.store_count_subkeys(&prefix).unwrap();
+
+        (0..count)
+            .map(|index| runtime.store_get_subkey(&prefix, index).unwrap())
+            .map(|subkey| String::from_utf8(subkey.as_bytes().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn store_move_store_copy_charged_per_byte_of_subtree() {
+        use crate::cost::{CostModel, HostCallCost};
+
+        // Arrange
+        const FROM: RefPath = RefPath::assert_from(b"/a");
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+        host.store_write(&FROM, &[0; 10], 0).unwrap();
+        host.store_write(&RefPath::assert_from(b"/a/b"), &[0; 20], 0)
+            .unwrap();
+        host.set_cost_model(CostModel {
+            store_move: HostCallCost::new(0, 1),
+            store_copy: HostCallCost::new(0, 2),
+            ..CostModel::free()
+        });
+
+        // Act
+        host.start_kernel_call();
+        host.store_copy(&FROM, &RefPath::assert_from(b"/c"))
+            .unwrap();
+        let copied = host.ticks();
+
+        host.start_kernel_call();
+        host.store_move(&FROM, &RefPath::assert_from(b"/d"))
+            .unwrap();
+        let moved = host.ticks();
+
+        // Assert
+        // both paths, the value at `/a`, and the subkey & value at `/a/b`
+        assert_eq!(2 * (4 + 10 + 22), copied);
+        assert_eq!(4 + 10 + 22, moved);
+    }
+
+    #[test]
+    fn transaction_subkeys_follow_changes() {
+        // Arrange
+        let mut host = host_with_values(&[(b"/tx/a", b"a"), (b"/tx/b", b"b")]);
+        let mut tx = Transaction::new(&mut host);
+
+        // Act
+        let before = subkeys(&tx, b"/tx");
+        let savepoint = tx.savepoint();
+
+        tx.store_write(&RefPath::assert_from(b"/tx/c"), b"c", 0)
+            .unwrap();
+        let written = subkeys(&tx, b"/tx");
+
+        tx.store_delete(&RefPath::assert_from(b"/tx/a")).unwrap();
+        let deleted = subkeys(&tx, b"/tx");
+
+        tx.rollback_to(savepoint);
+        let rolled_back = subkeys(&tx, b"/tx");
+
+        // Assert
+        assert_eq!(vec!["/a", "/b"], before);
+        assert_eq!(vec!["/a", "/b", "/c"], written);
+        assert_eq!(vec!["/b", "/c"], deleted);
+        assert_eq!(before, rolled_back);
+    }
+
+    #[test]
+    fn transaction_reads_pending_changes() {
+        // Arrange
+        const X: RefPath = RefPath::assert_from(b"/tx/a/x");
+        let mut host = host_with_values(&[
+            (b"/tx/a", b"a"),
+            (b"/tx/a/x", b"x"),
+            (b"/tx/a/y", b"y"),
+            (b"/tx/b", b"b"),
+        ]);
+        let mut tx = Transaction::new(&mut host);
+
+        // Act
+        tx.store_write(&RefPath::assert_from(b"/tx/a/z"), b"z", 0)
+            .unwrap();
+        tx.store_write(&RefPath::assert_from(b"/tx/a/y"), b"yy", 1)
+            .unwrap();
+        tx.store_delete(&X).unwrap();
+        tx.store_move(
+            &RefPath::assert_from(b"/tx/a"),
+            &RefPath::assert_from(b"/tx/c"),
+        )
+        .unwrap();
+        tx.store_copy(
+            &RefPath::assert_from(b"/tx/b"),
+            &RefPath::assert_from(b"/tx/c/b"),
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(
+            vec!["/b", "/c", "/c/b", "/c/y", "/c/z"],
+            subkeys(&tx, b"/tx")
+        );
+        assert_eq!(vec!["", "/b", "/y", "/z"], subkeys(&tx, b"/tx/c"));
+        assert_eq!(
+            Ok(b"yyy".to_vec()),
+            tx.store_read(&RefPath::assert_from(b"/tx/c/y"), 0, 10)
+        );
+        assert!(tx.store_has(&RefPath::assert_from(b"/tx/a")).is_none());
+        assert!(matches!(
+            tx.store_has(&RefPath::assert_from(b"/tx/c")),
+            Some(ValueType::ValueWithSubtree)
+        ));
+        assert_eq!(Err(RuntimeError::PathNotFound), tx.store_delete(&X));
+
+        tx.rollback();
+        assert_eq!(vec!["/a", "/a/x", "/a/y", "/b"], subkeys(&host, b"/tx"));
+    }
+
+    #[test]
+    fn transaction_commit() {
+        // Arrange
+        let value = (0..79).cycle().take(8000).collect::<Vec<u8>>();
+        let mut host = host_with_values(&[(b"/tx/a/x", b"x"), (b"/tx/b", b"b")]);
+        let mut tx = Transaction::new(&mut host);
+
+        tx.store_put(&RefPath::assert_from(b"/tx/a/y"), &value)
+            .unwrap();
+        tx.store_delete(&RefPath::assert_from(b"/tx/a/x")).unwrap();
+        tx.store_move(
+            &RefPath::assert_from(b"/tx/b"),
+            &RefPath::assert_from(b"/tx/c"),
+        )
+        .unwrap();
+
+        // Act
+        tx.commit().unwrap();
+
+        // Assert
+        assert_eq!(vec!["/a/y", "/c"], subkeys(&host, b"/tx"));
+        assert_eq!(
+            Ok(value),
+            host.store_get::<_, Vec<u8>>(&RefPath::assert_from(b"/tx/a/y"))
+        );
+        assert_eq!(
+            Ok(b"b".to_vec()),
+            host.store_read(&RefPath::assert_from(b"/tx/c"), 0, 10)
+        );
+    }
+
+    #[test]
+    fn transaction_savepoints() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/tx/value");
+        let mut host = host_with_values(&[(b"/tx/value", b"0")]);
+        let mut tx = Transaction::new(&mut host);
+
+        // Act
+        let first = tx.savepoint();
+        tx.store_write(&PATH, b"1", 0).unwrap();
+
+        let second = tx.savepoint();
+        tx.store_write(&PATH, b"2", 0).unwrap();
+
+        let third = tx.savepoint();
+        tx.store_write(&PATH, b"3", 0).unwrap();
+
+        tx.rollback_to(third);
+        let after_third = tx.store_read(&PATH, 0, 1);
+
+        tx.release(second);
+        tx.store_write(&PATH, b"4", 0).unwrap();
+        let after_release = tx.store_read(&PATH, 0, 1);
+
+        tx.rollback_to(first);
+        let after_first = tx.store_read(&PATH, 0, 1);
+
+        tx.store_write(&PATH, b"5", 0).unwrap();
+        tx.commit().unwrap();
+
+        // Assert
+        assert_eq!(Ok(b"2".to_vec()), after_third);
+        assert_eq!(Ok(b"4".to_vec()), after_release);
+        assert_eq!(Ok(b"0".to_vec()), after_first);
+        assert_eq!(Ok(b"5".to_vec()), host.store_read(&PATH, 0, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Savepoint no longer exists")]
+    fn transaction_savepoint_not_reused() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/tx/value");
+        let mut host = host_with_values(&[(b"/tx/value", b"0")]);
+        let mut tx = Transaction::new(&mut host);
+
+        let a = tx.savepoint();
+        let b = tx.savepoint();
+        tx.rollback_to(a);
+
+        let _c = tx.savepoint();
+        tx.store_write(&PATH, b"1", 0).unwrap();
+        let _d = tx.savepoint();
+
+        // Act
+        tx.rollback_to(b);
+    }
+
+    fn walk_host() -> MockHost {
+        host_with_values(&[
+            (b"/s", b""),
+            (b"/s/a", b""),
+            (b"/s/a/b", b""),
+            (b"/s/a.c", b""),
+            (b"/s/d", b""),
+            (b"/sd", b""),
+        ])
+    }
+
+    fn walk(host: &MockHost, mut visit: impl FnMut(&str) -> Walk) -> Vec<String> {
+        let mut visited = Vec::new();
+
+        host.store_walk(&RefPath::assert_from(b"/s"), |path| {
+            let path = String::from_utf8(path.as_bytes().to_vec()).unwrap();
+            let walk = visit(path.as_str());
+            visited.push(path);
+            walk
+        });
+
+        visited
+    }
+
+    #[test]
+    fn store_subkeys_includes_prefix() {
+        // Arrange
+        const PREFIX: RefPath = RefPath::assert_from(b"/s");
+        let host = walk_host();
+
+        // Act
+        let subkeys = host
+            .store_subkeys(&PREFIX)
+            .map(|path| String::from_utf8(path.as_bytes().to_vec()).unwrap())
+            .collect::<Vec<_>>();
+
+        // Assert
+        let mut expected = host
+            .state
+            .borrow()
+            .subkeys_of(PREFIX.as_bytes())
+            .map(|subkey| format!("/s{}", subkey))
+            .collect::<Vec<_>>();
+        expected.sort();
+
+        assert_eq!(vec!["/s", "/s/a", "/s/a.c", "/s/a/b", "/s/d"], subkeys);
+        assert_eq!(expected, subkeys);
+        assert_eq!(
+            0,
+            host.store_subkeys(&RefPath::assert_from(b"/missing"))
+                .count()
+        );
+    }
+
+    #[test]
+    fn store_walk_depth_first() {
+        // Arrange
+        let host = walk_host();
+
+        // Act
+        let all = walk(&host, |_| Walk::Continue);
+        let skipped = walk(&host, |path| match path {
+            "/s/a" => Walk::SkipSubtree,
+            _ => Walk::Continue,
+        });
+        let stopped = walk(&host, |path| match path {
+            "/s/a/b" => Walk::Stop,
+            _ => Walk::Continue,
+        });
+
+        // Assert
+        assert_eq!(vec!["/s", "/s/a", "/s/a/b", "/s/a.c", "/s/d"], all);
+        assert_eq!(vec!["/s", "/s/a", "/s/a.c", "/s/d"], skipped);
+        assert_eq!(vec!["/s", "/s/a", "/s/a/b"], stopped);
+    }
+
+    #[test]
+    fn store_walk_stop_lists_no_further() {
+        // Arrange
+        let host = walk_host();
+
+        // Act
+        host.start_kernel_call();
+        walk(&host, |_| Walk::Stop);
+        let stopped = host.ticks();
+
+        host.start_kernel_call();
+        walk(&host, |_| Walk::Continue);
+        let all = host.ticks();
+
+        // Assert
+        assert!(stopped < all);
+    }
+
+    #[test]
+    fn store_writer_store_reader_roundtrip() {
+        use std::io::{copy, Read};
+
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/testing/path");
+        let value = (0..79).cycle().take(10_000).collect::<Vec<u8>>();
+
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+
+        // Act
+        let mut writer = StoreWriter::new(&mut host, &PATH).unwrap();
+        let written = copy(&mut value.as_slice(), &mut writer).unwrap();
+
+        let mut result = Vec::new();
+        let mut reader = StoreReader::new(&host, &PATH).unwrap();
+        reader.read_to_end(&mut result).unwrap();
+
+        // Assert
+        assert_eq!(value.len() as u64, written);
+        assert_eq!(value, result);
+        assert_eq!(Ok(value), host.store_get::<_, Vec<u8>>(&PATH));
+    }
+
+    #[test]
+    fn store_writer_keeps_values_under_path() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/tx/a");
+        let mut host = host_with_values(&[(b"/tx/a", b"abc"), (b"/tx/a/x", b"x")]);
+
+        // Act
+        let result = StoreWriter::new(&mut host, &PATH).map(|_| ());
+
+        // Assert
+        assert_eq!(Err(RuntimeError::TruncateWithSubtree), result);
+        assert_eq!(Ok(b"abc".to_vec()), host.store_get::<_, Vec<u8>>(&PATH));
+        assert_eq!(vec!["", "/x"], subkeys(&host, b"/tx/a"));
+    }
+
+    #[test]
+    fn store_reader_seek() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/testing/path");
+        let value = (0..=255).cycle().take(5000).collect::<Vec<u8>>();
+
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+        host.store_put(&PATH, &value).unwrap();
+
+        let mut reader = StoreReader::new(&host, &PATH).unwrap();
+        let mut buffer = [0; 4];
+
+        // Act & Assert
+        assert_eq!(Ok(4996), reader.seek(SeekFrom::End(-4)));
+        assert_eq!(Ok(4), reader.read(&mut buffer));
+        assert_eq!(value[4996..], buffer);
+
+        assert_eq!(Ok(10), reader.seek(SeekFrom::Start(10)));
+        assert_eq!(Ok(12), reader.seek(SeekFrom::Current(2)));
+        assert_eq!(Ok(4), reader.read(&mut buffer));
+        assert_eq!(value[12..16], buffer);
+
+        assert_eq!(Ok(6000), reader.seek(SeekFrom::Start(6000)));
+        assert_eq!(Ok(0), reader.read(&mut buffer));
+        assert_eq!(
+            Err(RuntimeError::OffsetOutOfBounds),
+            reader.seek(SeekFrom::Current(-6001))
+        );
+    }
+
+    #[test]
+    fn store_writer_seek() {
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/testing/path");
+
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+        let mut writer = StoreWriter::new(&mut host, &PATH).unwrap();
+
+        // Act
+        writer.write(b"Hello, world").unwrap();
+        writer.seek(SeekFrom::Start(7)).unwrap();
+        writer.write(b"W").unwrap();
+        writer.seek(SeekFrom::End(0)).unwrap();
+        writer.write(b"!").unwrap();
+        let past_end = writer.seek(SeekFrom::Current(1));
+
+        // Assert
+        assert_eq!(Err(RuntimeError::OffsetOutOfBounds), past_end);
+        assert_eq!(13, writer.size());
+        assert_eq!(
+            Ok(b"Hello, World!".to_vec()),
+            host.store_get::<_, Vec<u8>>(&PATH)
+        );
+    }
+    #[test]
+    fn store_reader_reads_pending_transaction_value() {
+        use std::io::Read;
+
+        // Arrange
+        const PATH: RefPath = RefPath::assert_from(b"/testing/path");
+        let value = (0..79).cycle().take(5000).collect::<Vec<u8>>();
+
+        let mut host = MockHost {
+            state: new_host_state(),
+            ..Default::default()
+        };
+        let mut tx = Transaction::new(&mut host);
+        tx.store_put(&PATH, &value).unwrap();
+
+        // Act
+        let mut result = Vec::new();
+        let mut reader = StoreReader::new(&tx, &PATH).unwrap();
+        reader.read_to_end(&mut result).unwrap();
+
+        // Assert
+        assert_eq!(value, result);
+        assert_eq!(Ok(value.len()), reader.size());
+    }
 }
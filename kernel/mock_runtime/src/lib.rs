@@ -2,6 +2,14 @@
 #![deny(missing_docs)]
 #![deny(rustdoc::all)]
 
+pub mod cost;
 pub mod host;
+#[cfg(feature = "replay")]
+pub mod replay;
+pub mod runtime;
 pub mod state;
 pub mod trap;
+#[cfg(feature = "wasm")]
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,454 @@
+//! Replay of a complete inbox through the [`MockHost`].
+//!
+//! The inbox is read from a JSON file, listing the messages of each level as hex strings:
+//!
+//! ```json
+//! [
+//!   { "level": 1, "messages": ["00cafe", { "slot": "0123" }] },
+//!   { "level": 3, "messages": ["01beef"] }
+//! ]
+//! ```
+//!
+//! A message is either an inbox message, or a slot data chunk given as `{ "slot": .. }`.
+//!
+//! The kernel is driven by a [`MockRuntime`], until every level of the inbox has been
+//! run. The resulting durable store, outbox and debug log can then be written to disk,
+//! to be compared against a previous run.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use host::path::{Path as _, DURABLE_STORAGE_PREFIX};
+use host::rollup_core::Input;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::host::{debug_log, reset_debug_log, MockHost};
+use crate::runtime::MockRuntime;
+use crate::state::HostState;
+
+const STORE_FILE: &str = "store.json";
+const OUTBOX_FILE: &str = "outbox.json";
+const DEBUG_LOG_FILE: &str = "debug.log";
+
+/// Errors that may occur when loading an inbox, or writing the result of a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// Failure reading or writing a file.
+    Io(std::io::Error),
+    /// The inbox is not valid JSON, or does not match the expected format.
+    Json(serde_json::Error),
+    /// Levels of the inbox must be strictly increasing.
+    LevelsNotIncreasing {
+        /// The level preceding `level` in the inbox.
+        previous: i32,
+        /// The out-of-order level.
+        level: i32,
+    },
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+/// An input of the inbox.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum InboxInput {
+    /// An inbox message, given as a hex string.
+    Message(#[serde(deserialize_with = "from_hex")] Vec<u8>),
+    /// A slot data chunk, given as `{ "slot": <hex string> }`.
+    Slot {
+        /// The contents of the slot.
+        #[serde(deserialize_with = "from_hex")]
+        slot: Vec<u8>,
+    },
+}
+
+impl InboxInput {
+    fn to_input(&self) -> (Input, Vec<u8>) {
+        match self {
+            InboxInput::Message(payload) => (Input::MessageData, payload.clone()),
+            InboxInput::Slot { slot } => (Input::SlotDataChunk, slot.clone()),
+        }
+    }
+}
+
+/// The inputs added to the inbox at a level.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct InboxLevel {
+    /// The level of the inputs.
+    pub level: i32,
+    /// The inputs, in the order they are read by the kernel.
+    pub messages: Vec<InboxInput>,
+}
+
+/// Every input of the inbox, ordered by level.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Inbox {
+    levels: Vec<InboxLevel>,
+}
+
+impl Inbox {
+    /// Create an inbox from its levels, which must be strictly increasing.
+    pub fn new(levels: Vec<InboxLevel>) -> Result<Self, ReplayError> {
+        for pair in levels.windows(2) {
+            if pair[0].level >= pair[1].level {
+                return Err(ReplayError::LevelsNotIncreasing {
+                    previous: pair[0].level,
+                    level: pair[1].level,
+                });
+            }
+        }
+
+        Ok(Self { levels })
+    }
+
+    /// Parse an inbox from its JSON representation.
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        Self::new(serde_json::from_str(json)?)
+    }
+
+    /// Load an inbox from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// The levels of the inbox.
+    pub fn levels(&self) -> &[InboxLevel] {
+        self.levels.as_slice()
+    }
+}
+
+/// The state of the host after replaying an inbox.
+#[derive(Debug)]
+pub struct Replay {
+    /// The final state of the host.
+    pub state: HostState,
+    /// The debug messages written by the kernel, during the replay.
+    pub debug_log: Vec<String>,
+}
+
+impl Replay {
+    /// The contents of durable storage, by path - without the `/durable` prefix.
+    pub fn durable_store(&self) -> BTreeMap<String, Vec<u8>> {
+        let prefix = std::str::from_utf8(DURABLE_STORAGE_PREFIX.as_bytes())
+            .expect("durable prefix is valid utf8");
+
+        self.state
+            .store
+            .as_ref()
+            .iter()
+            .filter_map(|(path, value)| {
+                path.strip_prefix(prefix)
+                    .filter(|path| path.starts_with('/'))
+                    .map(|path| (path.to_string(), value.clone()))
+            })
+            .collect()
+    }
+
+    /// The outputs written by the kernel, by level - in the order they were written.
+    pub fn outbox(&self) -> BTreeMap<i32, Vec<Vec<u8>>> {
+        let mut outputs: BTreeMap<i32, BTreeMap<u32, Vec<u8>>> = BTreeMap::new();
+
+        for (path, value) in self.state.store.as_ref().iter() {
+            let mut steps = match path.strip_prefix("/output/") {
+                Some(steps) => steps.split('/'),
+                None => continue,
+            };
+
+            // `/output/id` holds the id of the next output, and is skipped.
+            if let (Some(Ok(level)), Some(Ok(id)), None) = (
+                steps.next().map(str::parse),
+                steps.next().map(str::parse),
+                steps.next(),
+            ) {
+                outputs.entry(level).or_default().insert(id, value.clone());
+            }
+        }
+
+        outputs
+            .into_iter()
+            .map(|(level, outputs)| (level, outputs.into_values().collect()))
+            .collect()
+    }
+
+    /// Write the durable store, outbox and debug log into `dir`:
+    ///
+    /// - `store.json`: a map from each durable path to its value, as a hex string.
+    /// - `outbox.json`: the outputs of each level, as hex strings.
+    /// - `debug.log`: the debug messages, one per line.
+    pub fn write_to(&self, dir: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let store: BTreeMap<String, String> = self
+            .durable_store()
+            .into_iter()
+            .map(|(path, value)| (path, to_hex(&value)))
+            .collect();
+        fs::write(dir.join(STORE_FILE), serde_json::to_string_pretty(&store)?)?;
+
+        let outbox: Vec<OutboxLevel> = self
+            .outbox()
+            .into_iter()
+            .map(|(level, outputs)| OutboxLevel {
+                level,
+                outputs: outputs.iter().map(|output| to_hex(output)).collect(),
+            })
+            .collect();
+        fs::write(
+            dir.join(OUTBOX_FILE),
+            serde_json::to_string_pretty(&outbox)?,
+        )?;
+
+        let log: String = self
+            .debug_log
+            .iter()
+            .map(|message| format!("{}\n", message))
+            .collect();
+        fs::write(dir.join(DEBUG_LOG_FILE), log)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct OutboxLevel {
+    level: i32,
+    outputs: Vec<String>,
+}
+
+/// Replay every level of `inbox`, calling `kernel_next` whenever the host would call
+/// the kernel.
+///
+/// The first level of the inbox is added to `state`, which is then driven by a
+/// [`MockRuntime`] until the last level of the inbox has been run.
+///
+/// # Panics
+/// Panics if the kernel traps, or does not read every input of a level before the
+/// next level is reached.
+pub fn replay(
+    mut state: HostState,
+    inbox: &Inbox,
+    kernel_next: impl FnMut(&mut MockHost) + 'static,
+) -> Replay {
+    reset_debug_log();
+
+    if let (Some(first), Some(last)) = (inbox.levels.first(), inbox.levels.last()) {
+        state.set_ready_for_input(first.level);
+
+        let mut runtime = MockRuntime::new(MockHost::from(state), kernel_next);
+        for level in inbox.levels.iter() {
+            runtime
+                .add_inputs(level.level, level.messages.iter().map(InboxInput::to_input));
+        }
+        runtime.run_until(last.level);
+
+        state = runtime.into_host().into_inner();
+    }
+
+    Replay {
+        state,
+        debug_log: debug_log(),
+    }
+}
+
+/// Replay the inbox stored in `inbox_file` from [`HostState::default`], and write the
+/// result into `output_dir`.
+///
+/// See [`Replay::write_to`].
+pub fn replay_file(
+    inbox_file: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    kernel_next: impl FnMut(&mut MockHost) + 'static,
+) -> Result<Replay, ReplayError> {
+    let inbox = Inbox::load(inbox_file)?;
+
+    let replay = replay(HostState::default(), &inbox, kernel_next);
+    replay.write_to(output_dir)?;
+
+    Ok(replay)
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    use serde::de::Error;
+
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(D::Error::custom(format!("odd length hex string {:?}", hex)));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| D::Error::custom(format!("invalid hex string {:?}", hex)))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use host::input::Input;
+    use host::path::RefPath;
+    use host::rollup_core::{RawRollupCore, MAX_INPUT_MESSAGE_SIZE};
+    use host::runtime::Runtime;
+
+    const COUNT: RefPath = RefPath::assert_from(b"/count");
+
+    // A directory unique to the test process, removed when dropped - even if the test
+    // panics.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let name = format!("{}_{}", name, std::process::id());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Echo each message to the outbox, counting the messages read in durable storage.
+    fn echo_kernel(host: &mut MockHost) {
+        while let Some(input) = Runtime::read_input(host, MAX_INPUT_MESSAGE_SIZE) {
+            let payload = match input {
+                Input::Message(message) => message.as_ref().to_vec(),
+                Input::Slot(slot) => slot.as_ref().to_vec(),
+            };
+            Runtime::write_output(host, payload.as_slice()).unwrap();
+
+            let debug = format!("read {} bytes", payload.len());
+            unsafe { MockHost::write_debug(debug.as_ptr(), debug.len()) };
+
+            let count = match Runtime::store_has(host, &COUNT) {
+                Some(_) => {
+                    let bytes = Runtime::store_read(host, &COUNT, 0, 4).unwrap();
+                    u32::from_le_bytes(bytes.try_into().unwrap())
+                }
+                None => 0,
+            };
+            Runtime::store_write(host, &COUNT, &(count + 1).to_le_bytes(), 0).unwrap();
+        }
+    }
+
+    #[test]
+    fn inbox_from_json() {
+        // Arrange
+        let json = r#"[
+            { "level": 1, "messages": ["00ff", { "slot": "0102" }] },
+            { "level": 4, "messages": [] }
+        ]"#;
+
+        // Act
+        let inbox = Inbox::from_json(json).unwrap();
+
+        // Assert
+        let expected = vec![
+            InboxLevel {
+                level: 1,
+                messages: vec![
+                    InboxInput::Message(vec![0, 255]),
+                    InboxInput::Slot { slot: vec![1, 2] },
+                ],
+            },
+            InboxLevel {
+                level: 4,
+                messages: vec![],
+            },
+        ];
+        assert_eq!(expected.as_slice(), inbox.levels());
+    }
+
+    #[test]
+    fn inbox_levels_not_increasing() {
+        // Arrange
+        let json = r#"[
+            { "level": 2, "messages": ["00"] },
+            { "level": 2, "messages": ["01"] }
+        ]"#;
+
+        // Act
+        let result = Inbox::from_json(json);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(ReplayError::LevelsNotIncreasing {
+                previous: 2,
+                level: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn replay_drains_inbox() {
+        // Arrange
+        let inbox = Inbox::from_json(
+            r#"[
+                { "level": 1, "messages": ["01", "0202"] },
+                { "level": 3, "messages": ["030303"] }
+            ]"#,
+        )
+        .unwrap();
+
+        // Act
+        let replay = replay(HostState::default(), &inbox, echo_kernel);
+
+        // Assert
+        let outbox: Vec<(i32, Vec<Vec<u8>>)> = replay.outbox().into_iter().collect();
+        assert_eq!(
+            vec![(1, vec![vec![1], vec![2, 2]]), (3, vec![vec![3, 3, 3]])],
+            outbox
+        );
+
+        let store = replay.durable_store();
+        assert_eq!(Some(&3_u32.to_le_bytes().to_vec()), store.get("/count"));
+
+        assert_eq!(
+            vec!["read 1 bytes", "read 2 bytes", "read 3 bytes"],
+            replay.debug_log
+        );
+    }
+
+    #[test]
+    fn replay_writes_results() {
+        // Arrange
+        let dir = TempDir::new("mock_runtime_replay_writes_results");
+        let inbox =
+            Inbox::from_json(r#"[{ "level": 0, "messages": ["cafe"] }]"#).unwrap();
+        let replay = replay(HostState::default(), &inbox, echo_kernel);
+
+        // Act
+        replay.write_to(&dir.0).unwrap();
+
+        // Assert
+        let store = fs::read_to_string(dir.0.join(STORE_FILE)).unwrap();
+        let store: BTreeMap<String, String> = serde_json::from_str(&store).unwrap();
+        assert_eq!(Some(&"01000000".to_string()), store.get("/count"));
+
+        let outbox = fs::read_to_string(dir.0.join(OUTBOX_FILE)).unwrap();
+        assert!(outbox.contains("cafe"));
+
+        let log = fs::read_to_string(dir.0.join(DEBUG_LOG_FILE)).unwrap();
+        assert_eq!("read 2 bytes\n", log);
+    }
+}
@@ -0,0 +1,380 @@
+//! Scheduler of the mock runtime - calls the kernel as the PVM would.
+//!
+//! [`MockRuntime`] follows each [`YieldStep`] returned by [`HostState::handle_yield`]:
+//! the kernel is called on every checkpoint of a level, rebooted when it requests it,
+//! and given the inputs added to the runtime when their level is reached.
+use std::collections::BTreeMap;
+
+use host::path::{Path, PATH_KERNEL_NEXT};
+use host::rollup_core::{Input, ValueType};
+
+use crate::host::MockHost;
+use crate::state::merkle::NodeHash;
+use crate::state::{
+    HostState, InputConsuming, InputLevel, Reboot, YieldStep, INPUT_CONSUMING, REBOOT,
+};
+
+/// The kernel calls made by the runtime at a level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelReport {
+    /// Number of times the kernel was called.
+    pub kernel_calls: usize,
+    /// Number of reboots requested by the kernel.
+    pub reboots: usize,
+    /// Ticks charged over every kernel call - see [`CostModel`](crate::cost::CostModel).
+    pub ticks: u64,
+    /// Root hash of durable storage once the level is finished - see
+    /// [`HostState::state_root`].
+    pub state_root: Option<NodeHash>,
+}
+
+/// Runtime driving a kernel over a [`MockHost`].
+///
+/// ```markdown
+/// /durable/kernel/next is set after a kernel call
+/// -----------------------------------------------
+/// Remove /durable/kernel/next
+/// /reboot := 1
+/// ```
+///
+/// A reboot takes effect on the next kernel call, and does not give the kernel any
+/// additional checkpoints within a level.
+pub struct MockRuntime {
+    host: MockHost,
+    kernel_next: Box<dyn FnMut(&mut MockHost)>,
+    inputs: BTreeMap<InputLevel, Vec<(Input, Vec<u8>)>>,
+}
+
+impl MockRuntime {
+    /// Create a runtime calling `kernel_next` over `host`.
+    pub fn new(host: MockHost, kernel_next: impl FnMut(&mut MockHost) + 'static) -> Self {
+        Self {
+            host,
+            kernel_next: Box::new(kernel_next),
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    /// The host of the kernel.
+    pub fn host(&self) -> &MockHost {
+        &self.host
+    }
+
+    /// The host of the kernel, mutably.
+    pub fn host_mut(&mut self) -> &mut MockHost {
+        &mut self.host
+    }
+
+    /// Consumes the runtime, returning its host.
+    pub fn into_host(self) -> MockHost {
+        self.host
+    }
+
+    /// The level the host is at.
+    pub fn level(&self) -> InputLevel {
+        self.host.input_level()
+    }
+
+    /// Add inputs to the inbox at `level`, read by the kernel once `level` is reached.
+    ///
+    /// # Panics
+    /// Panics if the host is past `level`, or has already started running it.
+    pub fn add_inputs(
+        &mut self,
+        level: InputLevel,
+        inputs: impl IntoIterator<Item = (Input, Vec<u8>)>,
+    ) {
+        let current = self.level();
+        let consuming = self
+            .host
+            .as_mut()
+            .store
+            .maybe_get_value::<InputConsuming>(INPUT_CONSUMING)
+            .unwrap_or(false);
+
+        if level < current || level == current && !consuming {
+            panic!(
+                "Attempted to add inputs at level {}, but host is at level {}",
+                level, current
+            );
+        }
+
+        self.inputs.entry(level).or_default().extend(inputs);
+    }
+
+    /// Run the kernel over every level up to, and including, `level`.
+    ///
+    /// Returns the kernel calls made at each level, and the state root reached at the
+    /// end of each finished level. A level at which no inputs were
+    /// added is given an empty inbox - the kernel must still read it in full.
+    ///
+    /// # Panics
+    /// Panics if the kernel traps, does not read every input of a level before the next
+    /// level is reached, or is called more than once per checkpoint of a level. A kernel
+    /// call using more ticks than allowed by the host's
+    /// [`CostModel`](crate::cost::CostModel) traps.
+    pub fn run_until(&mut self, level: InputLevel) -> BTreeMap<InputLevel, LevelReport> {
+        let mut reports: BTreeMap<InputLevel, LevelReport> = BTreeMap::new();
+
+        while self.level() <= level {
+            let previous = self.level();
+
+            match self.host.as_mut().handle_yield() {
+                YieldStep::HandleYield => (),
+                YieldStep::Reboot => {
+                    self.host.as_mut().store.delete_value(REBOOT);
+
+                    let current = self.level();
+                    reports.entry(current).or_default().reboots += 1;
+                }
+                YieldStep::Trampoline => {
+                    let current = self.level();
+                    reports.entry(current).or_default().kernel_calls += 1;
+
+                    self.host.start_kernel_call();
+                    (self.kernel_next)(&mut self.host);
+
+                    let ticks = self.host.ticks();
+                    reports.entry(current).or_default().ticks += ticks;
+
+                    self.handle_reboot_request();
+                }
+                YieldStep::InputTicks(at_level) => {
+                    let inputs = self.inputs.remove(&at_level).unwrap_or_default();
+
+                    self.host.as_mut().add_next_inputs(at_level, inputs.iter());
+                }
+                YieldStep::MarkLevelForInput(_) => {
+                    // levels past `level` are only marked by a later run, so that
+                    // inputs may still be added to the levels in between.
+                    let next = match self.inputs.keys().next() {
+                        Some(next) => InputLevel::min(*next, level),
+                        None => level,
+                    };
+
+                    self.host.as_mut().mark_level_for_input(next);
+                }
+            }
+
+            if self.level() != previous {
+                let state_root = self.host.as_mut().state_root();
+                reports.entry(previous).or_default().state_root = Some(state_root);
+            }
+        }
+
+        reports
+    }
+
+    // A reboot is requested by writing to `/kernel/next`.
+    fn handle_reboot_request(&mut self) {
+        let state: &mut HostState = self.host.as_mut();
+
+        if let ValueType::None = state.handle_store_has(PATH_KERNEL_NEXT.as_bytes()) {
+            return;
+        }
+
+        state.handle_store_delete(PATH_KERNEL_NEXT.as_bytes());
+        state.store.set_value::<Reboot>(REBOOT, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::{CostModel, HostCallCost};
+    use crate::state::CHECKPOINTS_PER_LEVEL;
+    use host::input::Input as KernelInput;
+    use host::path::PATH_KERNEL_BOOT;
+    use host::rollup_core::MAX_INPUT_MESSAGE_SIZE;
+    use host::runtime::Runtime;
+
+    // Read every input of the level, echoing each to the outbox.
+    fn echo_kernel(host: &mut MockHost) {
+        while let Some(input) = Runtime::read_input(host, MAX_INPUT_MESSAGE_SIZE) {
+            let payload = match input {
+                KernelInput::Message(message) => message.as_ref().to_vec(),
+                KernelInput::Slot(slot) => slot.as_ref().to_vec(),
+            };
+            Runtime::write_output(host, payload.as_slice()).unwrap();
+        }
+    }
+
+    // Read a single input per call, requesting a reboot after each.
+    fn reboot_kernel(host: &mut MockHost) {
+        if Runtime::read_input(host, MAX_INPUT_MESSAGE_SIZE).is_some() {
+            Runtime::store_write(host, &PATH_KERNEL_NEXT, PATH_KERNEL_BOOT.as_bytes(), 0)
+                .unwrap();
+        }
+    }
+
+    // Read every input of the level, requesting a reboot after every call.
+    fn always_reboot_kernel(host: &mut MockHost) {
+        echo_kernel(host);
+        Runtime::store_write(host, &PATH_KERNEL_NEXT, PATH_KERNEL_BOOT.as_bytes(), 0)
+            .unwrap();
+    }
+
+    fn outputs_at(runtime: &mut MockRuntime, level: InputLevel) -> usize {
+        let prefix = format!("/output/{}/", level);
+
+        runtime
+            .host_mut()
+            .as_mut()
+            .store
+            .list_paths()
+            .filter(|path| path.starts_with(&prefix))
+            .count()
+    }
+
+    #[test]
+    fn run_until_reports_kernel_calls() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), echo_kernel);
+        runtime.add_inputs(2, vec![(Input::MessageData, vec![1; 10])]);
+
+        // Act
+        let reports = runtime.run_until(3);
+
+        // Assert
+        assert_eq!(
+            vec![0, 1, 2, 3],
+            reports.keys().copied().collect::<Vec<_>>()
+        );
+        for report in reports.values() {
+            assert_eq!(CHECKPOINTS_PER_LEVEL, report.kernel_calls);
+            assert_eq!(0, report.reboots);
+            assert!(report.state_root.is_some());
+        }
+
+        assert_eq!(4, runtime.level());
+        assert_eq!(1, outputs_at(&mut runtime, 2));
+    }
+
+    #[test]
+    fn run_until_continues_from_previous_run() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), echo_kernel);
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![1; 10])]);
+        runtime.run_until(1);
+
+        runtime.add_inputs(
+            4,
+            vec![
+                (Input::MessageData, vec![2; 10]),
+                (Input::MessageData, vec![3; 10]),
+            ],
+        );
+
+        // Act
+        let reports = runtime.run_until(4);
+
+        // Assert
+        assert_eq!(vec![2, 3, 4], reports.keys().copied().collect::<Vec<_>>());
+        assert_eq!(1, outputs_at(&mut runtime, 1));
+        assert_eq!(2, outputs_at(&mut runtime, 4));
+    }
+
+    #[test]
+    fn run_until_reboots_kernel() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), reboot_kernel);
+        runtime.add_inputs(
+            1,
+            vec![
+                (Input::MessageData, vec![1; 10]),
+                (Input::MessageData, vec![2; 10]),
+            ],
+        );
+
+        // Act
+        let reports = runtime.run_until(1);
+
+        // Assert
+        let report = reports.get(&1).unwrap();
+        assert_eq!(CHECKPOINTS_PER_LEVEL, report.kernel_calls);
+        assert_eq!(2, report.reboots);
+
+        let state: &mut HostState = runtime.host_mut().as_mut();
+        assert_eq!(
+            ValueType::None,
+            state.handle_store_has(PATH_KERNEL_NEXT.as_bytes())
+        );
+        assert!(state.store.maybe_get_value::<Reboot>(REBOOT).is_none());
+    }
+
+    #[test]
+    fn run_until_bounds_kernel_calls_by_checkpoints() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), always_reboot_kernel);
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![1; 10])]);
+
+        // Act
+        let reports = runtime.run_until(1);
+
+        // Assert
+        let report = reports.get(&1).unwrap();
+        assert_eq!(CHECKPOINTS_PER_LEVEL, report.kernel_calls);
+        assert_eq!(CHECKPOINTS_PER_LEVEL, report.reboots);
+        assert_eq!(2, runtime.level());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempted to add inputs at level 1")]
+    fn add_inputs_at_past_level() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), echo_kernel);
+        runtime.run_until(1);
+
+        // Act
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![1; 10])]);
+    }
+
+    #[test]
+    fn run_until_reports_state_root() {
+        // Arrange
+        let mut runtime = MockRuntime::new(MockHost::default(), reboot_kernel);
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![1; 10])]);
+
+        // Act
+        let reports = runtime.run_until(1);
+
+        // Assert
+        let state_root = runtime.host_mut().as_mut().state_root();
+        assert_eq!(Some(state_root), reports.get(&1).unwrap().state_root);
+    }
+
+    #[test]
+    fn run_until_reports_ticks() {
+        // Arrange
+        let mut host = MockHost::default();
+        host.set_cost_model(CostModel {
+            kernel_call: 10,
+            ..CostModel::free()
+        });
+        let mut runtime = MockRuntime::new(host, echo_kernel);
+
+        // Act
+        let reports = runtime.run_until(0);
+
+        // Assert
+        let report = reports.get(&0).unwrap();
+        assert_eq!(10 * report.kernel_calls as u64, report.ticks);
+    }
+
+    #[test]
+    #[should_panic(expected = "TickLimitExceeded")]
+    fn run_until_traps_over_tick_limit() {
+        // Arrange
+        let mut host = MockHost::default();
+        host.set_cost_model(CostModel {
+            tick_limit: 100,
+            write_output: HostCallCost::new(0, 10),
+            ..CostModel::free()
+        });
+        let mut runtime = MockRuntime::new(host, echo_kernel);
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![1; 11])]);
+
+        // Act
+        runtime.run_until(1);
+    }
+}
@@ -0,0 +1,365 @@
+//! Merkle tree of durable storage.
+//!
+//! Each step of a [`Path`] is a node of the tree, so that a path of
+//! [`Path::len_steps`] steps is held by a node at that depth. The hash of a node commits
+//! to its value and to every one of its children, ordered by step:
+//!
+//! ```markdown
+//! value_hash := blake2b(value)
+//! node_hash  := blake2b(NODE_TAG
+//!                       ++ (0x00 | 0x01 ++ value_hash)
+//!                       ++ (len(step) ++ step ++ node_hash(child))*)
+//! ```
+//!
+//! The root hash of the tree is therefore deterministic, and any value may be proven to
+//! be included in the tree with an [`InclusionProof`].
+//!
+//! The tree holds the hash of each value, rather than the value itself - the values
+//! are held by the [`Store`], which updates its tree as durable values change. The
+//! hash of a node is kept until a value under it changes, so only the nodes along
+//! changed paths are hashed again by the next [`MerkleTree::root_hash`].
+//!
+//! [`Store`]: super::store::Store
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use crypto::blake2b::digest_256;
+use host::path::{Path, PATH_SEPARATOR};
+use host::rollup_core::PREIMAGE_HASH_SIZE;
+
+/// The blake2b hash of a node of the tree.
+pub type NodeHash = [u8; PREIMAGE_HASH_SIZE];
+
+const NODE_TAG: u8 = 0;
+const NO_VALUE_TAG: u8 = 0;
+const VALUE_TAG: u8 = 1;
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    value_hash: Option<NodeHash>,
+    children: BTreeMap<String, Node>,
+    // cleared whenever a value at, or under, the node changes
+    hash: Cell<Option<NodeHash>>,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.value_hash == other.value_hash && self.children == other.children
+    }
+}
+
+impl Eq for Node {}
+
+impl Node {
+    fn hash(&self) -> NodeHash {
+        if let Some(hash) = self.hash.get() {
+            return hash;
+        }
+
+        let children = self
+            .children
+            .iter()
+            .map(|(step, child)| (step.as_str(), child.hash()));
+
+        let hash = node_hash(self.value_hash.as_ref(), children);
+        self.hash.set(Some(hash));
+        hash
+    }
+
+    // Remove the value at `steps` under the node, along with any node left empty.
+    fn remove(&mut self, steps: &[&str]) -> Option<NodeHash> {
+        let removed = match steps.split_first() {
+            None => self.value_hash.take(),
+            Some((step, rest)) => {
+                let child = self.children.get_mut(*step)?;
+                let removed = child.remove(rest);
+
+                if child.value_hash.is_none() && child.children.is_empty() {
+                    self.children.remove(*step);
+                }
+                removed
+            }
+        };
+
+        if removed.is_some() {
+            self.hash.set(None);
+        }
+        removed
+    }
+
+    fn child_hashes(&self) -> Vec<(String, NodeHash)> {
+        self.children
+            .iter()
+            .map(|(step, child)| (step.clone(), child.hash()))
+            .collect()
+    }
+
+    // The hashes of the children, except the child at `step`.
+    fn siblings(&self, step: &str) -> Vec<(String, NodeHash)> {
+        let mut siblings = self.child_hashes();
+        siblings.retain(|(sibling, _)| sibling != step);
+        siblings
+    }
+}
+
+/// The hashes of the values of durable storage, held as a tree of path steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerkleTree {
+    root: Node,
+}
+
+impl MerkleTree {
+    /// Set the value at `path`, creating any missing node on the way.
+    pub fn insert<T: Path>(&mut self, path: &T, value: &[u8]) {
+        let mut node = &mut self.root;
+
+        for step in steps(path) {
+            node.hash.set(None);
+            node = node.children.entry(step.to_string()).or_default();
+        }
+
+        node.hash.set(None);
+        node.value_hash = Some(hash(value));
+    }
+
+    /// Remove the value at `path`, returning its hash - if there was such a value.
+    pub fn remove<T: Path>(&mut self, path: &T) -> Option<NodeHash> {
+        let steps: Vec<&str> = steps(path).collect();
+
+        self.root.remove(steps.as_slice())
+    }
+
+    /// The hash of the value at `path`, if any.
+    pub fn value_hash<T: Path>(&self, path: &T) -> Option<NodeHash> {
+        self.node(path).and_then(|node| node.value_hash)
+    }
+
+    /// The hash of the root of the tree.
+    pub fn root_hash(&self) -> NodeHash {
+        self.root.hash()
+    }
+
+    /// Prove that the value at `path` is included in the tree - if there is such a value.
+    pub fn prove<T: Path>(&self, path: &T) -> Option<InclusionProof> {
+        let steps: Vec<String> = steps(path).map(str::to_string).collect();
+
+        let mut ancestors = Vec::with_capacity(steps.len());
+        let mut node = &self.root;
+
+        for step in steps.iter() {
+            ancestors.push(ProofNode {
+                value_hash: node.value_hash,
+                siblings: node.siblings(step),
+            });
+            node = node.children.get(step)?;
+        }
+
+        // ancestors are given from the parent of the proven node up to the root
+        ancestors.reverse();
+
+        Some(InclusionProof {
+            steps,
+            value_hash: node.value_hash?,
+            children: node.child_hashes(),
+            ancestors,
+        })
+    }
+
+    fn node<T: Path>(&self, path: &T) -> Option<&Node> {
+        steps(path).try_fold(&self.root, |node, step| node.children.get(step))
+    }
+}
+
+// A node on the path of a proven value, without its child on that path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProofNode {
+    value_hash: Option<NodeHash>,
+    siblings: Vec<(String, NodeHash)>,
+}
+
+/// Proof that a value is held at a path, in a tree of a given root hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InclusionProof {
+    steps: Vec<String>,
+    value_hash: NodeHash,
+    children: Vec<(String, NodeHash)>,
+    ancestors: Vec<ProofNode>,
+}
+
+impl InclusionProof {
+    /// Returns whether the proof shows that `value` is held at `path`, in the tree of
+    /// the given `root` hash.
+    pub fn verify<T: Path>(&self, root: &NodeHash, path: &T, value: &[u8]) -> bool {
+        if hash(value) != self.value_hash
+            || !steps(path).eq(self.steps.iter().map(String::as_str))
+            || self.steps.len() != self.ancestors.len()
+        {
+            return false;
+        }
+
+        let children = self
+            .children
+            .iter()
+            .map(|(step, hash)| (step.as_str(), *hash));
+        let mut hash = node_hash(Some(&self.value_hash), children);
+
+        for (step, ancestor) in self.steps.iter().rev().zip(self.ancestors.iter()) {
+            if ancestor.siblings.iter().any(|(sibling, _)| sibling == step) {
+                return false;
+            }
+
+            let mut children: Vec<(&str, NodeHash)> = ancestor
+                .siblings
+                .iter()
+                .map(|(step, hash)| (step.as_str(), *hash))
+                .collect();
+            children.push((step.as_str(), hash));
+            children.sort_unstable();
+
+            hash = node_hash(ancestor.value_hash.as_ref(), children.into_iter());
+        }
+
+        &hash == root
+    }
+}
+
+fn steps<T: Path>(path: &T) -> impl Iterator<Item = &str> {
+    let steps = std::str::from_utf8(path.as_bytes())
+        .expect("A valid path is valid utf8")
+        .split(char::from(PATH_SEPARATOR))
+        .skip(1);
+    debug_assert_eq!(steps.clone().count(), path.len_steps());
+
+    steps
+}
+
+fn hash(bytes: &[u8]) -> NodeHash {
+    digest_256(bytes)
+        .expect("hashing failed")
+        .try_into()
+        .expect("hash is incorrect length")
+}
+
+fn node_hash<'a>(
+    value_hash: Option<&NodeHash>,
+    children: impl Iterator<Item = (&'a str, NodeHash)>,
+) -> NodeHash {
+    let mut bytes = vec![NODE_TAG];
+
+    match value_hash {
+        Some(value_hash) => {
+            bytes.push(VALUE_TAG);
+            bytes.extend_from_slice(value_hash);
+        }
+        None => bytes.push(NO_VALUE_TAG),
+    }
+
+    for (step, child) in children {
+        // steps are at most `PATH_MAX_SIZE` bytes, which fits in a byte
+        bytes.push(step.len() as u8);
+        bytes.extend_from_slice(step.as_bytes());
+        bytes.extend_from_slice(&child);
+    }
+
+    hash(bytes.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use host::path::RefPath;
+
+    const RED: RefPath = RefPath::assert_from(b"/tickets/red");
+    const BLUE: RefPath = RefPath::assert_from(b"/tickets/blue");
+    const TICKETS: RefPath = RefPath::assert_from(b"/tickets");
+
+    fn tree() -> MerkleTree {
+        let mut tree = MerkleTree::default();
+        tree.insert(&RED, &[1]);
+        tree.insert(&BLUE, &[2]);
+        tree.insert(&TICKETS, &[3]);
+        tree
+    }
+
+    #[test]
+    fn root_hash_is_deterministic() {
+        // Arrange
+        let mut reversed = MerkleTree::default();
+        reversed.insert(&TICKETS, &[3]);
+        reversed.insert(&BLUE, &[2]);
+        reversed.insert(&RED, &[1]);
+
+        // Act
+        let root = tree().root_hash();
+
+        // Assert
+        assert_eq!(reversed.root_hash(), root);
+
+        reversed.insert(&RED, &[4]);
+        assert_ne!(reversed.root_hash(), root);
+    }
+
+    #[test]
+    fn remove_prunes_empty_nodes() {
+        // Arrange
+        let mut tree = tree();
+        tree.root_hash();
+
+        let mut expected = MerkleTree::default();
+        expected.insert(&BLUE, &[2]);
+
+        // Act
+        let removed = [tree.remove(&RED), tree.remove(&TICKETS), tree.remove(&RED)];
+
+        // Assert
+        assert_eq!([Some(hash(&[1])), Some(hash(&[3])), None], removed);
+        assert_eq!(expected, tree);
+        assert_eq!(expected.root_hash(), tree.root_hash());
+    }
+
+    #[test]
+    fn inclusion_proof_verifies() {
+        // Arrange
+        let tree = tree();
+        let root = tree.root_hash();
+
+        for (path, value) in [(RED, [1]), (BLUE, [2]), (TICKETS, [3])] {
+            // Act
+            let proof = tree.prove(&path).unwrap();
+
+            // Assert
+            assert!(proof.verify(&root, &path, &value));
+        }
+    }
+
+    #[test]
+    fn inclusion_proof_rejects_other_value() {
+        // Arrange
+        let tree = tree();
+        let root = tree.root_hash();
+        let proof = tree.prove(&RED).unwrap();
+
+        // Act
+        let other_value = proof.verify(&root, &RED, &[2]);
+        let other_path = proof.verify(&root, &BLUE, &[1]);
+        let other_root = proof.verify(&[0; PREIMAGE_HASH_SIZE], &RED, &[1]);
+
+        // Assert
+        assert!(!other_value);
+        assert!(!other_path);
+        assert!(!other_root);
+    }
+
+    #[test]
+    fn prove_missing_value() {
+        // Arrange
+        let mut tree = MerkleTree::default();
+        tree.insert(&RED, &[1]);
+
+        // Act
+        let proof = tree.prove(&TICKETS);
+
+        // Assert
+        assert!(proof.is_none());
+    }
+}
@@ -16,7 +16,9 @@ use crate::trap::{
     TrapCondition::*,
 };
 
-pub(crate) mod store;
+pub mod merkle;
+pub mod store;
+use self::merkle::NodeHash;
 use self::store::Store;
 
 pub(crate) type InputLevel = i32;
@@ -276,6 +278,16 @@ impl HostState {
             && is_consuming
     }
 
+    /// The root hash of durable storage - see [`Store::durable_tree`].
+    pub fn state_root(&self) -> NodeHash {
+        self.store.durable_tree().root_hash()
+    }
+
+    /// The level the host is at.
+    pub(crate) fn input_level(&self) -> InputLevel {
+        self.store.get_value(INPUT_LEVEL)
+    }
+
     fn checkpoints(&self) -> Checkpoints {
         let checkpoints: Checkpoints = self.store.get_value(CHECKPOINTS);
 
@@ -736,8 +748,20 @@ impl HostState {
         }
     }
 
+    // The bytes held at, or under, `prefix` - counting both the subkeys and the values.
+    pub(crate) fn subtree_size(&self, prefix: &[u8]) -> usize {
+        let durable_prefix = with_durable(prefix);
+
+        self.subkeys_of(prefix)
+            .map(|subkey| {
+                let key = format!("{}{}", durable_prefix, subkey);
+                subkey.len() + self.store.as_ref().get(&key).map_or(0, Vec::len)
+            })
+            .sum()
+    }
+
     // Return an iterator over the subkeys of the given prefix.
-    fn subkeys_of(&self, prefix: &[u8]) -> impl Iterator<Item = &str> {
+    pub(crate) fn subkeys_of(&self, prefix: &[u8]) -> impl Iterator<Item = &str> {
         use host::path::PATH_SEPARATOR;
 
         let prefix = with_durable(prefix);
@@ -1,16 +1,34 @@
 //! Mock runtime store - the container for host state.
-use crate::trap::{trap, HostError::*, TrapCondition::*};
+//!
+//! The store can be snapshotted and restored, and two stores compared with [`diff`] -
+//! allowing tests to assert exactly which keys a kernel call touched.
+use super::merkle::MerkleTree;
+use super::{
+    Checkpoints, InputConsuming, InputId, InputLevel, OutputId, Reboot, CHECKPOINTS,
+    INPUT_CONSUMING, INPUT_ID, INPUT_LEVEL, OUTPUT_ID, REBOOT,
+};
+use crate::trap::{trap, HostError::*, KernelError, TrapCondition::*};
 use crypto::blake2b::digest_256;
+use host::path::{Path, RefPath, DURABLE_STORAGE_PREFIX};
 use host::rollup_core::{Input, PREIMAGE_HASH_SIZE};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+/// Key-value store of the host state, and of the preimages it may reveal.
+///
+/// The hashes of the values of durable storage are also held as a [`MerkleTree`],
+/// updated along with the values.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Store {
     inner: HashMap<String, Vec<u8>>,
+    durable: MerkleTree,
     preimages: HashMap<[u8; PREIMAGE_HASH_SIZE], Vec<u8>>,
 }
 
 impl Store {
+    /// Get the value at `path`.
+    ///
+    /// # Traps
+    /// Traps if there is no value at `path`, or it is not a valid `T`.
     pub fn get_value<T: StoreValue>(&self, path: &str) -> T {
         let value = self
             .inner
@@ -20,14 +38,25 @@ impl Store {
         T::from_bytes(value)
     }
 
+    /// Get the value at `path`, if any.
     pub fn maybe_get_value<T: StoreValue>(&self, path: &str) -> Option<T> {
         self.inner.get(path).map(|v| T::from_bytes(v))
     }
 
+    /// Set the value at `path`, replacing any previous value.
     pub fn set_value<T: StoreValue>(&mut self, path: &str, value: T) {
-        self.inner.insert(path.into(), value.to_bytes());
+        let value = value.to_bytes();
+
+        if let Some(durable) = durable_path(path) {
+            self.durable.insert(&durable, value.as_slice());
+        }
+        self.inner.insert(path.into(), value);
     }
 
+    /// Update the value at `path` with `update_fn`.
+    ///
+    /// # Traps
+    /// Traps if there is no value at `path`.
     pub fn update_value<T: StoreValue>(
         &mut self,
         path: &str,
@@ -37,26 +66,49 @@ impl Store {
             let value = T::from_bytes(bytes);
             let mut value = update_fn(value).to_bytes();
 
+            if let Some(durable) = durable_path(path) {
+                self.durable.insert(&durable, value.as_slice());
+            }
             std::mem::swap(bytes, &mut value);
         } else {
             trap(HostFailure(ExistingPathNotFound(path.to_string())));
         }
     }
 
+    /// Delete the value at `path`.
+    ///
+    /// # Traps
+    /// Traps if there is no value at `path`.
     pub fn delete_value(&mut self, path: &str) {
         if self.inner.remove(path).is_none() {
             trap(HostFailure(ExistingPathNotFound(path.into())))
         }
+
+        if let Some(durable) = durable_path(path) {
+            self.durable.remove(&durable);
+        }
     }
 
+    /// Returns whether there is a value at `path`.
     pub fn has_entry(&self, path: &str) -> bool {
         self.inner.contains_key(path)
     }
 
+    /// Iterate over every path holding a value, in no particular order.
     pub fn list_paths(&self) -> impl Iterator<Item = &String> {
         self.inner.keys()
     }
 
+    /// The hashes of durable storage, as a tree of path steps - by path as seen by the
+    /// kernel.
+    pub fn durable_tree(&self) -> &MerkleTree {
+        &self.durable
+    }
+
+    /// Store a preimage, returning its hash.
+    ///
+    /// # Panics
+    /// Panics if the preimage is larger than 4 KB.
     pub fn add_preimage(&mut self, preimage: Vec<u8>) -> [u8; PREIMAGE_HASH_SIZE] {
         if preimage.len() > 4096 {
             panic!("Preimage limited to 4 KB, got {}", preimage.len())
@@ -69,12 +121,195 @@ impl Store {
         hash
     }
 
+    /// Retrieve the preimage of `hash`.
+    ///
+    /// # Panics
+    /// Panics if no preimage of `hash` was stored.
     pub fn retrieve_preimage(&self, hash: &[u8; PREIMAGE_HASH_SIZE]) -> &[u8] {
         self.preimages
             .get(hash)
             .expect("Cannot retrieve preimage")
             .as_ref()
     }
+
+    /// Take a snapshot of the store, which it may later be restored to.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.clone())
+    }
+
+    /// Restore the store - including its preimages - to a previous `snapshot`.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        *self = snapshot.0.clone();
+    }
+}
+
+// The path of a value in durable storage, as seen by the kernel - without the
+// `/durable` prefix.
+//
+// Traps if the path is under `/durable`, but is not a valid path once without it.
+fn durable_path(path: &str) -> Option<RefPath<'_>> {
+    let prefix = std::str::from_utf8(DURABLE_STORAGE_PREFIX.as_bytes())
+        .expect("durable prefix is valid utf8");
+
+    let path = path
+        .strip_prefix(prefix)
+        .filter(|path| path.starts_with('/'))?;
+
+    match RefPath::try_from(path.as_bytes()) {
+        Ok(path) => Some(path),
+        Err(err) => trap(KernelFailure(KernelError::InvalidPath(err))),
+    }
+}
+
+/// A copy of the [`Store`] at a point in time - see [`Store::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot(Store);
+
+impl AsRef<Store> for Snapshot {
+    fn as_ref(&self) -> &Store {
+        &self.0
+    }
+}
+
+/// A value of the [`Store`], decoded when the type held at its path is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A flag, such as `/input/consuming`.
+    Bool(bool),
+    /// A single byte, such as `/reboot`.
+    Byte(u8),
+    /// A signed integer, such as `/input/level`.
+    I32(i32),
+    /// An unsigned integer, such as `/output/id`.
+    U32(u32),
+    /// A count, such as `/checkpoints`.
+    Usize(usize),
+    /// The type of an input, at `/input/<level>/<id>/type`.
+    Input(Input),
+    /// A value of unknown type - such as any value in durable storage.
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// Decode the value held at `path`.
+    ///
+    /// Values of the host state are decoded according to their type, while any other
+    /// value - or a value that is not a valid encoding of its type - is kept as bytes.
+    pub fn decode(path: &str, bytes: &[u8]) -> Self {
+        let steps: Vec<&str> = path.split('/').skip(1).collect();
+
+        let value = match (path, steps.as_slice()) {
+            (INPUT_LEVEL, _) => decode_sized::<InputLevel>(bytes).map(Value::I32),
+            (INPUT_ID, _) => decode_sized::<InputId>(bytes).map(Value::I32),
+            (OUTPUT_ID, _) => decode_sized::<OutputId>(bytes).map(Value::U32),
+            (CHECKPOINTS, _) => decode_sized::<Checkpoints>(bytes).map(Value::Usize),
+            (REBOOT, _) => decode_sized::<Reboot>(bytes).map(Value::Byte),
+            (INPUT_CONSUMING, _) => match bytes {
+                [b't'] | [b'f'] => Some(Value::Bool(InputConsuming::from_bytes(bytes))),
+                _ => None,
+            },
+            (_, ["input", _, "size"]) | (_, ["input", _, _, "n"]) => {
+                decode_sized::<i32>(bytes).map(Value::I32)
+            }
+            (_, ["input", _, _, "type"]) => match bytes {
+                [b'h'] | [b's'] => Some(Value::Input(Input::from_bytes(bytes))),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        value.unwrap_or_else(|| Value::Bytes(bytes.to_vec()))
+    }
+}
+
+// Decode a fixed-size integer, if `bytes` is of the expected size.
+fn decode_sized<T: StoreValue>(bytes: &[u8]) -> Option<T> {
+    if bytes.len() == std::mem::size_of::<T>() {
+        Some(T::from_bytes(bytes))
+    } else {
+        None
+    }
+}
+
+/// The differences between two stores - see [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StoreDiff {
+    /// Paths holding a value only in the later store.
+    pub added: BTreeMap<String, Value>,
+    /// Paths holding a value only in the earlier store.
+    pub removed: BTreeMap<String, Value>,
+    /// Paths holding different values, given as `(earlier, later)`.
+    pub changed: BTreeMap<String, (Value, Value)>,
+}
+
+impl StoreDiff {
+    /// Returns whether the stores hold the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Every path that was added, removed or changed.
+    pub fn paths(&self) -> BTreeSet<&str> {
+        self.added
+            .keys()
+            .chain(self.removed.keys())
+            .chain(self.changed.keys())
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The differences in durable storage only, by path as seen by the kernel - that
+    /// is, without the `/durable` prefix.
+    pub fn durable(&self) -> StoreDiff {
+        fn durable_only<V: Clone>(entries: &BTreeMap<String, V>) -> BTreeMap<String, V> {
+            let prefix = std::str::from_utf8(DURABLE_STORAGE_PREFIX.as_bytes())
+                .expect("durable prefix is valid utf8");
+
+            entries
+                .iter()
+                .filter_map(|(path, value)| {
+                    path.strip_prefix(prefix)
+                        .filter(|path| path.starts_with('/'))
+                        .map(|path| (path.to_string(), value.clone()))
+                })
+                .collect()
+        }
+
+        StoreDiff {
+            added: durable_only(&self.added),
+            removed: durable_only(&self.removed),
+            changed: durable_only(&self.changed),
+        }
+    }
+}
+
+/// Compare the values held by two stores - their preimages are not compared.
+pub fn diff(before: &Store, after: &Store) -> StoreDiff {
+    let mut diff = StoreDiff::default();
+
+    for (path, value) in before.inner.iter() {
+        match after.inner.get(path) {
+            None => {
+                diff.removed
+                    .insert(path.clone(), Value::decode(path, value));
+            }
+            Some(later) if later != value => {
+                diff.changed.insert(
+                    path.clone(),
+                    (Value::decode(path, value), Value::decode(path, later)),
+                );
+            }
+            Some(_) => (),
+        }
+    }
+
+    for (path, value) in after.inner.iter() {
+        if !before.inner.contains_key(path) {
+            diff.added.insert(path.clone(), Value::decode(path, value));
+        }
+    }
+
+    diff
 }
 
 impl AsRef<HashMap<String, Vec<u8>>> for Store {
@@ -83,8 +318,14 @@ impl AsRef<HashMap<String, Vec<u8>>> for Store {
     }
 }
 
+/// A value that may be held in the [`Store`].
 pub trait StoreValue {
+    /// Encode the value.
     fn to_bytes(self) -> Vec<u8>;
+    /// Decode the value.
+    ///
+    /// # Traps
+    /// Traps if `bytes` is not a valid encoding of the value.
     fn from_bytes(bytes: &[u8]) -> Self;
 }
 
@@ -183,3 +424,115 @@ impl StoreValue for Input {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::MockHost;
+    use crate::state::HostState;
+    use host::runtime::Runtime;
+
+    #[test]
+    fn snapshot_restore_roundtrip() {
+        // Arrange
+        let mut store = Store::default();
+        store.set_value("/a", vec![1, 2, 3]);
+        let snapshot = store.snapshot();
+
+        // Act
+        store.set_value("/a", vec![4]);
+        store.set_value("/b", vec![5]);
+        store.add_preimage(vec![6; 10]);
+        store.restore(&snapshot);
+
+        // Assert
+        assert_eq!(snapshot.as_ref(), &store);
+    }
+
+    #[test]
+    fn durable_tree_follows_store() {
+        // Arrange
+        const RED: RefPath = RefPath::assert_from(b"/tickets/red");
+        const BLUE: RefPath = RefPath::assert_from(b"/tickets/blue");
+
+        let mut store = Store::default();
+        store.set_value("/durable/tickets/red", vec![1]);
+        store.set_value("/durable/tickets/blue", vec![2]);
+        store.set_value("/input/level", 4_i32);
+        let root = store.durable_tree().root_hash();
+
+        let mut expected = MerkleTree::default();
+        expected.insert(&BLUE, &[3]);
+
+        // Act
+        store.update_value("/durable/tickets/blue", |_: Vec<u8>| vec![3]);
+        store.delete_value("/durable/tickets/red");
+
+        // Assert
+        assert_eq!(&expected, store.durable_tree());
+        assert_eq!(None, store.durable_tree().value_hash(&RED));
+        assert_ne!(root, store.durable_tree().root_hash());
+        assert_eq!(expected.root_hash(), store.durable_tree().root_hash());
+    }
+
+    #[test]
+    #[should_panic(expected = "InvalidPath")]
+    fn durable_tree_traps_on_invalid_path() {
+        // Arrange
+        let mut store = Store::default();
+
+        // Act
+        store.set_value("/durable/tickets/", vec![1]);
+    }
+
+    #[test]
+    fn diff_added_removed_changed() {
+        // Arrange
+        let mut before = Store::default();
+        before.set_value("/kept", vec![1]);
+        before.set_value("/removed", vec![2]);
+        before.set_value::<InputLevel>(INPUT_LEVEL, 4);
+
+        let mut after = before.clone();
+        after.delete_value("/removed");
+        after.set_value("/added", vec![3]);
+        after.set_value::<InputLevel>(INPUT_LEVEL, 5);
+
+        // Act
+        let diff = diff(&before, &after);
+
+        // Assert
+        let expected = StoreDiff {
+            added: [("/added".to_string(), Value::Bytes(vec![3]))].into(),
+            removed: [("/removed".to_string(), Value::Bytes(vec![2]))].into(),
+            changed: [(INPUT_LEVEL.to_string(), (Value::I32(4), Value::I32(5)))].into(),
+        };
+        assert_eq!(expected, diff);
+        assert!(super::diff(&after, &after).is_empty());
+    }
+
+    #[test]
+    fn diff_durable_keys_touched_by_kernel() {
+        // Arrange
+        const COUNTER: RefPath = RefPath::assert_from(b"/counter");
+        const UNTOUCHED: RefPath = RefPath::assert_from(b"/untouched");
+
+        let mut host = MockHost::from(HostState::default());
+        Runtime::store_write(&mut host, &UNTOUCHED, &[0], 0).unwrap();
+        let snapshot = host.as_mut().store.snapshot();
+
+        // Act
+        Runtime::store_write(&mut host, &COUNTER, &[1], 0).unwrap();
+        Runtime::write_output(&mut host, &[2]).unwrap();
+
+        // Assert
+        let diff = diff(snapshot.as_ref(), &host.as_mut().store);
+
+        assert_eq!(
+            BTreeSet::from(["/durable/counter", "/output/0/0", OUTPUT_ID]),
+            diff.paths()
+        );
+        assert_eq!((Value::U32(0), Value::U32(1)), diff.changed[OUTPUT_ID]);
+        assert_eq!(BTreeSet::from(["/counter"]), diff.durable().paths());
+    }
+}
@@ -4,13 +4,22 @@
 
 use host::path::PathError;
 
-/// Trap conditions are either caused by errors in the **Host** or **Kernel**.
+/// Trap conditions are either caused by errors in the **Host** or **Kernel**, or by the
+/// kernel running out of ticks.
 #[derive(Debug)]
 pub enum TrapCondition {
     /// Failure condition due to invalid assumption by the mock runtime.
     HostFailure(HostError),
     /// Failure condition due to incorrect kernel behaviour.
     KernelFailure(KernelError),
+    /// The kernel used more ticks in a single call than allowed by its
+    /// [`CostModel`](crate::cost::CostModel).
+    TickLimitExceeded {
+        /// The ticks available to each kernel call.
+        limit: u64,
+        /// The ticks charged to the kernel call, when trapping.
+        ticks: u64,
+    },
 }
 
 /// The mock runtime (the *host*) makes certain assumptions about its own behaviour.
@@ -46,6 +55,8 @@ pub enum KernelError {
     /// An offset into a value in durable storage was too large, compared to the size of
     /// the value in the store.
     OffsetOutOfBounds(usize, usize),
+    /// A wasm kernel failed to link against the host, or trapped while running.
+    WasmTrap(String),
     /// An index into the subkeys of a prefix was out of bounds.
     PrefixSubkeyIndexOutOfBounds {
         /// The prefix of the subkeys lookup.
@@ -0,0 +1,372 @@
+//! Validation of kernel modules against the rules of the PVM.
+//!
+//! A kernel that breaks any of these rules fails once deployed, rather than when
+//! tested against the [`MockHost`](crate::host::MockHost):
+//! - only functions of the `rollup_safe_core` host module - as declared in
+//!   [`host::rollup_core`] - may be imported.
+//! - `kernel_next` must be exported.
+//! - floating-point instructions are not deterministic, and are rejected.
+//! - start sections are not supported.
+//! - the module must fit in a single origination.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use wasmparser::{BinaryReaderError, ExternalKind, Operator, Parser, Payload, TypeRef};
+
+use crate::wasm::{HOST_MODULE, KERNEL_NEXT};
+
+/// Functions provided by the `rollup_safe_core` host module.
+pub const HOST_FUNCTIONS: [&str; 12] = [
+    "read_input",
+    "write_output",
+    "write_debug",
+    "store_has",
+    "store_read",
+    "store_write",
+    "store_delete",
+    "store_list_size",
+    "store_list_get",
+    "store_move",
+    "store_copy",
+    "reveal_preimage",
+];
+
+/// Maximum size of a kernel originated in a single operation, in bytes.
+pub const MAX_ORIGINATION_SIZE: usize = 32 * 1024;
+
+/// A rule of the PVM broken by a kernel module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// An import not provided by the PVM.
+    UnknownImport {
+        /// The module of the import.
+        module: String,
+        /// The name of the import.
+        name: String,
+    },
+    /// The module does not export a `kernel_next` function.
+    MissingKernelNext,
+    /// A function uses floating-point instructions.
+    FloatInstruction {
+        /// Index of the function.
+        function: u32,
+        /// Offset in the module of the first floating-point instruction of the function.
+        offset: usize,
+    },
+    /// The module has a start section.
+    StartSection {
+        /// Index of the start function.
+        function: u32,
+    },
+    /// The module is too large to be originated in a single operation.
+    CodeTooLarge {
+        /// Size of the module, in bytes.
+        size: usize,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownImport { module, name } => {
+                write!(f, "unknown import {}::{}", module, name)
+            }
+            Self::MissingKernelNext => write!(f, "missing export {}", KERNEL_NEXT),
+            Self::FloatInstruction { function, offset } => write!(
+                f,
+                "floating-point instruction in function {} at offset {:#x}",
+                function, offset
+            ),
+            Self::StartSection { function } => {
+                write!(f, "start section calling function {}", function)
+            }
+            Self::CodeTooLarge { size } => write!(
+                f,
+                "module of {} bytes exceeds origination limit of {} bytes",
+                size, MAX_ORIGINATION_SIZE
+            ),
+        }
+    }
+}
+
+/// Errors that may occur when reading a kernel module.
+#[derive(Debug)]
+pub enum ValidationError {
+    /// Failure reading the module from disk.
+    Io(std::io::Error),
+    /// The module is not valid wasm.
+    Parse(BinaryReaderError),
+}
+
+impl From<std::io::Error> for ValidationError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<BinaryReaderError> for ValidationError {
+    fn from(error: BinaryReaderError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+/// Check the kernel module `wasm` against the rules of the PVM, returning every rule
+/// broken.
+pub fn validate(wasm: &[u8]) -> Result<Vec<ValidationIssue>, ValidationError> {
+    let mut issues = Vec::new();
+    let mut has_kernel_next = false;
+    let mut imported_functions = 0;
+    let mut function = 0;
+
+    if wasm.len() > MAX_ORIGINATION_SIZE {
+        issues.push(ValidationIssue::CodeTooLarge { size: wasm.len() });
+    }
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    let import = import?;
+
+                    if let TypeRef::Func(_) = import.ty {
+                        imported_functions += 1;
+
+                        if import.module == HOST_MODULE
+                            && HOST_FUNCTIONS.contains(&import.name)
+                        {
+                            continue;
+                        }
+                    }
+
+                    issues.push(ValidationIssue::UnknownImport {
+                        module: import.module.to_string(),
+                        name: import.name.to_string(),
+                    });
+                }
+                function = imported_functions;
+            }
+            Payload::ExportSection(exports) => {
+                for export in exports {
+                    let export = export?;
+                    has_kernel_next |=
+                        export.name == KERNEL_NEXT && export.kind == ExternalKind::Func;
+                }
+            }
+            Payload::StartSection { func, .. } => {
+                issues.push(ValidationIssue::StartSection { function: func });
+            }
+            Payload::CodeSectionEntry(body) => {
+                let mut operators = body.get_operators_reader()?;
+
+                while !operators.eof() {
+                    let (operator, offset) = operators.read_with_offset()?;
+
+                    if is_float(&operator) {
+                        issues
+                            .push(ValidationIssue::FloatInstruction { function, offset });
+                        break;
+                    }
+                }
+                function += 1;
+            }
+            _ => (),
+        }
+    }
+
+    if !has_kernel_next {
+        issues.push(ValidationIssue::MissingKernelNext);
+    }
+
+    Ok(issues)
+}
+
+/// Check the kernel module stored in `path` - see [`validate`].
+pub fn validate_file(
+    path: impl AsRef<Path>,
+) -> Result<Vec<ValidationIssue>, ValidationError> {
+    let wasm = fs::read(path)?;
+
+    validate(wasm.as_slice())
+}
+
+fn is_float(operator: &Operator) -> bool {
+    use Operator::*;
+
+    matches!(
+        operator,
+        F32Load { .. }
+            | F64Load { .. }
+            | F32Store { .. }
+            | F64Store { .. }
+            | F32Const { .. }
+            | F64Const { .. }
+            | F32Eq
+            | F32Ne
+            | F32Lt
+            | F32Gt
+            | F32Le
+            | F32Ge
+            | F64Eq
+            | F64Ne
+            | F64Lt
+            | F64Gt
+            | F64Le
+            | F64Ge
+            | F32Abs
+            | F32Neg
+            | F32Ceil
+            | F32Floor
+            | F32Trunc
+            | F32Nearest
+            | F32Sqrt
+            | F32Add
+            | F32Sub
+            | F32Mul
+            | F32Div
+            | F32Min
+            | F32Max
+            | F32Copysign
+            | F64Abs
+            | F64Neg
+            | F64Ceil
+            | F64Floor
+            | F64Trunc
+            | F64Nearest
+            | F64Sqrt
+            | F64Add
+            | F64Sub
+            | F64Mul
+            | F64Div
+            | F64Min
+            | F64Max
+            | F64Copysign
+            | I32TruncF32S
+            | I32TruncF32U
+            | I32TruncF64S
+            | I32TruncF64U
+            | I64TruncF32S
+            | I64TruncF32U
+            | I64TruncF64S
+            | I64TruncF64U
+            | F32ConvertI32S
+            | F32ConvertI32U
+            | F32ConvertI64S
+            | F32ConvertI64U
+            | F32DemoteF64
+            | F64ConvertI32S
+            | F64ConvertI32U
+            | F64ConvertI64S
+            | F64ConvertI64U
+            | F64PromoteF32
+            | I32ReinterpretF32
+            | I64ReinterpretF64
+            | F32ReinterpretI32
+            | F64ReinterpretI64
+            | I32TruncSatF32S
+            | I32TruncSatF32U
+            | I32TruncSatF64S
+            | I32TruncSatF64U
+            | I64TruncSatF32S
+            | I64TruncSatF32U
+            | I64TruncSatF64S
+            | I64TruncSatF64U
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_WASM: &[u8] = include_bytes!("../../hello.wasm");
+
+    fn validate_wat(wat: &str) -> Vec<ValidationIssue> {
+        validate(wat::parse_str(wat).unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn hello_wasm_is_valid() {
+        // Act
+        let issues = validate(HELLO_WASM).unwrap();
+
+        // Assert
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn unknown_imports_and_missing_kernel_next() {
+        // Arrange
+        let wat = r#"
+            (module
+                (import "rollup_safe_core" "write_debug" (func (param i32 i32)))
+                (import "rollup_safe_core" "store_value_size" (func (param i32 i32)))
+                (import "env" "memory" (memory 1))
+                (func (export "kernel_run")))
+        "#;
+
+        // Act
+        let issues = validate_wat(wat);
+
+        // Assert
+        assert_eq!(
+            vec![
+                ValidationIssue::UnknownImport {
+                    module: "rollup_safe_core".to_string(),
+                    name: "store_value_size".to_string()
+                },
+                ValidationIssue::UnknownImport {
+                    module: "env".to_string(),
+                    name: "memory".to_string()
+                },
+                ValidationIssue::MissingKernelNext,
+            ],
+            issues
+        );
+    }
+
+    #[test]
+    fn float_instructions_and_start_section() {
+        // Arrange
+        let wat = r#"
+            (module
+                (import "rollup_safe_core" "write_debug" (func (param i32 i32)))
+                (func $init)
+                (func (export "kernel_next")
+                    (drop (i32.trunc_f32_s (f32.const 1.5))))
+                (start $init))
+        "#;
+
+        // Act
+        let issues = validate_wat(wat);
+
+        // Assert
+        assert!(matches!(
+            issues.as_slice(),
+            [
+                ValidationIssue::StartSection { function: 1 },
+                ValidationIssue::FloatInstruction { function: 2, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn code_too_large() {
+        // Arrange
+        let wat = format!(
+            r#"(module
+                (memory 1)
+                (data (i32.const 0) "{}")
+                (func (export "kernel_next")))"#,
+            "a".repeat(MAX_ORIGINATION_SIZE)
+        );
+
+        // Act
+        let issues = validate_wat(wat.as_str());
+
+        // Assert
+        assert!(matches!(
+            issues.as_slice(),
+            [ValidationIssue::CodeTooLarge { size }] if *size > MAX_ORIGINATION_SIZE
+        ));
+    }
+}
@@ -0,0 +1,649 @@
+//! Execution of compiled `.wasm` kernels against the [`MockHost`].
+//!
+//! The module is run by the [`wasmi`] interpreter. Its `rollup_safe_core` imports - as
+//! declared in [`host::rollup_core`] - are linked to the [`RawRollupCore`] implementation
+//! of the [`MockHost`], and its exported `kernel_next` is called once per kernel call:
+//!
+//! ```no_run
+//! # use mock_runtime::host::MockHost;
+//! # use mock_runtime::runtime::MockRuntime;
+//! # use mock_runtime::wasm::WasmKernel;
+//! let kernel = WasmKernel::from_file("hello.wasm").unwrap();
+//!
+//! let mut runtime = MockRuntime::new(MockHost::default(), move |host: &mut MockHost| {
+//!     kernel.kernel_next(host)
+//! });
+//! runtime.run_until(1);
+//! ```
+//!
+//! Pointers given by the kernel are checked against the bounds of its exported memory -
+//! an access out of bounds traps the kernel. Bytes only read by a host call that also
+//! writes to memory are first copied, as the kernel may give overlapping pointers.
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use host::rollup_core::{
+    Input, RawRollupCore, ValueType, WriteResult, PREIMAGE_HASH_SIZE,
+};
+use wasmi::core::Trap;
+use wasmi::{Caller, Engine, Linker, Memory, Module, Store};
+
+use crate::host::MockHost;
+use crate::trap::{trap, KernelError, TrapCondition};
+
+/// Name of the host module imported by kernels.
+pub const HOST_MODULE: &str = "rollup_safe_core";
+
+/// Name of the entrypoint exported by kernels.
+pub const KERNEL_NEXT: &str = "kernel_next";
+
+/// Errors that may occur when loading, or calling, a wasm kernel.
+#[derive(Debug)]
+pub enum WasmError {
+    /// Failure reading the module from disk.
+    Io(std::io::Error),
+    /// The module is invalid, does not link against the host, or trapped.
+    Wasmi(wasmi::Error),
+    /// The module does not export a memory for the host to read from and write to.
+    NoExportedMemory,
+}
+
+impl From<std::io::Error> for WasmError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<wasmi::Error> for WasmError {
+    fn from(error: wasmi::Error) -> Self {
+        Self::Wasmi(error)
+    }
+}
+
+impl From<Trap> for WasmError {
+    fn from(error: Trap) -> Self {
+        Self::Wasmi(error.into())
+    }
+}
+
+// State of the wasm store, while the kernel is called.
+struct WasmHost {
+    host: MockHost,
+    memory: Option<Memory>,
+}
+
+/// A compiled kernel, called through its exported `kernel_next`.
+pub struct WasmKernel {
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmKernel {
+    /// Load a kernel from its wasm binary.
+    pub fn new(wasm: &[u8]) -> Result<Self, WasmError> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm)?;
+
+        Ok(Self { engine, module })
+    }
+
+    /// Load a kernel from a `.wasm` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, WasmError> {
+        let wasm = fs::read(path)?;
+
+        Self::new(wasm.as_slice())
+    }
+
+    /// Call `kernel_next` of the kernel, over `host`.
+    ///
+    /// The kernel is instantiated afresh on each call - as with the PVM, only the
+    /// durable storage of the host persists between calls. The ticks charged by
+    /// `host` are reset at the start of each call.
+    pub fn call(&self, host: &mut MockHost) -> Result<(), WasmError> {
+        host.start_kernel_call();
+
+        let mut store = Store::new(
+            &self.engine,
+            WasmHost {
+                host: std::mem::take(host),
+                memory: None,
+            },
+        );
+
+        let result = self.instantiate_and_call(&mut store);
+
+        *host = store.into_data().host;
+        result
+    }
+
+    /// Call `kernel_next` of the kernel, over `host` - suitable as the kernel of a
+    /// [`MockRuntime`](crate::runtime::MockRuntime).
+    ///
+    /// # Panics
+    /// Traps with [`KernelError::WasmTrap`] if the kernel fails to link, or traps.
+    pub fn kernel_next(&self, host: &mut MockHost) {
+        if let Err(error) = self.call(host) {
+            trap(TrapCondition::KernelFailure(KernelError::WasmTrap(
+                format!("{:?}", error),
+            )));
+        }
+    }
+
+    fn instantiate_and_call(&self, store: &mut Store<WasmHost>) -> Result<(), WasmError> {
+        let linker = self.linker()?;
+        let instance = linker
+            .instantiate(&mut *store, &self.module)?
+            .start(&mut *store)?;
+
+        let memory = self
+            .module
+            .exports()
+            .find(|export| export.ty().memory().is_some())
+            .and_then(|export| instance.get_memory(&*store, export.name()))
+            .ok_or(WasmError::NoExportedMemory)?;
+        store.data_mut().memory = Some(memory);
+
+        instance
+            .get_typed_func::<(), ()>(&*store, KERNEL_NEXT)?
+            .call(&mut *store, ())?;
+
+        Ok(())
+    }
+
+    fn linker(&self) -> Result<Linker<WasmHost>, WasmError> {
+        let mut linker = Linker::new(&self.engine);
+
+        linker
+            .func_wrap(HOST_MODULE, "read_input", read_input)
+            .and_then(|l| l.func_wrap(HOST_MODULE, "write_output", write_output))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_has", store_has))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_read", store_read))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_write", store_write))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_delete", store_delete))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_list_size", store_list_size))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_list_get", store_list_get))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_move", store_move))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "store_copy", store_copy))
+            .and_then(|l| l.func_wrap(HOST_MODULE, "reveal_preimage", reveal_preimage))
+            .map_err(wasmi::Error::from)?;
+
+        // early kernels - such as `hello.wasm` - expect `write_debug` to return a value.
+        let debug_returns_value = self.module.imports().any(|import| {
+            import.module() == HOST_MODULE
+                && import.name() == "write_debug"
+                && matches!(import.ty().func(), Some(ty) if !ty.results().is_empty())
+        });
+
+        if debug_returns_value {
+            linker.func_wrap(
+                HOST_MODULE,
+                "write_debug",
+                |caller: Caller<'_, WasmHost>, src: u32, num_bytes: u32| {
+                    write_debug(caller, src, num_bytes).map(|()| 0_i32)
+                },
+            )
+        } else {
+            linker.func_wrap(HOST_MODULE, "write_debug", write_debug)
+        }
+        .map_err(wasmi::Error::from)?;
+
+        Ok(linker)
+    }
+}
+
+// Run `f` over the memory of the kernel, and the host.
+fn with_memory<R>(
+    caller: &mut Caller<'_, WasmHost>,
+    f: impl FnOnce(&KernelMemory, &MockHost) -> Result<R, Trap>,
+) -> Result<R, Trap> {
+    let memory = caller
+        .data()
+        .memory
+        .ok_or_else(|| Trap::new("host function called before memory was exported"))?;
+
+    let (memory, state) = memory.data_and_store_mut(caller);
+    f(&KernelMemory::new(memory), &state.host)
+}
+
+// The memory of the kernel, as a single pointer from which every range is taken - so
+// that taking a range does not invalidate those taken before.
+struct KernelMemory<'a> {
+    base: *mut u8,
+    len: usize,
+    _memory: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> KernelMemory<'a> {
+    fn new(memory: &'a mut [u8]) -> Self {
+        Self {
+            base: memory.as_mut_ptr(),
+            len: memory.len(),
+            _memory: PhantomData,
+        }
+    }
+
+    // A pointer to `len` bytes at `offset`, if within bounds.
+    fn range(&self, offset: u32, len: u32) -> Result<*mut u8, Trap> {
+        let (offset, len) = (offset as usize, len as usize);
+
+        match offset.checked_add(len) {
+            // safe as `offset` is within the bounds of memory
+            Some(end) if end <= self.len => Ok(unsafe { self.base.add(offset) }),
+            _ => Err(Trap::new(format!(
+                "memory access out of bounds: {} bytes at {}",
+                len, offset
+            ))),
+        }
+    }
+
+    // A copy of the `len` bytes at `offset`, if within bounds.
+    fn copy(&self, offset: u32, len: u32) -> Result<Vec<u8>, Trap> {
+        let src = self.range(offset, len)?;
+
+        // safe as `src` points to `len` bytes of memory
+        Ok(unsafe { std::slice::from_raw_parts(src, len as usize) }.to_vec())
+    }
+
+    fn write_i32(&self, offset: u32, value: i32) -> Result<(), Trap> {
+        let dst = self.range(offset, 4)?;
+
+        // safe as `dst` points to 4 bytes of memory
+        unsafe { std::ptr::copy_nonoverlapping(value.to_le_bytes().as_ptr(), dst, 4) };
+        Ok(())
+    }
+}
+
+fn read_input(
+    mut caller: Caller<'_, WasmHost>,
+    r#type: u32,
+    level: u32,
+    id: u32,
+    dst: u32,
+    max_bytes: u32,
+) -> Result<u32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let dst = memory.range(dst, max_bytes)?;
+
+        let mut input_type = Input::MessageData;
+        let mut input_level = 0;
+        let mut input_id = 0;
+
+        // safe as `dst` points to `max_bytes` bytes of memory
+        let size = unsafe {
+            host.read_input(
+                &mut input_type,
+                &mut input_level,
+                &mut input_id,
+                dst,
+                max_bytes as usize,
+            )
+        };
+
+        if size > 0 {
+            memory.write_i32(r#type, input_type as i32)?;
+            memory.write_i32(level, input_level)?;
+            memory.write_i32(id, input_id)?;
+        }
+
+        Ok(size as u32)
+    })
+}
+
+fn write_output(
+    mut caller: Caller<'_, WasmHost>,
+    src: u32,
+    num_bytes: u32,
+) -> Result<i32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let src = memory.range(src, num_bytes)?;
+
+        // safe as `src` points to `num_bytes` bytes of memory
+        let result = unsafe { host.write_output(src, num_bytes as usize) };
+        Ok(result as i32)
+    })
+}
+
+fn write_debug(
+    mut caller: Caller<'_, WasmHost>,
+    src: u32,
+    num_bytes: u32,
+) -> Result<(), Trap> {
+    with_memory(&mut caller, |memory, _host| {
+        let src = memory.range(src, num_bytes)?;
+
+        // safe as `src` points to `num_bytes` bytes of memory
+        unsafe { MockHost::write_debug(src, num_bytes as usize) };
+        Ok(())
+    })
+}
+
+fn store_has(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+) -> Result<i32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.range(path, path_len)?;
+
+        // safe as `path` points to `path_len` bytes of memory
+        let value_type: ValueType = unsafe { host.store_has(path, path_len as usize) };
+        Ok(value_type as i32)
+    })
+}
+
+fn store_read(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+    offset: u32,
+    dst: u32,
+    num_bytes: u32,
+) -> Result<u32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.copy(path, path_len)?;
+        let dst = memory.range(dst, num_bytes)?;
+
+        // safe as `dst` points to `num_bytes` bytes of memory
+        let size = unsafe {
+            host.store_read(
+                path.as_ptr(),
+                path_len as usize,
+                offset as usize,
+                dst,
+                num_bytes as usize,
+            )
+        };
+        Ok(size as u32)
+    })
+}
+
+fn store_write(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+    offset: u32,
+    src: u32,
+    num_bytes: u32,
+) -> Result<i32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.range(path, path_len)?;
+        let src = memory.range(src, num_bytes)?;
+
+        // safe as `path` & `src` point to `path_len` & `num_bytes` bytes of memory
+        let result: WriteResult = unsafe {
+            host.store_write(
+                path,
+                path_len as usize,
+                offset as usize,
+                src,
+                num_bytes as usize,
+            )
+        };
+        Ok(result as i32)
+    })
+}
+
+fn store_delete(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+) -> Result<(), Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.range(path, path_len)?;
+
+        // safe as `path` points to `path_len` bytes of memory
+        unsafe { host.store_delete(path, path_len as usize) };
+        Ok(())
+    })
+}
+
+fn store_list_size(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+) -> Result<i64, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.range(path, path_len)?;
+
+        // safe as `path` points to `path_len` bytes of memory
+        Ok(unsafe { host.store_list_size(path, path_len as usize) })
+    })
+}
+
+fn store_list_get(
+    mut caller: Caller<'_, WasmHost>,
+    path: u32,
+    path_len: u32,
+    index: i64,
+    dst: u32,
+    max_size: u32,
+) -> Result<u32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let path = memory.copy(path, path_len)?;
+        let dst = memory.range(dst, max_size)?;
+
+        // safe as `dst` points to `max_size` bytes of memory
+        let size = unsafe {
+            host.store_list_get(
+                path.as_ptr(),
+                path_len as usize,
+                index,
+                dst,
+                max_size as usize,
+            )
+        };
+        Ok(size as u32)
+    })
+}
+
+fn store_move(
+    mut caller: Caller<'_, WasmHost>,
+    from_path: u32,
+    from_path_len: u32,
+    to_path: u32,
+    to_path_len: u32,
+) -> Result<(), Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let from_path = memory.range(from_path, from_path_len)?;
+        let to_path = memory.range(to_path, to_path_len)?;
+
+        // safe as both paths point to their length in bytes of memory
+        unsafe {
+            host.store_move(
+                from_path,
+                from_path_len as usize,
+                to_path,
+                to_path_len as usize,
+            )
+        };
+        Ok(())
+    })
+}
+
+fn store_copy(
+    mut caller: Caller<'_, WasmHost>,
+    from_path: u32,
+    from_path_len: u32,
+    to_path: u32,
+    to_path_len: u32,
+) -> Result<(), Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let from_path = memory.range(from_path, from_path_len)?;
+        let to_path = memory.range(to_path, to_path_len)?;
+
+        // safe as both paths point to their length in bytes of memory
+        unsafe {
+            host.store_copy(
+                from_path,
+                from_path_len as usize,
+                to_path,
+                to_path_len as usize,
+            )
+        };
+        Ok(())
+    })
+}
+
+fn reveal_preimage(
+    mut caller: Caller<'_, WasmHost>,
+    hash_addr: u32,
+    destination_addr: u32,
+    max_bytes: u32,
+) -> Result<u32, Trap> {
+    with_memory(&mut caller, |memory, host| {
+        let hash = memory.copy(hash_addr, PREIMAGE_HASH_SIZE as u32)?;
+        let destination = memory.range(destination_addr, max_bytes)?;
+
+        // safe as `destination` points to `max_bytes` bytes of memory
+        let size = unsafe {
+            host.reveal_preimage(hash.as_ptr(), destination, max_bytes as usize)
+        };
+        Ok(size as u32)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::{CostModel, HostCallCost};
+    use crate::host::check_debug_log;
+    use crate::runtime::MockRuntime;
+    use crate::state::HostState;
+    use host::path::{Path as _, RefPath};
+
+    const ECHO_PATH: RefPath = RefPath::assert_from(b"/kernel/echo");
+
+    const HELLO_WASM: &[u8] = include_bytes!("../../hello.wasm");
+
+    // Reads the first input of each call into memory, writing it to the outbox and to
+    // `/kernel/echo`.
+    const ECHO_WAT: &str = r#"
+        (module
+            (import "rollup_safe_core" "read_input"
+                (func $read_input (param i32 i32 i32 i32 i32) (result i32)))
+            (import "rollup_safe_core" "write_output"
+                (func $write_output (param i32 i32) (result i32)))
+            (import "rollup_safe_core" "store_write"
+                (func $store_write (param i32 i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "/kernel/echo")
+            (func (export "kernel_next")
+                (local $size i32)
+                (local.set $size (call $read_input
+                    (i32.const 100) (i32.const 104) (i32.const 108)
+                    (i32.const 200) (i32.const 4096)))
+                (if (i32.gt_u (local.get $size) (i32.const 0))
+                    (then
+                        (drop (call $write_output (i32.const 200) (local.get $size)))
+                        (drop (call $store_write
+                            (i32.const 0) (i32.const 12) (i32.const 0)
+                            (i32.const 200) (local.get $size)))))))
+    "#;
+
+    fn echo_kernel() -> WasmKernel {
+        WasmKernel::new(wat::parse_str(ECHO_WAT).unwrap().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn hello_wasm_writes_debug() {
+        // Arrange
+        let kernel = WasmKernel::new(HELLO_WASM).unwrap();
+        let mut host = MockHost::default();
+
+        // Act
+        kernel.call(&mut host).unwrap();
+
+        // Assert
+        check_debug_log(|log| assert!(log.contains(&"hello, world!".to_string())));
+    }
+
+    #[test]
+    fn kernel_reads_input_and_writes_storage() {
+        // Arrange
+        let kernel = echo_kernel();
+        let mut runtime =
+            MockRuntime::new(MockHost::default(), move |host: &mut MockHost| {
+                kernel.kernel_next(host)
+            });
+        runtime.add_inputs(1, vec![(Input::MessageData, vec![5; 10])]);
+
+        // Act
+        runtime.run_until(1);
+
+        // Assert
+        let state: &mut HostState = runtime.host_mut().as_mut();
+        assert_eq!(
+            vec![5; 10],
+            state.handle_store_read(ECHO_PATH.as_bytes(), 0, 10)
+        );
+        assert_eq!(vec![5; 10], state.store.get_value::<Vec<u8>>("/output/1/0"));
+    }
+
+    #[test]
+    fn kernel_traps_out_of_bounds() {
+        // Arrange
+        let wat = ECHO_WAT.replace("(i32.const 4096)", "(i32.const 65536)");
+        let kernel = WasmKernel::new(wat::parse_str(wat).unwrap().as_slice()).unwrap();
+        let mut host = MockHost::default();
+
+        // Act
+        let result = kernel.call(&mut host);
+
+        // Assert
+        assert!(matches!(result, Err(WasmError::Wasmi(_))));
+    }
+
+    #[test]
+    fn call_resets_ticks() {
+        // Arrange
+        let kernel = echo_kernel();
+        let mut host = MockHost::default();
+        host.set_cost_model(CostModel {
+            tick_limit: 20,
+            kernel_call: 10,
+            read_input: HostCallCost::new(5, 0),
+            ..CostModel::free()
+        });
+
+        // Act
+        kernel.call(&mut host).unwrap();
+        let first = host.ticks();
+        kernel.call(&mut host).unwrap();
+
+        // Assert
+        assert_eq!(15, first);
+        assert_eq!(15, host.ticks());
+    }
+
+    #[test]
+    fn store_read_into_own_path() {
+        // Arrange
+        const WAT: &str = r#"
+            (module
+                (import "rollup_safe_core" "store_read"
+                    (func $store_read (param i32 i32 i32 i32 i32) (result i32)))
+                (import "rollup_safe_core" "write_output"
+                    (func $write_output (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "/kernel/echo")
+                (func (export "kernel_next")
+                    (drop (call $write_output (i32.const 0)
+                        (call $store_read
+                            (i32.const 0) (i32.const 12) (i32.const 0)
+                            (i32.const 0) (i32.const 12))))))
+        "#;
+        let kernel = WasmKernel::new(wat::parse_str(WAT).unwrap().as_slice()).unwrap();
+
+        let mut host = MockHost::default();
+        host.as_mut()
+            .handle_store_write(ECHO_PATH.as_bytes(), 0, &[7; 12]);
+
+        // Act
+        kernel.call(&mut host).unwrap();
+
+        // Assert
+        let state: &mut HostState = host.as_mut();
+        assert_eq!(vec![7; 12], state.store.get_value::<Vec<u8>>("/output/0/0"));
+    }
+}
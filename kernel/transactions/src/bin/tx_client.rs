@@ -0,0 +1,459 @@
+/* Command-line client of the transactions kernel.
+
+   Builds the inbox messages understood by the kernel, hex-encoded so that they can be
+   injected into the rollup inbox, and decodes any inbox message back:
+
+   tx-client keygen
+   tx-client deposit --to <tz4> --creator <contract> --contents <string> --amount <n>
+   tx-client transfer --key <secret-key> --counter <n> --to <tz4>
+                      --creator <contract> --contents <string> --amount <n>
+   tx-client withdraw --key <secret-key> --counter <n> --to <contract>
+                      --creator <contract> --contents <string> --amount <n>
+                      [--entrypoint <name>]
+   tx-client decode <hex>
+*/
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+
+use crypto::blake2b::Blake2bError;
+use crypto::hash::{ HashTrait, Layer2Tz4Hash };
+use rand::RngCore;
+use thiserror::Error;
+use tezos_encoding::enc::{ BinError, BinWriter };
+
+use transactions::encoding::bls::{ BlsError, BlsSecretKey, BLS_SECRET_KEY_SIZE };
+use transactions::encoding::contract::Contract;
+use transactions::encoding::entrypoint::{ Entrypoint, EntrypointError };
+use transactions::encoding::string_ticket::StringTicket;
+use transactions::inbox::v1::sendable::{ Batch, SigningError, Transaction };
+use transactions::inbox::v1::{ Operation, OperationContent };
+use transactions::inbox::{
+    sendable,
+    DepositFromPayloadError,
+    ExternalInboxMessage,
+    InboxDeposit,
+    InboxMessage,
+    InternalInboxMessage,
+    ParsedExternalInboxMessage,
+    Signer,
+};
+
+const USAGE: &str =
+    "Usage:
+  tx-client keygen
+  tx-client deposit --to <tz4> --creator <contract> --contents <string> --amount <n>
+  tx-client transfer --key <secret-key> --counter <n> --to <tz4>
+                     --creator <contract> --contents <string> --amount <n>
+  tx-client withdraw --key <secret-key> --counter <n> --to <contract>
+                     --creator <contract> --contents <string> --amount <n>
+                     [--entrypoint <name>]
+  tx-client decode <hex>";
+
+// errors occuring when building or decoding an inbox message
+#[derive(Error, Debug)]
+enum ClientError {
+    #[error("Missing command")] MissingCommand,
+    #[error("Unknown command {0:?}")] UnknownCommand(String),
+    #[error("Unexpected argument {0:?}")] UnexpectedArgument(String),
+    #[error("Missing value of option --{0}")] MissingValue(String),
+    #[error("Missing option --{0}")] MissingOption(&'static str),
+    #[error("Invalid value of option --{0}")] InvalidOption(&'static str),
+    #[error("Invalid hex string")] InvalidHex,
+    #[error("{0}")] Entrypoint(#[from] EntrypointError),
+    #[error("{0}")] Bls(#[from] BlsError),
+    #[error("{0}")] Signing(#[from] SigningError),
+    #[error("Unable to hash public key: {0}")] Hashing(#[from] Blake2bError),
+    #[error("Unable to encode inbox message: {0}")] Encoding(#[from] BinError),
+    #[error("Unable to decode inbox message: {0}")] Decoding(String),
+    #[error("{0}")] Deposit(#[from] DepositFromPayloadError),
+    #[error("{0} trailing bytes after the inbox message")] TrailingBytes(usize),
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, USAGE);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<String, ClientError> {
+    let (command, args) = args.split_first().ok_or(ClientError::MissingCommand)?;
+
+    match command.as_str() {
+        "keygen" => keygen(),
+        "deposit" => deposit(&Options::parse(args)?),
+        "transfer" => transfer(&Options::parse(args)?),
+        "withdraw" => withdraw(&Options::parse(args)?),
+        "decode" =>
+            match args {
+                [hex] => decode(hex),
+                [] => Err(ClientError::MissingValue("hex".to_string())),
+                [_, unexpected, ..] => Err(ClientError::UnexpectedArgument(unexpected.clone())),
+            }
+        "help" | "--help" | "-h" => Ok(USAGE.to_string()),
+        _ => Err(ClientError::UnknownCommand(command.clone())),
+    }
+}
+
+// Options, given as `--name value`
+struct Options(HashMap<String, String>);
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, ClientError> {
+        let mut options = HashMap::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| ClientError::UnexpectedArgument(arg.clone()))?;
+            let value = args.next().ok_or_else(|| ClientError::MissingValue(name.to_string()))?;
+
+            options.insert(name.to_string(), value.clone());
+        }
+        Ok(Self(options))
+    }
+
+    fn get(&self, name: &'static str) -> Result<&str, ClientError> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .ok_or(ClientError::MissingOption(name))
+    }
+
+    fn parse_value<T: std::str::FromStr>(&self, name: &'static str) -> Result<T, ClientError> {
+        self.get(name)?
+            .parse()
+            .map_err(|_| ClientError::InvalidOption(name))
+    }
+
+    fn layer2_address(&self, name: &'static str) -> Result<Layer2Tz4Hash, ClientError> {
+        Layer2Tz4Hash::from_b58check(self.get(name)?).map_err(|_| ClientError::InvalidOption(name))
+    }
+
+    fn contract(&self, name: &'static str) -> Result<Contract, ClientError> {
+        Contract::from_b58check(self.get(name)?).map_err(|_| ClientError::InvalidOption(name))
+    }
+
+    fn secret_key(&self) -> Result<BlsSecretKey, ClientError> {
+        let bytes = from_hex(self.get("key")?)?;
+        Ok(BlsSecretKey::try_from(bytes.as_slice())?)
+    }
+
+    fn ticket(&self) -> Result<StringTicket, ClientError> {
+        Ok(
+            StringTicket::new(
+                self.contract("creator")?,
+                self.get("contents")?.to_string(),
+                self.parse_value("amount")?
+            )
+        )
+    }
+}
+
+// Generate a new tz4 account
+fn keygen() -> Result<String, ClientError> {
+    let mut ikm = [0; BLS_SECRET_KEY_SIZE];
+    rand::thread_rng().fill_bytes(&mut ikm);
+
+    let sk = BlsSecretKey::from_ikm(&ikm)?;
+    let pk = sk.public_key();
+
+    Ok(
+        format!(
+            "secret key: {}\npublic key: {}\naddress:    {}",
+            to_hex(&sk.to_bytes()),
+            to_hex(pk.as_bytes()),
+            pk.hash()?.to_b58check()
+        )
+    )
+}
+
+// The internal message of a deposit, as sent by the layer 1 bridge contract
+fn deposit(options: &Options) -> Result<String, ClientError> {
+    let deposit = InboxDeposit {
+        destination: options.layer2_address("to")?,
+        ticket: options.ticket()?,
+    };
+
+    encode(sendable::InboxMessage::Internal(deposit.into()))
+}
+
+fn transfer(options: &Options) -> Result<String, ClientError> {
+    let content = OperationContent::transfer(options.layer2_address("to")?, options.ticket()?);
+
+    sign_operation(options, content)
+}
+
+fn withdraw(options: &Options) -> Result<String, ClientError> {
+    let entrypoint = match options.0.get("entrypoint") {
+        Some(name) => Entrypoint::try_from(name.clone())?,
+        None => Entrypoint::default(),
+    };
+    let content = OperationContent::withdrawal(
+        options.contract("to")?,
+        options.ticket()?,
+        entrypoint
+    );
+
+    sign_operation(options, content)
+}
+
+// An external message, made of a batch of a single operation signed by `--key`.
+//
+// The operation reveals the public key of its signer, so that it is accepted even by an
+// account that has not yet been linked to a public key.
+fn sign_operation(options: &Options, content: OperationContent) -> Result<String, ClientError> {
+    let sk = options.secret_key()?;
+    let operation = Operation {
+        signer: Signer::BlsPublicKey(sk.public_key()),
+        counter: options.parse_value("counter")?,
+        contents: vec![content],
+    };
+
+    let batch = Batch::sign(vec![Transaction::new(vec![operation])], &[sk])?;
+
+    encode(sendable::InboxMessage::External(sendable::ExternalInboxMessage::V1(batch)))
+}
+
+fn encode(message: sendable::InboxMessage) -> Result<String, ClientError> {
+    let mut bytes = Vec::new();
+    message.bin_write(&mut bytes)?;
+    Ok(to_hex(&bytes))
+}
+
+// Pretty-print an inbox message
+fn decode(hex: &str) -> Result<String, ClientError> {
+    let bytes = from_hex(hex)?;
+    let (remaining, message) = InboxMessage::parse(&bytes).map_err(|err|
+        ClientError::Decoding(format!("{:?}", err))
+    )?;
+    if !remaining.is_empty() {
+        return Err(ClientError::TrailingBytes(remaining.len()));
+    }
+
+    match message {
+        InboxMessage::Internal(InternalInboxMessage { payload }) => {
+            let InboxDeposit { destination, ticket } = payload.try_into()?;
+
+            Ok(
+                format!(
+                    "Internal deposit\n  destination: {}\n  ticket:      {} {:?} from {}",
+                    destination.to_b58check(),
+                    ticket.amount(),
+                    ticket.contents(),
+                    ticket.creator().to_b58check()
+                )
+            )
+        }
+        InboxMessage::External(ExternalInboxMessage(external)) => {
+            let (remaining, ParsedExternalInboxMessage::V1(batch)) =
+                ParsedExternalInboxMessage::parse(external).map_err(|err|
+                    ClientError::Decoding(format!("{:?}", err))
+                )?;
+            if !remaining.is_empty() {
+                return Err(ClientError::TrailingBytes(remaining.len()));
+            }
+
+            let mut output = String::from("External batch (v1)");
+            for (index, transaction) in batch.transactions.iter().enumerate() {
+                output.push_str(&format!("\n  transaction {}", index));
+
+                for operation in transaction.operations() {
+                    output.push_str(
+                        &format!(
+                            "\n    operation of {} (counter {})",
+                            operation.signer.address()?.to_b58check(),
+                            operation.counter
+                        )
+                    );
+                    for content in operation.contents.iter() {
+                        output.push_str(&format!("\n      {:?}", content));
+                    }
+                }
+            }
+            output.push_str(
+                &format!(
+                    "\n  aggregated signature: {}",
+                    to_hex(batch.aggregated_signature.as_bytes())
+                )
+            );
+            Ok(output)
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, ClientError> {
+    let hex = hex.trim();
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if hex.len() % 2 != 0 {
+        return Err(ClientError::InvalidHex);
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(ClientError::InvalidHex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use transactions::encoding::public_key_hash::PublicKeyHash;
+    use transactions::memory::Accounts;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+
+    fn creator() -> String {
+        Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![1; 20]))).to_b58check()
+    }
+
+    fn secret_key() -> String {
+        to_hex(&BlsSecretKey::from_ikm(&[7; 32]).unwrap().to_bytes())
+    }
+
+    #[test]
+    fn deposit_encode_decode() {
+        // Arrange
+        let destination = Layer2Tz4Hash(vec![2; 20]).to_b58check();
+        let creator = creator();
+
+        // Act
+        let hex = run(
+            &args(
+                &[
+                    "deposit",
+                    "--to",
+                    &destination,
+                    "--creator",
+                    &creator,
+                    "--contents",
+                    "Hello, Ticket!",
+                    "--amount",
+                    "5",
+                ]
+            )
+        ).unwrap();
+
+        // Assert
+        let bytes = from_hex(&hex).unwrap();
+        let (_, message) = InboxMessage::parse(&bytes).unwrap();
+        let payload = match message {
+            InboxMessage::Internal(InternalInboxMessage { payload }) => payload,
+            InboxMessage::External(_) => panic!("Expected an internal message"),
+        };
+        let deposit: InboxDeposit = payload.try_into().unwrap();
+
+        assert_eq!(Layer2Tz4Hash(vec![2; 20]), deposit.destination);
+        assert_eq!(
+            StringTicket::new(
+                Contract::from_b58check(&creator).unwrap(),
+                "Hello, Ticket!".to_string(),
+                5
+            ),
+            deposit.ticket
+        );
+
+        let decoded = run(&args(&["decode", &hex])).unwrap();
+        assert!(decoded.contains(&destination));
+    }
+
+    #[test]
+    fn transfer_signature_verifies() {
+        // Arrange
+        let destination = Layer2Tz4Hash(vec![2; 20]).to_b58check();
+        let creator = creator();
+        let key = secret_key();
+
+        // Act
+        let hex = run(
+            &args(
+                &[
+                    "transfer",
+                    "--key",
+                    &key,
+                    "--counter",
+                    "0",
+                    "--to",
+                    &destination,
+                    "--creator",
+                    &creator,
+                    "--contents",
+                    "Hello, Ticket!",
+                    "--amount",
+                    "5",
+                ]
+            )
+        ).unwrap();
+
+        // Assert
+        let bytes = from_hex(&hex).unwrap();
+        let (_, message) = InboxMessage::parse(&bytes).unwrap();
+        let external = match message {
+            InboxMessage::External(ExternalInboxMessage(external)) => external,
+            InboxMessage::Internal(_) => panic!("Expected an external message"),
+        };
+        let (_, ParsedExternalInboxMessage::V1(batch)) = ParsedExternalInboxMessage::parse(
+            external
+        ).unwrap();
+
+        assert_eq!(1, batch.transactions.len());
+        assert!(batch.verify_signature(&Accounts::default()).is_ok());
+
+        let decoded = run(&args(&["decode", &hex])).unwrap();
+        assert!(decoded.contains("counter 0"));
+    }
+
+    #[test]
+    fn withdraw_missing_option() {
+        // Arrange
+        let key = secret_key();
+
+        // Act
+        let result = run(&args(&["withdraw", "--key", &key, "--counter", "0"]));
+
+        // Assert
+        assert!(matches!(result, Err(ClientError::MissingOption("to"))));
+    }
+
+    #[test]
+    fn decode_invalid_hex() {
+        // Act
+        let result = run(&args(&["decode", "0x0"]));
+
+        // Assert
+        assert!(matches!(result, Err(ClientError::InvalidHex)));
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        // Arrange
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        // Act
+        let decoded = from_hex(&to_hex(&bytes)).unwrap();
+
+        // Assert
+        assert_eq!(bytes, decoded);
+    }
+}
@@ -5,7 +5,7 @@ use debug::debug_msg;
 
 use crate::{
     encoding::string_ticket::{ StringTicket, TicketHashError },
-    memory::{ Account, AccountError, Memory },
+    memory::{ AccountError, Memory },
 };
 
 // Deposit tickets into the kernel state
@@ -38,25 +38,14 @@ pub fn deposit_ticket<Host: RawRollupCore>(
         &account_address
     );
 
-    let result: Result<Option<Account>, AccountError> = memory
-        .accounts_mut()
-        .account_of_mut(&account_address)
-        .map_or_else(
-            || {
-                let mut account = Account::default();
-                account.add_ticket(id_proof.identify().clone(), ticket_amount)?;
-                Ok(Some(account))
-            },
-            |account| {
-                account.add_ticket(id_proof.identify().clone(), ticket_amount)?;
-                Ok(None)
-            }
-        );
+    // the ticket enters the rollup: its supply grows with the account balance
+    memory.accounts_mut().atomically(|accounts| {
+        accounts
+            .account_or_default_mut(&account_address)
+            .add_ticket(id_proof.identify().clone(), ticket_amount)?;
 
-    match result? {
-        Some(new_account) => memory.accounts_mut().add_account(account_address, new_account)?,
-        None => (),
-    }
+        accounts.mint(id_proof.identify(), ticket_amount)
+    })?;
 
     // update global ticket table
     memory.add_ticket(id_proof);
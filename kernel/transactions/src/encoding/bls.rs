@@ -0,0 +1,278 @@
+/* BLS public keys and signatures, used by tz4 layer 2 accounts */
+
+use blst::min_pk::{ AggregateSignature, PublicKey, SecretKey, Signature };
+use blst::BLST_ERROR;
+use crypto::blake2b::{ digest_160, Blake2bError };
+use crypto::hash::Layer2Tz4Hash;
+use nom::bytes::complete::take;
+use nom::combinator::map_res;
+use serde::{ Deserialize, Serialize };
+use thiserror::Error;
+use tezos_encoding::enc::{ self, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ NomReader, NomResult };
+
+// Size of a compressed BLS public key
+pub const BLS_PUBLIC_KEY_SIZE: usize = 48;
+
+// Size of a compressed BLS signature
+pub const BLS_SIGNATURE_SIZE: usize = 96;
+
+// Size of a BLS secret key
+pub const BLS_SECRET_KEY_SIZE: usize = 32;
+
+// Tezos uses the augmented scheme: the public key is prepended to each signed message
+pub(crate) const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_AUG_";
+
+// errors occuring when parsing or verifying BLS keys and signatures
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BlsError {
+    #[error("Invalid BLS public key: {0:?}")] InvalidPublicKey(BLST_ERROR),
+    #[error("Invalid BLS signature: {0:?}")] InvalidSignature(BLST_ERROR),
+    #[error("Signature verification failed: {0:?}")] VerificationFailed(BLST_ERROR),
+    #[error("Nothing to verify the signature against")] NoMessages,
+    #[error("Invalid BLS secret key: {0:?}")] InvalidSecretKey(BLST_ERROR),
+}
+
+// A compressed BLS public key, of a tz4 account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlsPublicKey(Vec<u8>);
+
+impl BlsPublicKey {
+    // The tz4 address of the public key
+    pub fn hash(&self) -> Result<Layer2Tz4Hash, Blake2bError> {
+        Ok(Layer2Tz4Hash(digest_160(self.0.as_slice())?))
+    }
+
+    // The compressed bytes of the public key
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+impl TryFrom<&[u8]> for BlsPublicKey {
+    type Error = BlsError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        PublicKey::key_validate(bytes).map_err(BlsError::InvalidPublicKey)?;
+
+        Ok(Self(bytes.to_vec()))
+    }
+}
+
+// A BLS secret key, signing on behalf of a tz4 account
+pub struct BlsSecretKey(SecretKey);
+
+impl BlsSecretKey {
+    // Derive a secret key from input keying material, of at least 32 bytes
+    pub fn from_ikm(ikm: &[u8]) -> Result<Self, BlsError> {
+        SecretKey::key_gen(ikm, &[]).map(Self).map_err(BlsError::InvalidSecretKey)
+    }
+
+    // The public key of the account
+    pub fn public_key(&self) -> BlsPublicKey {
+        BlsPublicKey(self.0.sk_to_pk().compress().to_vec())
+    }
+
+    // Sign the message, with the augmented scheme
+    pub fn sign(&self, message: &[u8]) -> BlsSignature {
+        let pk = self.public_key();
+        BlsSignature(self.0.sign(message, BLS_DST, pk.as_bytes()).compress().to_vec())
+    }
+
+    // The bytes of the secret key
+    pub fn to_bytes(&self) -> [u8; BLS_SECRET_KEY_SIZE] {
+        self.0.to_bytes()
+    }
+}
+
+impl TryFrom<&[u8]> for BlsSecretKey {
+    type Error = BlsError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        SecretKey::from_bytes(bytes).map(Self).map_err(BlsError::InvalidSecretKey)
+    }
+}
+
+// An aggregate of BLS signatures, each over a message and public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlsSignature(Vec<u8>);
+
+impl BlsSignature {
+    // The compressed bytes of the signature
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    // Aggregate signatures into one
+    pub fn aggregate(signatures: &[BlsSignature]) -> Result<Self, BlsError> {
+        let signatures = signatures
+            .iter()
+            .map(|sig| Signature::sig_validate(sig.as_bytes(), true))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(BlsError::InvalidSignature)?;
+        let signatures: Vec<&Signature> = signatures.iter().collect();
+
+        let aggregate = AggregateSignature::aggregate(signatures.as_slice(), false).map_err(
+            BlsError::InvalidSignature
+        )?;
+
+        Ok(Self(aggregate.to_signature().compress().to_vec()))
+    }
+
+    // Verify the signature over each message, signed by the paired public key
+    pub fn aggregate_verify<'a>(
+        &self,
+        messages: impl Iterator<Item = (&'a BlsPublicKey, &'a [u8])>
+    ) -> Result<(), BlsError> {
+        let signature = Signature::sig_validate(self.as_bytes(), true).map_err(
+            BlsError::InvalidSignature
+        )?;
+
+        let mut public_keys = Vec::new();
+        let mut augmented = Vec::new();
+        for (pk, message) in messages {
+            public_keys.push(
+                PublicKey::from_bytes(pk.as_bytes()).map_err(BlsError::InvalidPublicKey)?
+            );
+            augmented.push([pk.as_bytes(), message].concat());
+        }
+
+        if public_keys.is_empty() {
+            return Err(BlsError::NoMessages);
+        }
+
+        let public_keys: Vec<&PublicKey> = public_keys.iter().collect();
+        let augmented: Vec<&[u8]> = augmented.iter().map(Vec::as_slice).collect();
+
+        // keys were validated when parsed
+        match
+            signature.aggregate_verify(
+                false,
+                augmented.as_slice(),
+                BLS_DST,
+                public_keys.as_slice(),
+                false
+            )
+        {
+            BLST_ERROR::BLST_SUCCESS => Ok(()),
+            err => Err(BlsError::VerificationFailed(err)),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for BlsSignature {
+    type Error = BlsError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Signature::sig_validate(bytes, true).map_err(BlsError::InvalidSignature)?;
+
+        Ok(Self(bytes.to_vec()))
+    }
+}
+
+// Encoding
+has_encoding!(BlsPublicKey, BLS_PUBLIC_KEY_ENCODING, { Encoding::Custom });
+has_encoding!(BlsSignature, BLS_SIGNATURE_ENCODING, { Encoding::Custom });
+
+impl NomReader for BlsPublicKey {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map_res(take(BLS_PUBLIC_KEY_SIZE), BlsPublicKey::try_from)(input)
+    }
+}
+
+impl NomReader for BlsSignature {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map_res(take(BLS_SIGNATURE_SIZE), BlsSignature::try_from)(input)
+    }
+}
+
+impl BinWriter for BlsPublicKey {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(self.as_bytes(), output);
+        Ok(())
+    }
+}
+
+impl BinWriter for BlsSignature {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(self.as_bytes(), output);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use blst::min_pk::SecretKey;
+
+    // Generate a key pair, deterministically from the seed
+    pub(crate) fn key_pair(seed: u8) -> (SecretKey, BlsPublicKey) {
+        let sk = SecretKey::key_gen(&[seed; 32], &[]).unwrap();
+        let pk = BlsPublicKey(sk.sk_to_pk().compress().to_vec());
+        (sk, pk)
+    }
+
+    // Sign the message, with the augmented scheme
+    pub(crate) fn sign(sk: &SecretKey, pk: &BlsPublicKey, message: &[u8]) -> BlsSignature {
+        BlsSignature(sk.sign(message, BLS_DST, pk.as_bytes()).compress().to_vec())
+    }
+
+    #[test]
+    fn aggregate_verify_multiple_signers() {
+        let (sk1, pk1) = key_pair(1);
+        let (sk2, pk2) = key_pair(2);
+
+        let signature = BlsSignature::aggregate(
+            &[sign(&sk1, &pk1, b"first"), sign(&sk2, &pk2, b"second")]
+        ).unwrap();
+
+        let messages = [(&pk1, b"first".as_slice()), (&pk2, b"second".as_slice())];
+        assert_eq!(Ok(()), signature.aggregate_verify(messages.into_iter()));
+
+        let swapped = [(&pk2, b"first".as_slice()), (&pk1, b"second".as_slice())];
+        assert!(
+            matches!(
+                signature.aggregate_verify(swapped.into_iter()),
+                Err(BlsError::VerificationFailed(_))
+            )
+        );
+    }
+
+    #[test]
+    fn bls_encode_decode() {
+        let (sk, pk) = key_pair(3);
+        let signature = sign(&sk, &pk, b"message");
+
+        let mut bytes = Vec::new();
+        pk.bin_write(&mut bytes).unwrap();
+        signature.bin_write(&mut bytes).unwrap();
+
+        let (remaining, decoded_pk) = BlsPublicKey::nom_read(bytes.as_slice()).unwrap();
+        let (remaining, decoded_signature) = BlsSignature::nom_read(remaining).unwrap();
+
+        assert!(remaining.is_empty());
+        assert_eq!(pk, decoded_pk);
+        assert_eq!(signature, decoded_signature);
+    }
+
+    #[test]
+    fn bls_secret_key_signs() {
+        let sk = BlsSecretKey::from_ikm(&[4; 32]).unwrap();
+        let pk = sk.public_key();
+
+        let restored = BlsSecretKey::try_from(sk.to_bytes().as_slice()).unwrap();
+        let signature = restored.sign(b"message");
+
+        let messages = [(&pk, b"message".as_slice())];
+        assert_eq!(Ok(()), signature.aggregate_verify(messages.into_iter()));
+    }
+
+    #[test]
+    fn bls_public_key_invalid() {
+        let bytes = [0xff; BLS_PUBLIC_KEY_SIZE];
+
+        assert!(BlsPublicKey::nom_read(bytes.as_slice()).is_err());
+    }
+}
@@ -3,11 +3,14 @@ use nom::bytes::complete::tag;
 use nom::sequence::preceded;
 use tezos_encoding::nom::{ NomReader, NomResult };
 use tezos_encoding::enc::{ self, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use serde::{ Deserialize, Serialize };
 use super::public_key_hash::PublicKeyHash;
 
 // Create contract
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 // Use only implicit
 pub enum Contract {
     Implicit(PublicKeyHash),
@@ -31,6 +34,9 @@ impl Contract {
     }
 }
 
+// Encoding
+has_encoding!(Contract, CONTRACT_ENCODING, { Encoding::Custom });
+
 // implement nomreader for contract
 impl NomReader for Contract {
     fn nom_read(input: &[u8]) -> NomResult<Self> {
@@ -0,0 +1,112 @@
+use nom::combinator::map_res;
+use thiserror::Error;
+use tezos_encoding::enc::{ self, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ self as nom_read, NomReader, NomResult };
+
+// The maximum length of an entrypoint name on layer 1
+pub const MAX_ENTRYPOINT_LENGTH: usize = 31;
+
+const DEFAULT_ENTRYPOINT: &str = "default";
+
+// Entrypoint of a layer 1 contract, targeted by an outbox transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entrypoint {
+    name: String,
+}
+
+// errors occuring when validating an entrypoint name
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EntrypointError {
+    #[error("Entrypoint name must be at most {MAX_ENTRYPOINT_LENGTH} bytes, got {0}")] TooLarge(
+        usize,
+    ),
+    #[error("Entrypoint name contains invalid character {0:?}")] InvalidChar(char),
+}
+
+impl Entrypoint {
+    // the entrypoint name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Default for Entrypoint {
+    fn default() -> Self {
+        Self { name: DEFAULT_ENTRYPOINT.into() }
+    }
+}
+
+impl TryFrom<String> for Entrypoint {
+    type Error = EntrypointError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if name.len() > MAX_ENTRYPOINT_LENGTH {
+            return Err(EntrypointError::TooLarge(name.len()));
+        }
+
+        let is_valid = |c: &char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '%' | '@');
+
+        if let Some(c) = name.chars().find(|c| !is_valid(c)) {
+            return Err(EntrypointError::InvalidChar(c));
+        }
+
+        // the empty entrypoint is an alias of the default one
+        if name.is_empty() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self { name })
+    }
+}
+
+// Encoding
+has_encoding!(Entrypoint, ENTRYPOINT_ENCODING, { Encoding::Custom });
+
+impl NomReader for Entrypoint {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map_res(nom_read::bounded_string(MAX_ENTRYPOINT_LENGTH), Entrypoint::try_from)(input)
+    }
+}
+
+impl BinWriter for Entrypoint {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::string(&self.name, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entrypoint_empty_is_default() {
+        let entrypoint = Entrypoint::try_from(String::new());
+
+        assert_eq!(Ok(Entrypoint::default()), entrypoint);
+    }
+
+    #[test]
+    fn entrypoint_too_large() {
+        let name = "a".repeat(MAX_ENTRYPOINT_LENGTH + 1);
+
+        assert_eq!(
+            Err(EntrypointError::TooLarge(MAX_ENTRYPOINT_LENGTH + 1)),
+            Entrypoint::try_from(name)
+        );
+    }
+
+    #[test]
+    fn entrypoint_encode_decode() {
+        let entrypoint = Entrypoint::try_from("burn_tickets".to_string()).unwrap();
+
+        let mut bytes = Vec::new();
+        entrypoint.bin_write(&mut bytes).unwrap();
+
+        let (remaining, decoded) = Entrypoint::nom_read(bytes.as_slice()).unwrap();
+
+        assert!(remaining.is_empty());
+        assert_eq!(entrypoint, decoded);
+    }
+}
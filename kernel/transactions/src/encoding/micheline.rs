@@ -2,6 +2,7 @@ use tezos_encoding::types::Zarith;
 use tezos_encoding::encoding::{ Encoding, HasEncoding };
 use tezos_encoding::has_encoding;
 use tezos_encoding::nom::{ self as nom_read, NomInput, NomReader, NomResult };
+use tezos_encoding::enc::{ self, BinResult, BinWriter };
 use nom::sequence::{ pair, preceded };
 use nom::combinator::map;
 use nom::bytes::complete::tag;
@@ -43,6 +44,16 @@ pub fn nom_read_micheline_bytes<'a, T: Clone>(
     preceded(tag([MICHELINE_BYTES_TAG]), nom_read::dynamic(parser))
 }
 
+// Serialization combinators
+pub fn bin_write_micheline_bytes<T>(
+    mut bin_write_t: impl FnMut(T, &mut Vec<u8>) -> BinResult
+) -> impl FnMut(T, &mut Vec<u8>) -> BinResult {
+    move |data, output| {
+        enc::put_byte(&MICHELINE_BYTES_TAG, output);
+        enc::dynamic(|data, output| bin_write_t(data, output))(data, output)
+    }
+}
+
 // Nom reader
 
 impl NomReader for MichelineInt {
@@ -72,4 +83,30 @@ impl<Arg1, Arg2, const PRIM_TAG: u8> NomReader
             arg2,
         })(input)
     }
-}
\ No newline at end of file
+}
+// Bin writer
+
+impl BinWriter for MichelineInt {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_byte(&MICHELINE_INT_TAG, output);
+        self.0.bin_write(output)
+    }
+}
+
+impl BinWriter for MichelineString {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_byte(&MICHELINE_STRING_TAG, output);
+        enc::string(&self.0, output)
+    }
+}
+
+impl<Arg1, Arg2, const PRIM_TAG: u8> BinWriter
+    for MichelinePrim2ArgsNoAnnots<Arg1, Arg2, PRIM_TAG>
+    where Arg1: BinWriter + Debug + PartialEq + Eq, Arg2: BinWriter + Debug + PartialEq + Eq
+{
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(&[MICHELINE_PRIM_2_ARGS_NO_ANNOTS_TAG, PRIM_TAG], output);
+        self.arg1.bin_write(output)?;
+        self.arg2.bin_write(output)
+    }
+}
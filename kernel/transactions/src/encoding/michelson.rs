@@ -2,9 +2,15 @@ use super::contract::Contract;
 use std::fmt::Debug;
 use tezos_encoding::encoding::{ Encoding, HasEncoding };
 use tezos_encoding::nom::{ NomReader, NomResult };
+use tezos_encoding::enc::{ self, BinResult, BinWriter };
 use nom::combinator::map;
 
-use super::micheline::{ nom_read_micheline_bytes, MichelinePrim2ArgsNoAnnots };
+use super::micheline::{
+    bin_write_micheline_bytes,
+    nom_read_micheline_bytes,
+    MichelinePrim2ArgsNoAnnots,
+    MICHELINE_PRIM_2_ARGS_NO_ANNOTS_TAG,
+};
 
 use v1_primitives as prim;
 
@@ -53,6 +59,25 @@ impl<Arg0, Arg1> NomReader
     }
 }
 
+// Encoding implement BinWriter
+
+impl BinWriter for MichelsonContract {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        bin_write_micheline_bytes(Contract::bin_write)(&self.0, output)
+    }
+}
+
+impl<Arg0, Arg1> BinWriter
+    for MichelsonPair<Arg0, Arg1>
+    where Arg0: BinWriter + Debug + PartialEq + Eq, Arg1: BinWriter + Debug + PartialEq + Eq
+{
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(&[MICHELINE_PRIM_2_ARGS_NO_ANNOTS_TAG, prim::PAIR_TAG], output);
+        self.0.bin_write(output)?;
+        self.1.bin_write(output)
+    }
+}
+
 impl<Arg0, Arg1> From<MichelinePrim2ArgsNoAnnots<Arg0, Arg1, { prim::PAIR_TAG }>>
     for MichelsonPair<Arg0, Arg1>
     where Arg0: Debug + PartialEq + Eq, Arg1: Debug + PartialEq + Eq
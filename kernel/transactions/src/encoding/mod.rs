@@ -1,4 +1,6 @@
+pub mod bls;
 pub mod contract;
+pub mod entrypoint;
 pub mod micheline;
 pub mod michelson;
 pub mod public_key_hash;
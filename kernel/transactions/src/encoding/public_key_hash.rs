@@ -2,8 +2,9 @@ use crypto::hash::{ ContractTz1Hash, Hash, HashTrait };
 use tezos_encoding::encoding::HasEncoding;
 use tezos_encoding::nom::NomReader;
 use tezos_encoding::enc::BinWriter;
+use serde::{ Deserialize, Serialize };
 
-#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+#[derive(Debug, Clone, PartialEq, Eq, HasEncoding, NomReader, BinWriter, Serialize, Deserialize)]
 pub enum PublicKeyHash {
     //tz1-contract
     Ed25519(ContractTz1Hash),
@@ -4,12 +4,48 @@ use super::michelson::{ MichelsonContract, MichelsonPair };
 use tezos_encoding::enc::{ self };
 use crypto::blake2b::{ digest_256, Blake2bError };
 use thiserror::Error;
-use tezos_encoding::enc::{ BinWriter, BinError };
+use tezos_encoding::enc::{ BinWriter, BinError, BinResult };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ self as nom_read, NomReader, NomResult };
+use tezos_encoding::types::Zarith;
+use nom::bytes::complete::take;
+use nom::combinator::map;
+use nom::number::complete::be_u64;
+use nom::sequence::tuple;
+use num_traits::ToPrimitive;
+use serde::{ Deserialize, Serialize };
+
+// Size of the hash of a string ticket
+pub const STRING_TICKET_HASH_SIZE: usize = 32;
 
 // The hash of a string ticket
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct StringTicketHash(Vec<u8>);
 
+impl StringTicketHash {
+    // Hex-encoding of the hash, usable as a durable storage path step
+    pub fn to_hex(&self) -> String {
+        self.0
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    // Decode the hash from its hex-encoding
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .map(StringTicketHash)
+    }
+}
+
 // Proof that a ticket-identiy matches a ticket
 
 pub struct TrustlessTicketIdentity(StringTicketHash, StringTicket);
@@ -38,6 +74,12 @@ impl TrustlessTicketIdentity {
     }
 }
 
+// errors occuring when converting a ticket from its michelson representation
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TicketConversionError {
+    #[error("Ticket amount is not a valid u64: {0}")] InvalidAmount(String),
+}
+
 /* Define String ticket repr */
 
 pub(crate) type StringTicketRepr = MichelsonPair<
@@ -47,7 +89,7 @@ pub(crate) type StringTicketRepr = MichelsonPair<
 
 /* Define string ticket */
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StringTicket {
     pub(crate) creator: Contract,
     pub(crate) contents: String,
@@ -63,19 +105,107 @@ impl StringTicket {
 
     // Return an identifying hash of the ticket creator and contents
     pub fn identify(&self) -> Result<StringTicketHash, TicketHashError> {
-        let mut bytes = Vec::new();
-        self.creator.bin_write(&mut bytes)?;
-        enc::string(&self.contents, &mut bytes)?;
-        let digest = digest_256(bytes.as_slice())?;
-        Ok(StringTicketHash(digest))
+        identify(&self.creator, &self.contents)
     }
 
     pub fn identify_trustless(self) -> Result<TrustlessTicketIdentity, TicketHashError> {
         Ok(TrustlessTicketIdentity(self.identify()?, self))
     }
 
+    // creator
+    pub fn creator(&self) -> &Contract {
+        &self.creator
+    }
+
+    // contents
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
     // amount
     pub fn amount(&self) -> u64 {
         self.amount
     }
-}
\ No newline at end of file
+}
+
+// Identify the ticket from its michelson representation, without converting it
+pub(crate) fn identify_repr(repr: &StringTicketRepr) -> Result<StringTicketHash, TicketHashError> {
+    let MichelsonPair(
+        MichelsonContract(creator),
+        MichelsonPair(MichelineString(contents), _),
+    ) = repr;
+
+    identify(creator, contents)
+}
+
+fn identify(creator: &Contract, contents: &str) -> Result<StringTicketHash, TicketHashError> {
+    let mut bytes = Vec::new();
+    creator.bin_write(&mut bytes)?;
+    enc::string(contents, &mut bytes)?;
+    let digest = digest_256(bytes.as_slice())?;
+    Ok(StringTicketHash(digest))
+}
+
+impl From<StringTicket> for StringTicketRepr {
+    fn from(ticket: StringTicket) -> Self {
+        MichelsonPair(
+            MichelsonContract(ticket.creator),
+            MichelsonPair(
+                MichelineString(ticket.contents),
+                MichelineInt(Zarith(ticket.amount.into()))
+            )
+        )
+    }
+}
+
+impl TryFrom<StringTicketRepr> for StringTicket {
+    type Error = TicketConversionError;
+
+    fn try_from(repr: StringTicketRepr) -> Result<Self, Self::Error> {
+        let MichelsonPair(
+            MichelsonContract(creator),
+            MichelsonPair(MichelineString(contents), MichelineInt(Zarith(amount))),
+        ) = repr;
+
+        let amount = amount
+            .to_u64()
+            .ok_or_else(|| TicketConversionError::InvalidAmount(amount.to_string()))?;
+
+        Ok(Self { creator, contents, amount })
+    }
+}
+
+// Encoding
+has_encoding!(StringTicketHash, STRING_TICKET_HASH_ENCODING, { Encoding::Custom });
+has_encoding!(StringTicket, STRING_TICKET_ENCODING, { Encoding::Custom });
+
+impl NomReader for StringTicketHash {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map(take(STRING_TICKET_HASH_SIZE), |bytes: &[u8]| StringTicketHash(bytes.to_vec()))(input)
+    }
+}
+
+impl BinWriter for StringTicketHash {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(self.0.as_slice(), output);
+        Ok(())
+    }
+}
+
+// Tickets are stored as creator, contents and amount - rather than as michelson
+impl NomReader for StringTicket {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map(tuple((Contract::nom_read, nom_read::string, be_u64)), |(creator, contents, amount)| {
+            StringTicket { creator, contents, amount }
+        })(input)
+    }
+}
+
+impl BinWriter for StringTicket {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        self.creator.bin_write(output)?;
+        enc::string(&self.contents, output)?;
+        enc::put_bytes(&self.amount.to_be_bytes(), output);
+        Ok(())
+    }
+}
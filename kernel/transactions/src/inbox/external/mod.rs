@@ -1,16 +1,50 @@
 use crypto::hash::Layer2Tz4Hash;
-use tezos_encoding::nom::NomReader;
+use crypto::blake2b::Blake2bError;
+use crate::encoding::bls::BlsPublicKey;
+use tezos_encoding::nom::{ NomReader, NomResult };
 use tezos_encoding::encoding::HasEncoding;
+use tezos_encoding::enc::BinWriter;
+use nom::bytes::complete::tag;
+use nom::combinator::map;
+use nom::sequence::preceded;
 
 pub mod sendable;
 pub mod v1;
 
+const V1_TAG: u8 = 0;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct ExternalInboxMessage<'a>(pub &'a [u8]);
 
+// An external inbox message, parsed according to its version
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParsedExternalInboxMessage<'a> {
+    V1(v1::ParsedBatch<'a>),
+}
+
+impl<'a> ParsedExternalInboxMessage<'a> {
+    pub fn parse(input: &'a [u8]) -> NomResult<Self> {
+        map(preceded(tag([V1_TAG]), v1::ParsedBatch::parse), ParsedExternalInboxMessage::V1)(
+            input
+        )
+    }
+}
+
 // Signer
 
-#[derive(Debug, Clone, PartialEq, Eq, NomReader, HasEncoding)]
+#[derive(Debug, Clone, PartialEq, Eq, NomReader, HasEncoding, BinWriter)]
 pub enum Signer {
     Layer2Address(Layer2Tz4Hash),
+    // reveals the public key of the account, binding it to its tz4 address
+    BlsPublicKey(BlsPublicKey),
+}
+
+impl Signer {
+    // the address of the account signing the operation
+    pub fn address(&self) -> Result<Layer2Tz4Hash, Blake2bError> {
+        match self {
+            Signer::Layer2Address(address) => Ok(address.clone()),
+            Signer::BlsPublicKey(pk) => pk.hash(),
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use super::v1;
-use tezos_encoding_derive::{ HasEncoding };
+use tezos_encoding_derive::{ BinWriter, HasEncoding };
 
-#[derive(Debug, PartialEq, HasEncoding)]
+#[derive(Debug, PartialEq, HasEncoding, BinWriter)]
 pub enum ExternalInboxMessage {
     // version 1 of operation batching
     V1(v1::sendable::Batch),
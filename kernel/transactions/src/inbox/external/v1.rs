@@ -1,9 +1,14 @@
 use crypto::hash::{ Layer2Tz4Hash };
+use crate::encoding::bls::BlsSignature;
+use crate::encoding::contract::Contract;
+use crate::encoding::entrypoint::Entrypoint;
 use crate::encoding::string_ticket::StringTicketRepr;
 use tezos_encoding::encoding::HasEncoding;
+use tezos_encoding::enc::BinWriter;
 use verifiable::VerifiableTransaction;
 use nom::multi::many1;
 use nom::combinator::map;
+use nom::sequence::pair;
 use tezos_encoding::nom::{ dynamic, NomReader };
 
 use super::Signer;
@@ -12,16 +17,25 @@ pub mod sendable;
 pub mod verifiable;
 
 // transfer
-#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader)]
+#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
 pub struct OperationTransfer {
-    destination: Layer2Tz4Hash,
-    ticket: StringTicketRepr,
+    pub(crate) destination: Layer2Tz4Hash,
+    pub(crate) ticket: StringTicketRepr,
 }
 
-// an operation transfer ticket first
-#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader)]
+// withdrawal of a ticket back to a layer 1 contract
+#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
+pub struct OperationWithdrawal {
+    pub(crate) destination: Contract,
+    pub(crate) ticket: StringTicketRepr,
+    pub(crate) entrypoint: Entrypoint,
+}
+
+// an operation either transfers a ticket on layer 2, or withdraws it to layer 1
+#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
 pub enum OperationContent {
     Transfer(OperationTransfer),
+    Withdrawal(OperationWithdrawal),
 }
 
 impl OperationContent {
@@ -35,13 +49,27 @@ impl OperationContent {
             ticket: ticket.into(),
         })
     }
+
+    // create a new withdrawal operation
+    pub fn withdrawal(
+        destination: Contract,
+        ticket: impl Into<StringTicketRepr>,
+        entrypoint: Entrypoint
+    ) -> OperationContent {
+        OperationContent::Withdrawal(OperationWithdrawal {
+            destination,
+            ticket: ticket.into(),
+            entrypoint,
+        })
+    }
 }
 
 // operation
-#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader)]
+#[derive(Debug, PartialEq, Eq, HasEncoding, NomReader, BinWriter)]
 pub struct Operation {
     pub signer: Signer,
     pub counter: i64,
+    #[encoding(dynamic, list)]
     pub contents: Vec<OperationContent>,
 }
 
@@ -49,13 +77,18 @@ pub struct Operation {
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParsedBatch<'a> {
     pub transactions: Vec<VerifiableTransaction<'a>>,
+    pub aggregated_signature: BlsSignature,
 }
 
 impl<'a> ParsedBatch<'a> {
     // parse a batch where each transaction is verifiable
     pub fn parse(input: &'a [u8]) -> tezos_encoding::nom::NomResult<Self> {
-        map(dynamic(many1(VerifiableTransaction::parse)), |transactions| ParsedBatch {
-            transactions,
-        })(input)
+        map(
+            pair(dynamic(many1(VerifiableTransaction::parse)), BlsSignature::nom_read),
+            |(transactions, aggregated_signature)| ParsedBatch {
+                transactions,
+                aggregated_signature,
+            }
+        )(input)
     }
 }
\ No newline at end of file
@@ -1,34 +1,343 @@
 use super::{ Operation };
+use crate::encoding::bls::{ BlsError, BlsSecretKey, BlsSignature };
+use crypto::blake2b::Blake2bError;
+use crypto::hash::Layer2Tz4Hash;
+use thiserror::Error;
+use tezos_encoding::enc::{ self, BinError, BinResult, BinWriter };
 use tezos_encoding::encoding::{ Encoding, HasEncoding };
 use tezos_encoding::has_encoding;
 
 // transaction
-#[derive(Debug, PartialEq, HasEncoding)]
+#[derive(Debug, PartialEq, HasEncoding, BinWriter)]
 pub struct Transaction {
     #[encoding(dynamic, list)]
     operations: Vec<Operation>,
 }
 
 impl Transaction {
+    // create a new transaction from a list of operations
+
+    pub fn new(operations: Vec<Operation>) -> Self {
+        Self { operations }
+    }
+
     // Get the operations
 
     pub fn operations(&self) -> &[Operation] {
         self.operations.as_slice()
     }
+
+    // the bytes of the transaction, signed by each of its signers
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinError> {
+        let mut bytes = Vec::new();
+        self.bin_write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // the addresses of the signers of the transaction, each listed once
+    fn signers(&self) -> Result<Vec<Layer2Tz4Hash>, Blake2bError> {
+        let mut signers = Vec::new();
+
+        for operation in self.operations.iter() {
+            let address = operation.signer.address()?;
+            if !signers.contains(&address) {
+                signers.push(address);
+            }
+        }
+        Ok(signers)
+    }
+}
+
+// errors occuring when signing a batch
+#[derive(Error, Debug)]
+pub enum SigningError {
+    #[error("No secret key given for signer {0}")] MissingKey(Layer2Tz4Hash),
+    #[error("Unable to hash public key: {0}")] Hashing(#[from] Blake2bError),
+    #[error("Unable to encode transaction: {0}")] Encoding(#[from] BinError),
+    #[error("{0}")] Bls(#[from] BlsError),
 }
 
 // batch
 #[derive(Debug, PartialEq)]
 pub struct Batch {
     transactions: Vec<Transaction>,
+    aggregated_signature: BlsSignature,
 }
 
 has_encoding!(Batch, SENDABLE_BATCH_ENCODING, { Encoding::Custom });
 
 impl Batch {
-    // create a new batch from a list of transactions
+    // create a new batch from a list of transactions, and the aggregate of their signatures
+
+    pub fn new(transactions: Vec<Transaction>, aggregated_signature: BlsSignature) -> Self {
+        Self { transactions, aggregated_signature }
+    }
+
+    // Create a batch, where every transaction is signed by each of its signers.
+    //
+    // The secret key of every signer must be among `keys`.
+    pub fn sign(
+        transactions: Vec<Transaction>,
+        keys: &[BlsSecretKey]
+    ) -> Result<Self, SigningError> {
+        let keys = keys
+            .iter()
+            .map(|sk| Ok((sk.public_key().hash()?, sk)))
+            .collect::<Result<Vec<_>, Blake2bError>>()?;
+
+        let mut signatures = Vec::new();
+        for transaction in transactions.iter() {
+            let bytes = transaction.to_bytes()?;
+
+            for signer in transaction.signers()? {
+                let (_, sk) = keys
+                    .iter()
+                    .find(|(address, _)| address == &signer)
+                    .ok_or(SigningError::MissingKey(signer))?;
+
+                signatures.push(sk.sign(bytes.as_slice()));
+            }
+        }
+
+        let aggregated_signature = BlsSignature::aggregate(signatures.as_slice())?;
+        Ok(Self::new(transactions, aggregated_signature))
+    }
 
-    pub fn new(transactions: Vec<Transaction>) -> Self {
-        Self { transactions }
+    // Get the transactions
+
+    pub fn transactions(&self) -> &[Transaction] {
+        self.transactions.as_slice()
+    }
+
+    // the bytes of the batch, as parsed by `ParsedBatch::parse`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BinError> {
+        let mut bytes = Vec::new();
+        self.bin_write(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl BinWriter for Batch {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::dynamic(|transactions: &Vec<Transaction>, output| {
+            transactions.iter().try_for_each(|transaction| transaction.bin_write(output))
+        })(&self.transactions, output)?;
+
+        self.aggregated_signature.bin_write(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use tezos_encoding::nom::NomReader;
+
+    use crate::encoding::contract::Contract;
+    use crate::encoding::entrypoint::Entrypoint;
+    use crate::encoding::public_key_hash::PublicKeyHash;
+    use crate::encoding::string_ticket::StringTicket;
+    use crate::inbox::external::v1::{ OperationContent, ParsedBatch };
+    use crate::inbox::external::Signer;
+    use crate::inbox::sendable::{ ExternalInboxMessage, InboxMessage };
+    use crate::inbox::{ self, ParsedExternalInboxMessage };
+    use crate::memory::Accounts;
+
+    fn contract_of(byte: u8) -> Contract {
+        Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![byte; 20])))
+    }
+
+    fn ticket(amount: u64) -> StringTicket {
+        StringTicket::new(contract_of(1), "Hello, Ticket!".to_string(), amount)
     }
-}
\ No newline at end of file
+
+    fn operation(signer: Signer, counter: i64, contents: Vec<OperationContent>) -> Operation {
+        Operation { signer, counter, contents }
+    }
+
+    fn sample_transactions(keys: &[BlsSecretKey]) -> Vec<Transaction> {
+        let first = keys[0].public_key();
+        let second = keys[1].public_key();
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+
+        vec![
+            Transaction::new(
+                vec![
+                    operation(
+                        Signer::BlsPublicKey(first.clone()),
+                        0,
+                        vec![
+                            OperationContent::transfer(receiver.clone(), ticket(1)),
+                            OperationContent::transfer(second.hash().unwrap(), ticket(2))
+                        ]
+                    ),
+                    operation(
+                        Signer::BlsPublicKey(second),
+                        0,
+                        vec![
+                            OperationContent::withdrawal(
+                                contract_of(3),
+                                ticket(3),
+                                Entrypoint::default()
+                            )
+                        ]
+                    )
+                ]
+            ),
+            Transaction::new(
+                vec![
+                    operation(
+                        Signer::Layer2Address(first.hash().unwrap()),
+                        1,
+                        vec![OperationContent::transfer(receiver, ticket(4))]
+                    )
+                ]
+            )
+        ]
+    }
+
+    fn keys() -> Vec<BlsSecretKey> {
+        vec![BlsSecretKey::from_ikm(&[1; 32]).unwrap(), BlsSecretKey::from_ikm(&[2; 32]).unwrap()]
+    }
+
+    #[test]
+    fn batch_encode_parse_roundtrip() {
+        // Arrange
+        let keys = keys();
+        let batch = Batch::sign(sample_transactions(&keys), &keys).unwrap();
+
+        // Act
+        let bytes = batch.to_bytes().unwrap();
+        let (remaining, parsed) = ParsedBatch::parse(bytes.as_slice()).unwrap();
+
+        // Assert
+        assert!(remaining.is_empty());
+        assert_eq!(batch.transactions().len(), parsed.transactions.len());
+
+        let transactions = batch.transactions().iter().zip(parsed.transactions.iter());
+        for (transaction, verifiable) in transactions {
+            assert_eq!(transaction.to_bytes().unwrap().as_slice(), verifiable.encoded());
+
+            let operations: Vec<&Operation> = verifiable.operations().collect();
+            let expected: Vec<&Operation> = transaction.operations().iter().collect();
+            assert_eq!(expected, operations);
+        }
+
+        assert_eq!(batch.aggregated_signature, parsed.aggregated_signature);
+    }
+
+    #[test]
+    fn batch_signature_verifies() {
+        // Arrange
+        let keys = keys();
+        let batch = Batch::sign(sample_transactions(&keys), &keys).unwrap();
+        let bytes = batch.to_bytes().unwrap();
+
+        // Act
+        let (_, parsed) = ParsedBatch::parse(bytes.as_slice()).unwrap();
+
+        // Assert
+        assert!(parsed.verify_signature(&Accounts::default()).is_ok());
+    }
+
+    #[test]
+    fn batch_sign_missing_key() {
+        // Arrange
+        let keys = keys();
+        let transactions = sample_transactions(&keys);
+
+        // Act
+        let result = Batch::sign(transactions, &keys[..1]);
+
+        // Assert
+        assert!(matches!(result, Err(SigningError::MissingKey(_))));
+    }
+
+    #[test]
+    fn inbox_message_encode_parse_roundtrip() {
+        // Arrange
+        let keys = keys();
+        let batch = Batch::sign(sample_transactions(&keys), &keys).unwrap();
+        let batch_bytes = batch.to_bytes().unwrap();
+
+        let message = InboxMessage::External(ExternalInboxMessage::V1(batch));
+
+        // Act
+        let mut bytes = Vec::new();
+        message.bin_write(&mut bytes).unwrap();
+
+        // Assert
+        let (_, parsed) = inbox::InboxMessage::parse(bytes.as_slice()).unwrap();
+        let external = match parsed {
+            inbox::InboxMessage::External(inbox::ExternalInboxMessage(external)) => external,
+            inbox::InboxMessage::Internal(_) => panic!("Expected an external message"),
+        };
+
+        let (remaining, ParsedExternalInboxMessage::V1(parsed)) = ParsedExternalInboxMessage::parse(
+            external
+        ).unwrap();
+        assert!(remaining.is_empty());
+
+        let (_, expected) = ParsedBatch::parse(batch_bytes.as_slice()).unwrap();
+        assert_eq!(expected, parsed);
+    }
+
+    #[test]
+    fn operation_encode_decode() {
+        // Arrange
+        let keys = keys();
+        let transactions = sample_transactions(&keys);
+
+        for operation in transactions.iter().flat_map(|t| t.operations()) {
+            // Act
+            let mut bytes = Vec::new();
+            operation.bin_write(&mut bytes).unwrap();
+            let (remaining, decoded) = Operation::nom_read(bytes.as_slice()).unwrap();
+
+            // Assert
+            assert!(remaining.is_empty());
+            assert_eq!(operation, &decoded);
+        }
+    }
+
+    #[test]
+    fn operation_encoding_fixed_bytes() {
+        // Arrange
+        let operation = operation(
+            Signer::Layer2Address(Layer2Tz4Hash(vec![2; 20])),
+            1,
+            vec![
+                OperationContent::transfer(
+                    Layer2Tz4Hash(vec![3; 20]),
+                    StringTicket::new(contract_of(1), "Hi".to_string(), 1)
+                )
+            ]
+        );
+
+        /* the contents are prefixed by their length in bytes, so that an operation may be
+           followed by another in the same transaction */
+        let expected = [
+            vec![0],
+            vec![2; 20],
+            1i64.to_be_bytes().to_vec(),
+            vec![0, 0, 0, 61],
+            vec![0],
+            vec![3; 20],
+            vec![7, 7, 10, 0, 0, 0, 22, 0, 0],
+            vec![1; 20],
+            vec![7, 7, 1, 0, 0, 0, 2],
+            b"Hi".to_vec(),
+            vec![0, 1],
+        ].concat();
+
+        // Act
+        let mut bytes = Vec::new();
+        operation.bin_write(&mut bytes).unwrap();
+        let (remaining, decoded) = Operation::nom_read(expected.as_slice()).unwrap();
+
+        // Assert
+        assert_eq!(expected, bytes);
+        assert!(remaining.is_empty());
+        assert_eq!(operation, decoded);
+    }
+}
@@ -1,8 +1,14 @@
+use alloc::collections::BTreeMap;
+use crypto::blake2b::Blake2bError;
+use crypto::hash::Layer2Tz4Hash;
 use nom::multi::many1;
 use nom::combinator::{ consumed, map };
+use thiserror::Error;
 use tezos_encoding::nom::{ dynamic, NomReader };
+use crate::encoding::bls::{ BlsError, BlsPublicKey };
 use crate::inbox::external::Signer;
-use super::{ Operation };
+use crate::memory::Accounts;
+use super::{ Operation, ParsedBatch };
 
 #[derive(Debug, PartialEq, Eq, NomReader)]
 pub struct VerifiableOperation {
@@ -10,7 +16,7 @@ pub struct VerifiableOperation {
 }
 
 impl VerifiableOperation {
-    fn signer(&self) -> &Signer {
+    pub fn signer(&self) -> &Signer {
         &self.operation.signer
     }
 }
@@ -31,4 +37,174 @@ impl<'a> VerifiableTransaction<'a> {
             }
         )(input)
     }
-}
\ No newline at end of file
+
+    // the bytes of the transaction, signed by each of its signers
+    pub fn encoded(&self) -> &'a [u8] {
+        self.encoded
+    }
+
+    // the operations of the transaction, in the order they should be applied
+    pub fn operations(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().map(|op| &op.operation)
+    }
+
+    // consume the transaction, returning its operations
+    pub fn into_operations(self) -> impl Iterator<Item = Operation> {
+        self.operations.into_iter().map(|op| op.operation)
+    }
+}
+
+// errors occuring when verifying the signature of a batch
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("No public key revealed for account {0}")] UnknownPublicKey(Layer2Tz4Hash),
+    #[error("Unable to hash public key: {0}")] Hashing(#[from] Blake2bError),
+    #[error("{0}")] Bls(#[from] BlsError),
+}
+
+impl<'a> ParsedBatch<'a> {
+    // Verify the aggregated signature of the batch.
+    //
+    // Every signer of a transaction must have signed its encoded bytes. The public key of
+    // a signer is either linked to its account, or revealed earlier in the batch.
+    pub fn verify_signature(&self, accounts: &Accounts) -> Result<(), SignatureError> {
+        let mut revealed: BTreeMap<Layer2Tz4Hash, &BlsPublicKey> = BTreeMap::new();
+        let mut messages = Vec::new();
+
+        for transaction in self.transactions.iter() {
+            let mut signers: Vec<&BlsPublicKey> = Vec::new();
+
+            for operation in transaction.operations() {
+                let pk = match &operation.signer {
+                    Signer::BlsPublicKey(pk) => {
+                        revealed.insert(pk.hash()?, pk);
+                        pk
+                    }
+                    Signer::Layer2Address(address) =>
+                        accounts
+                            .account_of(address)
+                            .and_then(|account| account.public_key())
+                            .or_else(|| revealed.get(address).copied())
+                            .ok_or_else(|| SignatureError::UnknownPublicKey(address.clone()))?,
+                };
+
+                // a signer signs the transaction once, however many operations it has
+                if !signers.contains(&pk) {
+                    signers.push(pk);
+                }
+            }
+
+            messages.extend(signers.into_iter().map(|pk| (pk, transaction.encoded())));
+        }
+
+        self.aggregated_signature.aggregate_verify(messages.into_iter())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::bls::BlsSignature;
+    use crate::encoding::bls::tests::{ key_pair, sign };
+    use crate::memory::Account;
+
+    fn transaction<'a>(encoded: &'a [u8], signers: &[Signer]) -> VerifiableTransaction<'a> {
+        let operations = signers
+            .iter()
+            .enumerate()
+            .map(|(counter, signer)| VerifiableOperation {
+                operation: Operation {
+                    signer: signer.clone(),
+                    counter: counter as i64,
+                    contents: vec![],
+                },
+            })
+            .collect();
+
+        VerifiableTransaction { encoded, operations }
+    }
+
+    #[test]
+    fn verify_signature_revealed_and_linked_keys() {
+        // Arrange
+        let (sk1, pk1) = key_pair(1);
+        let (sk2, pk2) = key_pair(2);
+        let address1 = pk1.hash().unwrap();
+        let address2 = pk2.hash().unwrap();
+
+        let mut account = Account::default();
+        account.link_public_key(pk2.clone()).unwrap();
+
+        let mut accounts = Accounts::default();
+        accounts.add_account(address2.clone(), account).unwrap();
+
+        let first = b"first transaction".as_slice();
+        let second = b"second transaction".as_slice();
+
+        let batch = ParsedBatch {
+            transactions: vec![
+                transaction(
+                    first,
+                    &[Signer::BlsPublicKey(pk1.clone()), Signer::Layer2Address(address2)]
+                ),
+                transaction(
+                    second,
+                    &[Signer::Layer2Address(address1.clone()), Signer::Layer2Address(address1)]
+                )
+            ],
+            aggregated_signature: BlsSignature::aggregate(
+                &[sign(&sk1, &pk1, first), sign(&sk2, &pk2, first), sign(&sk1, &pk1, second)]
+            ).unwrap(),
+        };
+
+        // Act
+        let result = batch.verify_signature(&accounts);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn verify_signature_missing_signer() {
+        // Arrange
+        let (sk1, pk1) = key_pair(1);
+        let (_sk2, pk2) = key_pair(2);
+
+        let encoded = b"transaction".as_slice();
+        let batch = ParsedBatch {
+            transactions: vec![
+                transaction(
+                    encoded,
+                    &[Signer::BlsPublicKey(pk1.clone()), Signer::BlsPublicKey(pk2)]
+                )
+            ],
+            aggregated_signature: sign(&sk1, &pk1, encoded),
+        };
+
+        // Act
+        let result = batch.verify_signature(&Accounts::default());
+
+        // Assert
+        assert!(matches!(result, Err(SignatureError::Bls(BlsError::VerificationFailed(_)))));
+    }
+
+    #[test]
+    fn verify_signature_unknown_public_key() {
+        // Arrange
+        let (sk, pk) = key_pair(1);
+        let address = pk.hash().unwrap();
+
+        let encoded = b"transaction".as_slice();
+        let batch = ParsedBatch {
+            transactions: vec![transaction(encoded, &[Signer::Layer2Address(address)])],
+            aggregated_signature: sign(&sk, &pk, encoded),
+        };
+
+        // Act
+        let result = batch.verify_signature(&Accounts::default());
+
+        // Assert
+        assert!(matches!(result, Err(SignatureError::UnknownPublicKey(_))));
+    }
+}
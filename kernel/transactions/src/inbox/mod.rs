@@ -1,12 +1,15 @@
 /* This is an inbox messages */
 
-use crypto::hash::{ Layer2Tz4Hash };
+use crypto::base58::FromBase58CheckError;
+use crypto::hash::{ HashTrait, Layer2Tz4Hash };
+use thiserror::Error;
 use tezos_encoding::encoding::HasEncoding;
 use tezos_encoding::nom::NomReader;
+use tezos_encoding::enc::BinWriter;
 use nom::combinator::{ map, rest };
 use crate::encoding::micheline::MichelineString;
 use crate::encoding::michelson::MichelsonPair;
-use crate::encoding::string_ticket::{ StringTicket, StringTicketRepr };
+use crate::encoding::string_ticket::{ StringTicket, StringTicketRepr, TicketConversionError };
 
 pub mod external;
 pub mod sendable;
@@ -42,7 +45,7 @@ impl<'a> InboxMessage<'a> {
 }
 
 // Declare InternalInboxMessage
-#[derive(Debug, PartialEq, Eq, NomReader, HasEncoding)]
+#[derive(Debug, PartialEq, Eq, NomReader, HasEncoding, BinWriter)]
 
 // Define only payload and source
 pub struct InternalInboxMessage {
@@ -56,4 +59,36 @@ pub type InternalMessagePayloadRepr = MichelsonPair<MichelineString, StringTicke
 pub struct InboxDeposit {
     pub destination: Layer2Tz4Hash,
     pub ticket: StringTicket,
-}
\ No newline at end of file
+}
+
+// errors occuring when reading a deposit from an internal message payload
+#[derive(Error, Debug)]
+pub enum DepositFromPayloadError {
+    #[error("Invalid layer 2 destination: {0}")] InvalidDestination(#[from] FromBase58CheckError),
+    #[error("{0}")] InvalidTicket(#[from] TicketConversionError),
+}
+
+impl TryFrom<InternalMessagePayloadRepr> for InboxDeposit {
+    type Error = DepositFromPayloadError;
+
+    fn try_from(payload: InternalMessagePayloadRepr) -> Result<Self, Self::Error> {
+        let MichelsonPair(MichelineString(destination), ticket) = payload;
+
+        Ok(InboxDeposit {
+            destination: Layer2Tz4Hash::from_b58check(&destination)?,
+            ticket: StringTicket::try_from(ticket)?,
+        })
+    }
+}
+
+// the internal message of a deposit, as sent by the layer 1 bridge contract
+impl From<InboxDeposit> for InternalInboxMessage {
+    fn from(deposit: InboxDeposit) -> Self {
+        InternalInboxMessage {
+            payload: MichelsonPair(
+                MichelineString(deposit.destination.to_b58check()),
+                deposit.ticket.into()
+            ),
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use tezos_encoding_derive::{ HasEncoding };
+use tezos_encoding_derive::{ BinWriter, HasEncoding };
 
 pub use super::{ external::sendable::ExternalInboxMessage, InternalInboxMessage };
 
-#[derive(Debug, PartialEq, HasEncoding)]
+#[derive(Debug, PartialEq, HasEncoding, BinWriter)]
 pub enum InboxMessage {
     Internal(InternalInboxMessage),
     External(ExternalInboxMessage),
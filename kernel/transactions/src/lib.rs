@@ -14,17 +14,35 @@ pub mod memory;
 pub mod encoding;
 pub mod inbox;
 pub mod deposit;
+pub mod outbox;
+pub mod withdrawal;
+pub mod transfer;
+pub mod transaction;
+pub mod slot;
+pub mod ticket_registry;
 
-use host::input::Input;
+use host::input::{ Input, MessageData, SlotData };
+use host::path::{ Path, RefPath, PATH_KERNEL_BOOT, PATH_KERNEL_NEXT };
 use host::rollup_core::{ RawRollupCore, MAX_INPUT_MESSAGE_SIZE, MAX_INPUT_SLOT_DATA_CHUNK_SIZE };
+use host::runtime::Runtime;
 
-use deposit::{ deposit_ticket };
+use deposit::{ deposit_ticket, DepositError };
+use slot::reassemble_slot_chunk;
+use transaction::process_batch;
 use debug::debug_msg;
 use thiserror::Error;
 use tezos_encoding::nom::error::DecodeError;
 
-use crate::inbox::{ InboxDeposit, InboxMessage, InternalInboxMessage };
-use crate::memory::Memory;
+use crate::inbox::{
+    DepositFromPayloadError,
+    ExternalInboxMessage,
+    InboxDeposit,
+    InboxMessage,
+    InternalInboxMessage,
+    ParsedExternalInboxMessage,
+};
+use crate::encoding::string_ticket::TicketHashError;
+use crate::memory::{ AccountStorageError, Memory };
 
 const MAX_READ_INPUT_SIZE: usize = if MAX_INPUT_MESSAGE_SIZE > MAX_INPUT_SLOT_DATA_CHUNK_SIZE {
     MAX_INPUT_MESSAGE_SIZE
@@ -32,23 +50,180 @@ const MAX_READ_INPUT_SIZE: usize = if MAX_INPUT_MESSAGE_SIZE > MAX_INPUT_SLOT_DA
     MAX_INPUT_SLOT_DATA_CHUNK_SIZE
 };
 
+/* An input read once the budget ran out, kept for the next call */
+const PENDING_INPUT_PATH: RefPath = RefPath::assert_from(b"/tx/pending_input");
+const PENDING_MESSAGE_TAG: u8 = 0;
+const PENDING_SLOT_TAG: u8 = 1;
+
+const DEFAULT_MAX_MESSAGES: usize = 64;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+
+/// Limits on the inputs read by a single call of the kernel, to stay within its tick limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputBudget {
+    /// Maximum number of inputs read.
+    pub max_messages: usize,
+    /// Maximum number of input bytes read.
+    pub max_bytes: usize,
+}
+
+impl Default for InputBudget {
+    fn default() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl InputBudget {
+    // whether another input may be read, after `messages` inputs of `bytes` in total
+    fn allows(&self, messages: usize, bytes: usize) -> bool {
+        messages < self.max_messages && bytes < self.max_bytes
+    }
+}
+
 /* Entrypoint of the *transactions* kernel */
 pub fn transactions_run<Host: RawRollupCore>(host: &mut Host) {
+    transactions_run_with_budget(host, InputBudget::default())
+}
+
+/// Read and process inputs until the inbox is drained, or the `budget` runs out.
+///
+/// When the budget runs out while inputs remain, a reboot is requested through
+/// [PATH_KERNEL_NEXT], and the remaining inputs are read by the next call.
+pub fn transactions_run_with_budget<Host: RawRollupCore>(host: &mut Host, budget: InputBudget) {
     // each kernel has one memory
     let mut memory = Memory::load_memory(host);
-    /* if there is some input, use host.read_input to match 
-       what kinds of input it is: message or a slot
-     */
-    match host.read_input(MAX_READ_INPUT_SIZE) {
-        Some(Input::Message(message)) => {
+
+    // carrying on from a call that ran out of budget
+    if Runtime::store_has(host, &PATH_KERNEL_NEXT).is_some() {
+        let _ = Runtime::store_delete(host, &PATH_KERNEL_NEXT);
+    }
+    let mut pending = take_pending_input(host);
+
+    let mut messages = 0;
+    let mut bytes = 0;
+
+    loop {
+        let input = pending.take().or_else(|| Runtime::read_input(host, MAX_READ_INPUT_SIZE));
+        let input = match input {
+            Some(input) => input,
+            None => break,
+        };
+
+        if !budget.allows(messages, bytes) {
+            debug_msg!(Host, "Input budget exhausted after {} inputs, {} bytes", messages, bytes);
+
+            // the input is already read: keep it, and reboot into the same kernel to read
+            // it along with the remaining inputs
+            save_pending_input(host, &input);
+            if let Err(err) = Runtime::store_write(
+                host,
+                &PATH_KERNEL_NEXT,
+                PATH_KERNEL_BOOT.as_bytes(),
+                0
+            ) {
+                debug_msg!(Host, "Unable to request reboot {:?}", err);
+            }
+            break;
+        }
+
+        messages += 1;
+        bytes += match &input {
+            Input::Message(message) => message.as_ref().len(),
+            Input::Slot(slot) => slot.as_ref().len(),
+        };
+
+        process_input(host, &mut memory, input);
+
+        #[cfg(any(test, feature = "check-supply"))]
+        if let Err(err) = memory.accounts().check_supply(host) {
+            panic!("Ticket supply invariant broken: {}", err);
+        }
+    }
+
+    // flush memory, so that it survives until the next call
+    memory.save_memory(host);
+}
+
+/* Pending input is stored as its tag, level and id, followed by its payload */
+fn save_pending_input<Host: RawRollupCore>(host: &mut Host, input: &Input) {
+    let (tag, level, id, payload) = match input {
+        Input::Message(message) =>
+            (PENDING_MESSAGE_TAG, message.level, message.id, message.as_ref()),
+        Input::Slot(slot) => (PENDING_SLOT_TAG, slot.level, slot.id, slot.as_ref()),
+    };
+
+    let mut bytes = Vec::with_capacity(9 + payload.len());
+    bytes.push(tag);
+    bytes.extend_from_slice(&level.to_be_bytes());
+    bytes.extend_from_slice(&id.to_be_bytes());
+    bytes.extend_from_slice(payload);
+
+    host::runtime::save_value_sized(host, &PENDING_INPUT_PATH, bytes.as_slice());
+}
+
+fn take_pending_input<Host: RawRollupCore>(host: &mut Host) -> Option<Input> {
+    Runtime::store_has(host, &PENDING_INPUT_PATH)?;
+
+    let bytes = host::runtime::load_value_sized(host, &PENDING_INPUT_PATH);
+    let _ = Runtime::store_delete(host, &PENDING_INPUT_PATH);
+
+    let bytes = match bytes {
+        Ok(bytes) if bytes.len() >= 9 => bytes,
+        _ => {
+            debug_msg!(Host, "Unable to load pending input");
+            return None;
+        }
+    };
+    let level = i32::from_be_bytes(bytes[1..5].try_into().ok()?);
+    let id = i32::from_be_bytes(bytes[5..9].try_into().ok()?);
+    let payload = bytes[9..].to_vec();
+
+    match bytes[0] {
+        PENDING_MESSAGE_TAG => Some(Input::Message(MessageData::new(level, id, payload))),
+        PENDING_SLOT_TAG => Some(Input::Slot(SlotData::new(level, id, payload))),
+        _ => None,
+    }
+}
+
+/* if there is some input, match what kinds of input it is: message or a slot */
+fn process_input<Host: RawRollupCore>(host: &mut Host, memory: &mut Memory, input: Input) {
+    match input {
+        Input::Message(message) => {
             debug_msg!(Host, "Processing MessageData {} at level {}", message.id, message.level);
 
-            if let Err(err) = process_header_payload(host, &mut memory, message.as_ref()) {
+            if let Err(err) = process_header_payload(
+                host,
+                memory,
+                message.level,
+                message.id,
+                message.as_ref()
+            ) {
                 debug_msg!(Host, "Error processing header payload {}", err);
             }
         }
-        Some(Input::Slot(_message)) => todo!("handle slot message"),
-        None => {}
+        Input::Slot(slot) => {
+            debug_msg!(Host, "Processing SlotData {} at level {}", slot.id, slot.level);
+
+            // a slot is only processed once all of its chunks have been received
+            match reassemble_slot_chunk(host, slot.level, slot.id, slot.as_ref()) {
+                Ok(Some(message)) => {
+                    if let Err(err) = process_external_message(
+                        host,
+                        memory,
+                        slot.level,
+                        slot.id,
+                        message.as_slice()
+                    ) {
+                        debug_msg!(Host, "Error processing slot message {}", err);
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => debug_msg!(Host, "Error reassembling slot data {}", err),
+            }
+        }
     }
 }
 
@@ -58,6 +233,10 @@ enum TransactionError<'a> {
     #[error("unable to parse header inbox message {0}")] MalformedInboxMessage(
         nom::Err<DecodeError<&'a [u8]>>,
     ),
+    #[error("invalid deposit {0}")] InvalidDeposit(#[from] DepositFromPayloadError),
+    #[error("unable to deposit ticket {0}")] Deposit(#[from] DepositError),
+    #[error("unable to load account {0}")] Storage(#[from] AccountStorageError),
+    #[error("unable to identify ticket {0}")] TicketHash(#[from] TicketHashError),
 }
 
 /* Define process_header_payload in transactions_run */
@@ -65,6 +244,8 @@ enum TransactionError<'a> {
 fn process_header_payload<'a, Host: RawRollupCore>(
     host: &mut Host,
     memory: &mut Memory,
+    level: i32,
+    id: i32,
     payload: &'a [u8]
 ) -> Result<(), TransactionError<'a>> {
     let (remaining, message) = InboxMessage::parse(payload).map_err(
@@ -75,19 +256,320 @@ fn process_header_payload<'a, Host: RawRollupCore>(
         InboxMessage::Internal(InternalInboxMessage { payload, .. }) => {
             let InboxDeposit { destination, ticket } = payload.try_into()?;
 
+            memory.accounts_mut().load_account(host, &destination)?;
+            memory.accounts_mut().load_supply(host, &ticket.identify()?)?;
+
             deposit_ticket::<Host>(memory, destination, ticket)?;
 
             // Internal inbox message - not batched
             debug_assert!(remaining.is_empty());
             Ok(())
         }
+        InboxMessage::External(ExternalInboxMessage(bytes)) =>
+            process_external_message(host, memory, level, id, bytes),
     }
 }
 
+/* External messages are received from the inbox, or reassembled from slot data */
+
+fn process_external_message<'a, Host: RawRollupCore>(
+    host: &mut Host,
+    memory: &mut Memory,
+    level: i32,
+    id: i32,
+    bytes: &'a [u8]
+) -> Result<(), TransactionError<'a>> {
+    let (remaining, ParsedExternalInboxMessage::V1(batch)) = ParsedExternalInboxMessage::parse(
+        bytes
+    ).map_err(TransactionError::MalformedInboxMessage)?;
+
+    process_batch(host, memory, level, id, batch);
+
+    // External message - one batch per message
+    debug_assert!(remaining.is_empty());
+    Ok(())
+}
+
 /* Define the `kernel_next` for the transactions kernel */
 #[cfg(feature = "tx-kernel")]
 pub mod tx_kernel {
     use crate::transactions_run;
     use kernel::kernel_entry;
     kernel_entry!(transactions_run);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::{ ContractTz1Hash, HashTrait, Layer2Tz4Hash };
+    use host::rollup_core::Input as InputType;
+    use mock_runtime::host::MockHost;
+    use mock_runtime::replay::{ replay, Inbox, InboxInput, InboxLevel };
+    use mock_runtime::state::HostState;
+    use tezos_encoding::enc::BinWriter;
+
+    use crate::encoding::{
+        bls::{ tests::{ key_pair, sign }, BlsSignature },
+        contract::Contract,
+        public_key_hash::PublicKeyHash,
+        string_ticket::StringTicket,
+    };
+    use crate::inbox::v1::{ sendable::Transaction, Operation, OperationContent };
+    use crate::inbox::{ sendable, Signer };
+    use crate::memory::tests::assert_supply_invariant;
+    use crate::transaction::{ read_receipt, OperationReceipt };
+
+    fn ticket(amount: u64) -> StringTicket {
+        let creator = Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![1; 20])));
+        StringTicket::new(creator, "Hello, Ticket!".to_string(), amount)
+    }
+
+    fn deposit_message(destination: &Layer2Tz4Hash, ticket: StringTicket) -> Vec<u8> {
+        let deposit = InboxDeposit { destination: destination.clone(), ticket };
+
+        let mut message = Vec::new();
+        sendable::InboxMessage::Internal(deposit.into()).bin_write(&mut message).unwrap();
+        message
+    }
+
+    // A transaction made of a single transfer, as read by `VerifiableTransaction::parse`
+    fn transfer_transaction(
+        signer: &Signer,
+        counter: i64,
+        destination: &Layer2Tz4Hash,
+        ticket: StringTicket
+    ) -> Vec<u8> {
+        let operation = Operation {
+            signer: signer.clone(),
+            counter,
+            contents: vec![OperationContent::transfer(destination.clone(), ticket)],
+        };
+
+        Transaction::new(vec![operation]).to_bytes().unwrap()
+    }
+
+    #[test]
+    fn deposit_persists_across_reboot() {
+        // Arrange
+        let destination = Layer2Tz4Hash(vec![3; 20]);
+        let hash = ticket(0).identify().unwrap();
+
+        let mut state = HostState::default();
+        state.set_ready_for_input(0);
+        state.add_next_inputs(
+            0,
+            vec![(InputType::MessageData, deposit_message(&destination, ticket(25)))].iter()
+        );
+        let mut host = MockHost::from(state);
+
+        // Act
+        transactions_run(&mut host);
+
+        // reboot - only the durable store is kept
+        let host = MockHost::from(host.into_inner());
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &destination).unwrap();
+        memory.tickets_mut().load_ticket(&host, &hash).unwrap();
+
+        // Assert
+        let account = memory.accounts().account_of(&destination).unwrap();
+        assert_eq!(25, account.balance(&hash));
+
+        let registered = memory.tickets().lookup(&hash).unwrap();
+        assert_eq!(ticket(25), registered.ticket(25));
+
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn replay_deposits_over_levels() {
+        // Arrange
+        let destination = Layer2Tz4Hash(vec![3; 20]);
+        let hash = ticket(0).identify().unwrap();
+
+        let level = |level, amount| InboxLevel {
+            level,
+            messages: vec![InboxInput::Message(deposit_message(&destination, ticket(amount)))],
+        };
+        let inbox = Inbox::new(vec![level(1, 25), level(4, 5)]).unwrap();
+
+        // Act
+        let replay = replay(HostState::default(), &inbox, transactions_run::<MockHost>);
+
+        // Assert
+        let host = MockHost::from(replay.state);
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &destination).unwrap();
+
+        let account = memory.accounts().account_of(&destination).unwrap();
+        assert_eq!(30, account.balance(&hash));
+
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn batch_split_across_slot_chunks() {
+        // Arrange
+        const NUM_TRANSFERS: usize = 50;
+
+        let (sk, pk) = key_pair(1);
+        let sender = pk.hash().unwrap();
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+        let hash = ticket(0).identify().unwrap();
+
+        // the first transfer reveals the public key of the sender
+        let transactions: Vec<Vec<u8>> = (0..NUM_TRANSFERS)
+            .map(|counter| {
+                let signer = match counter {
+                    0 => Signer::BlsPublicKey(pk.clone()),
+                    _ => Signer::Layer2Address(sender.clone()),
+                };
+                transfer_transaction(&signer, counter as i64, &receiver, ticket(1))
+            })
+            .collect();
+
+        let signatures: Vec<BlsSignature> = transactions
+            .iter()
+            .map(|transaction| sign(&sk, &pk, transaction.as_slice()))
+            .collect();
+
+        let transactions = transactions.concat();
+        let mut message = vec![0];
+        message.extend_from_slice(&(transactions.len() as u32).to_be_bytes());
+        message.extend(transactions);
+        BlsSignature::aggregate(signatures.as_slice()).unwrap().bin_write(&mut message).unwrap();
+
+        let mut slot_data = (message.len() as u32).to_be_bytes().to_vec();
+        slot_data.extend(message);
+        assert!(slot_data.len() > MAX_INPUT_SLOT_DATA_CHUNK_SIZE);
+
+        let mut state = HostState::default();
+        state.set_ready_for_input(0);
+        state.add_next_inputs(
+            0,
+            vec![
+                (InputType::MessageData, deposit_message(&sender, ticket(100))),
+                (InputType::SlotDataChunk, slot_data.clone())
+            ].iter()
+        );
+        let mut host = MockHost::from(state);
+
+        // Act
+        transactions_run(&mut host);
+
+        // Assert
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &sender).unwrap();
+        memory.accounts_mut().load_account(&host, &receiver).unwrap();
+
+        let sender_account = memory.accounts().account_of(&sender).unwrap();
+        assert_eq!(100 - NUM_TRANSFERS as u64, sender_account.balance(&hash));
+        assert_eq!(NUM_TRANSFERS as i64, sender_account.counter());
+        assert_eq!(Some(&pk), sender_account.public_key());
+
+        let receiver_account = memory.accounts().account_of(&receiver).unwrap();
+        assert_eq!(NUM_TRANSFERS as u64, receiver_account.balance(&hash));
+
+        // the slot takes the id of its first chunk
+        for index in 0..NUM_TRANSFERS {
+            assert_eq!(Some(OperationReceipt::Applied), read_receipt(&host, 0, 1, index, 0));
+        }
+
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn inbox_drained_in_one_call() {
+        // Arrange
+        let addresses: Vec<_> = (1..=3).map(|i| Layer2Tz4Hash(vec![i; 20])).collect();
+        let inputs: Vec<_> = addresses
+            .iter()
+            .map(|address| (InputType::MessageData, deposit_message(address, ticket(5))))
+            .collect();
+
+        let mut state = HostState::default();
+        state.set_ready_for_input(0);
+        state.add_next_inputs(0, inputs.iter());
+        let mut host = MockHost::from(state);
+
+        // Act
+        transactions_run(&mut host);
+
+        // Assert
+        let mut memory = Memory::load_memory(&host);
+        for address in addresses.iter() {
+            memory.accounts_mut().load_account(&host, address).unwrap();
+            assert!(memory.accounts().account_of(address).is_some());
+        }
+        assert!(Runtime::store_has(&host, &PATH_KERNEL_NEXT).is_none());
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn input_budget_carries_on_next_call() {
+        // Arrange
+        let addresses: Vec<_> = (1..=3).map(|i| Layer2Tz4Hash(vec![i; 20])).collect();
+        let inputs: Vec<_> = addresses
+            .iter()
+            .map(|address| (InputType::MessageData, deposit_message(address, ticket(5))))
+            .collect();
+
+        let mut state = HostState::default();
+        state.set_ready_for_input(0);
+        state.add_next_inputs(0, inputs.iter());
+        let mut host = MockHost::from(state);
+
+        let budget = InputBudget {
+            max_messages: 2,
+            ..InputBudget::default()
+        };
+        let is_loaded = |host: &MockHost, address: &Layer2Tz4Hash| {
+            let mut memory = Memory::load_memory(host);
+            memory.accounts_mut().load_account(host, address).unwrap();
+            memory.accounts().account_of(address).is_some()
+        };
+
+        // Act
+        transactions_run_with_budget(&mut host, budget);
+
+        // Assert
+        assert!(is_loaded(&host, &addresses[1]));
+        assert!(!is_loaded(&host, &addresses[2]));
+        assert!(Runtime::store_has(&host, &PATH_KERNEL_NEXT).is_some());
+
+        // Act
+        transactions_run_with_budget(&mut host, budget);
+
+        // Assert
+        assert!(is_loaded(&host, &addresses[2]));
+        assert!(Runtime::store_has(&host, &PATH_KERNEL_NEXT).is_none());
+        assert!(Runtime::store_has(&host, &PENDING_INPUT_PATH).is_none());
+    }
+
+    #[test]
+    fn input_budget_spent_on_last_input_does_not_reboot() {
+        // Arrange
+        let addresses: Vec<_> = (1..=2).map(|i| Layer2Tz4Hash(vec![i; 20])).collect();
+        let inputs: Vec<_> = addresses
+            .iter()
+            .map(|address| (InputType::MessageData, deposit_message(address, ticket(5))))
+            .collect();
+
+        let mut state = HostState::default();
+        state.set_ready_for_input(0);
+        state.add_next_inputs(0, inputs.iter());
+        let mut host = MockHost::from(state);
+
+        let budget = InputBudget {
+            max_messages: 2,
+            ..InputBudget::default()
+        };
+
+        // Act
+        transactions_run_with_budget(&mut host, budget);
+
+        // Assert
+        assert!(Runtime::store_has(&host, &PATH_KERNEL_NEXT).is_none());
+        assert!(Runtime::store_has(&host, &PENDING_INPUT_PATH).is_none());
+        assert_supply_invariant(&host);
+    }
+}
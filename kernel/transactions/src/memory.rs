@@ -1,49 +1,291 @@
 /* Define operations over kernel memory - persisted in RAM between yields */
 
-use host::path::RefPath;
+use host::path::{ OwnedPath, Path, PathError, RefPath };
 use host::rollup_core::RawRollupCore;
-use alloc::collections::BTreeMap;
-use crypto::hash::Layer2Tz4Hash;
-use crate::{ encoding::{ string_ticket::{ StringTicketHash } } };
+use host::runtime::{ Runtime, RuntimeError, ValueType };
+use alloc::collections::{ BTreeMap, BTreeSet };
+use crypto::hash::{ HashTrait, Layer2Tz4Hash };
+use crate::{
+    encoding::{
+        bls::{ BlsPublicKey, BLS_PUBLIC_KEY_SIZE },
+        string_ticket::{ StringTicket, StringTicketHash, TrustlessTicketIdentity },
+    },
+    ticket_registry::TicketRegistry,
+};
 
+use nom::bytes::complete::tag;
+use nom::branch::alt;
+use nom::combinator::{ all_consuming, map };
+use nom::multi::many0;
+use nom::number::complete::{ be_i64, be_u64 };
+use nom::sequence::{ pair, preceded, tuple };
+use serde::{ Deserialize, Serialize };
 use thiserror::Error;
+use tezos_encoding::enc::{ self, BinError, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ dynamic, NomReader, NomResult };
 
 /* need load_memory to use in lib.rs */
 
-const MEMORY_PATH: RefPath = RefPath::assert_from(b"/tx/memory/");
+const MEMORY_PATH: RefPath = RefPath::assert_from(b"/tx/memory");
+
+/* Each account is stored under its own path, as a versioned binary value:
+   /tx/accounts/<tz4>
+
+   Accounts saved before the binary encoding are split over several paths, and are
+   migrated when next saved:
+   /tx/accounts/<tz4>/counter
+   /tx/accounts/<tz4>/public_key
+   /tx/accounts/<tz4>/balances/<ticket-hash>
+*/
+const ACCOUNTS_PATH: &str = "/tx/accounts";
+const COUNTER_STEP: &str = "counter";
+const PUBLIC_KEY_STEP: &str = "public_key";
+const BALANCES_STEP: &str = "balances";
+
+/* The total supply of each ticket is stored under its own path:
+   /tx/supply/<ticket-hash>
+*/
+const SUPPLY_PATH: &str = "/tx/supply";
+
+/* Versions of the stored encodings - memory stored as JSON predates these, and always
+   starts with '{' */
+const MEMORY_V1_TAG: u8 = 1;
+const MEMORY_V2_TAG: u8 = 2;
+const ACCOUNT_V1_TAG: u8 = 1;
+const LEGACY_MEMORY_START: u8 = b'{';
+
+// tags of an optional value
+const OPTION_NONE_TAG: u8 = 0x00;
+const OPTION_SOME_TAG: u8 = 0xff;
 
 #[derive(Default, Debug)]
 /* Memory contents: ticket defintions and the account balance sheet */
 pub struct Memory {
-    // add only account
+    // tickets and accounts are loaded lazily, and stored separately
+    tickets: TicketRegistry,
     accounts: Accounts,
 }
 
 impl Memory {
     // Load memory from the durable store.
+    //
+    // Accounts and tickets are not loaded: see `Accounts::load_account` and
+    // `TicketRegistry::load_ticket`.
     pub fn load_memory<Host: RawRollupCore>(host: &Host) -> Self {
         host::runtime
             ::load_value_sized(host, &MEMORY_PATH)
-            .map(|mem| {
-                serde_json::from_slice(mem.as_slice()).expect("Could not deserialize memory")
-            })
+            .map(|mem| Self::decode(mem.as_slice()).expect("Could not decode memory"))
             .unwrap_or_default()
     }
 
+    // Flush memory to the durable store, to be loaded back on the next call.
+    //
+    // Only the accounts loaded by this call, and newly registered tickets, are written back.
+    pub fn save_memory<Host: RawRollupCore>(&self, host: &mut Host) {
+        let mut mem = Vec::new();
+        self.bin_write(&mut mem).expect("Could not encode memory");
+
+        host::runtime::save_value_sized(host, &MEMORY_PATH, mem.as_slice());
+
+        self.tickets.save_tickets(host).expect("Could not save tickets");
+        self.accounts.save_accounts(host).expect("Could not save accounts");
+    }
+
+    // Add the ticket to the global ticket registry, if it is not already known
+    pub fn add_ticket(&mut self, id_proof: TrustlessTicketIdentity) {
+        self.tickets.register(&id_proof);
+    }
+
+    // deal with the ticket registry
+    pub fn tickets(&self) -> &TicketRegistry {
+        &self.tickets
+    }
+
+    // deal with the ticket registry mutably
+    pub fn tickets_mut(&mut self) -> &mut TicketRegistry {
+        &mut self.tickets
+    }
+
     // deal with accounts
     pub fn accounts(&self) -> &Accounts {
         &self.accounts
     }
+
+    // deal with accounts mutably
+    pub fn accounts_mut(&mut self) -> &mut Accounts {
+        &mut self.accounts
+    }
+
+    // deal with accounts mutably, while looking up tickets
+    pub fn tickets_and_accounts_mut(&mut self) -> (&TicketRegistry, &mut Accounts) {
+        (&self.tickets, &mut self.accounts)
+    }
+
+    // Decode stored memory, migrating it from the JSON encoding if necessary
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes.first() {
+            Some(&LEGACY_MEMORY_START) =>
+                serde_json::from_slice::<LegacyMemory>(bytes).ok().map(Into::into),
+            _ => all_consuming(Memory::nom_read)(bytes).ok().map(|(_, memory)| memory),
+        }
+    }
 }
 
-// Accounts balance sheet
-#[derive(Debug, Default, PartialEq, Eq)]
-pub struct Accounts(BTreeMap<Layer2Tz4Hash, Account>);
+// Accounts balance sheet - holding the accounts loaded from durable storage, and the total
+// supply of the tickets they hold
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Accounts {
+    accounts: BTreeMap<Layer2Tz4Hash, Account>,
+    supply: BTreeMap<StringTicketHash, u64>,
+    undo: Option<UndoLog>,
+}
+
+// The previous value of every account, and ticket supply, changed since the start of
+// `Accounts::atomically` - `None` if it was not loaded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct UndoLog {
+    accounts: BTreeMap<Layer2Tz4Hash, Option<Account>>,
+    supply: BTreeMap<StringTicketHash, Option<u64>>,
+}
 
 impl Accounts {
+    // Load the account at address from durable storage, unless it is already loaded.
+    //
+    // Must be called before accessing the account.
+    pub fn load_account<Host: RawRollupCore>(
+        &mut self,
+        host: &Host,
+        address: &Layer2Tz4Hash
+    ) -> Result<(), AccountStorageError> {
+        if self.accounts.contains_key(address) {
+            return Ok(());
+        }
+
+        if let Some(account) = Account::load(host, address)? {
+            self.accounts.insert(address.clone(), account);
+        }
+        Ok(())
+    }
+
+    // Load the supply of the ticket from durable storage, unless it is already loaded.
+    //
+    // Must be called before minting or burning the ticket.
+    pub fn load_supply<Host: RawRollupCore>(
+        &mut self,
+        host: &Host,
+        hash: &StringTicketHash
+    ) -> Result<(), AccountStorageError> {
+        if self.supply.contains_key(hash) {
+            return Ok(());
+        }
+
+        let path = supply_path(hash)?;
+        let supply = match Runtime::store_has(host, &path) {
+            None => 0,
+            Some(_) => u64::from_le_bytes(read_bytes(host, &path)?),
+        };
+
+        self.supply.insert(hash.clone(), supply);
+        Ok(())
+    }
+
+    // Write every loaded account, and ticket supply, back to durable storage
+    pub fn save_accounts<Host: RawRollupCore>(
+        &self,
+        host: &mut Host
+    ) -> Result<(), AccountStorageError> {
+        self.accounts.iter().try_for_each(|(address, account)| account.save(host, address))?;
+
+        for (hash, supply) in self.supply.iter() {
+            let path = supply_path(hash)?;
+
+            if *supply == 0 {
+                let _ = Runtime::store_delete(host, &path);
+            } else {
+                Runtime::store_write(host, &path, &supply.to_le_bytes(), 0).map_err(
+                    AccountStorageError::Runtime
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // Get a reference to account
+    pub fn account_of(&self, address: &Layer2Tz4Hash) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
     // Get a mutable reference to account
     pub fn account_of_mut(&mut self, address: &Layer2Tz4Hash) -> Option<&mut Account> {
-        self.0.get_mut(address)
+        self.record_account(address);
+        self.accounts.get_mut(address)
+    }
+
+    // Get a mutable reference to account, creating an empty one if it does not exist
+    pub fn account_or_default_mut(&mut self, address: &Layer2Tz4Hash) -> &mut Account {
+        self.record_account(address);
+        self.accounts.entry(address.clone()).or_default()
+    }
+
+    // Apply `f` to the accounts - if it fails, none of its changes are kept.
+    //
+    // Only the accounts and supplies changed by `f` are restored: the previous value of
+    // each is recorded when first changed.
+    pub fn atomically<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Accounts) -> Result<T, E>
+    ) -> Result<T, E> {
+        let outer = self.undo.replace(UndoLog::default());
+
+        let result = f(self);
+
+        let undo = core::mem::replace(&mut self.undo, outer).unwrap_or_default();
+        if result.is_err() {
+            self.restore(undo);
+        } else if let Some(outer) = self.undo.as_mut() {
+            /* keep the values from before the enclosing call, in case that fails */
+            for (address, account) in undo.accounts {
+                outer.accounts.entry(address).or_insert(account);
+            }
+            for (hash, supply) in undo.supply {
+                outer.supply.entry(hash).or_insert(supply);
+            }
+        }
+        result
+    }
+
+    fn record_account(&mut self, address: &Layer2Tz4Hash) {
+        if let Some(undo) = self.undo.as_mut() {
+            if !undo.accounts.contains_key(address) {
+                undo.accounts.insert(address.clone(), self.accounts.get(address).cloned());
+            }
+        }
+    }
+
+    fn record_supply(&mut self, hash: &StringTicketHash) {
+        if let Some(undo) = self.undo.as_mut() {
+            if !undo.supply.contains_key(hash) {
+                undo.supply.insert(hash.clone(), self.supply.get(hash).copied());
+            }
+        }
+    }
+
+    fn restore(&mut self, undo: UndoLog) {
+        for (address, account) in undo.accounts {
+            match account {
+                Some(account) => self.accounts.insert(address, account),
+                None => self.accounts.remove(&address),
+            };
+        }
+
+        for (hash, supply) in undo.supply {
+            match supply {
+                Some(supply) => self.supply.insert(hash, supply),
+                None => self.supply.remove(&hash),
+            };
+        }
     }
 
     // Add a new account at address
@@ -52,10 +294,83 @@ impl Accounts {
         address: Layer2Tz4Hash,
         account: Account
     ) -> Result<(), AccountError> {
-        if self.0.contains_key(&address) {
+        if self.accounts.contains_key(&address) {
             return Err(AccountError::AddressOccupied(address));
         }
-        self.0.insert(address, account);
+        self.record_account(&address);
+        self.accounts.insert(address, account);
+        Ok(())
+    }
+
+    // The total amount of the ticket held by all accounts
+    pub fn supply(&self, hash: &StringTicketHash) -> u64 {
+        self.supply.get(hash).copied().unwrap_or_default()
+    }
+
+    // Record that `amount` of the ticket entered the rollup
+    pub fn mint(&mut self, hash: &StringTicketHash, amount: u64) -> Result<(), AccountError> {
+        let supply = self.supply(hash);
+        let minted = supply
+            .checked_add(amount)
+            .ok_or(AccountError::SupplyOverflow(supply, amount))?;
+
+        self.record_supply(hash);
+        self.supply.insert(hash.clone(), minted);
+        Ok(())
+    }
+
+    // Record that `amount` of the ticket left the rollup
+    pub fn burn(&mut self, hash: &StringTicketHash, amount: u64) -> Result<(), AccountError> {
+        let supply = self.supply(hash);
+        let burned = supply
+            .checked_sub(amount)
+            .ok_or(AccountError::SupplyUnderflow(supply, amount))?;
+
+        self.record_supply(hash);
+        self.supply.insert(hash.clone(), burned);
+        Ok(())
+    }
+
+    // Check that the balances of all accounts add up to the supply of every ticket.
+    //
+    // Every account and supply in durable storage is loaded for the check, on top of those
+    // already loaded: it is only meant for debugging.
+    pub fn check_supply<Host: RawRollupCore>(&self, host: &Host) -> Result<(), SupplyError> {
+        let mut all = self.clone();
+
+        for step in stored_steps(host, ACCOUNTS_PATH)? {
+            let address = Layer2Tz4Hash::from_b58check(&step).map_err(|_| {
+                SupplyError::InvalidStep(step.clone())
+            })?;
+            all.load_account(host, &address)?;
+        }
+
+        for step in stored_steps(host, SUPPLY_PATH)? {
+            let hash = StringTicketHash::from_hex(&step).ok_or(SupplyError::InvalidStep(step))?;
+            all.load_supply(host, &hash)?;
+        }
+
+        let mut balances: BTreeMap<&StringTicketHash, u128> = BTreeMap::new();
+        for account in all.accounts.values() {
+            for (hash, amount) in account.balance.iter() {
+                *balances.entry(hash).or_default() += *amount as u128;
+            }
+        }
+
+        let tickets: BTreeSet<&StringTicketHash> = balances
+            .keys()
+            .copied()
+            .chain(all.supply.keys())
+            .collect();
+
+        for ticket in tickets {
+            let supply = all.supply(ticket);
+            let held = balances.get(ticket).copied().unwrap_or_default();
+
+            if supply as u128 != held {
+                return Err(SupplyError::Mismatch { ticket: ticket.clone(), supply, held });
+            }
+        }
         Ok(())
     }
 }
@@ -67,6 +382,40 @@ pub enum AccountError {
     #[error("Could not add new account due to previous account at address {0}")] AddressOccupied(
         Layer2Tz4Hash,
     ),
+    // Adding the amount to the ticket balance would overflow
+    #[error("Balance overflow: could not add {1} to {0}")] BalanceOverflow(u64, u64),
+    // Not enough of the ticket in the account
+    #[error("Insufficient balance: could not remove {1} from {0}")] InsufficientBalance(
+        u64,
+        u64,
+    ),
+    // A different public key is already linked to the account
+    #[error("Account is already linked to public key {0:?}")] PublicKeyMismatch(BlsPublicKey),
+    // Adding the amount to the ticket supply would overflow
+    #[error("Supply overflow: could not mint {1} on top of {0}")] SupplyOverflow(u64, u64),
+    // More of the ticket burned than was minted
+    #[error("Supply underflow: could not burn {1} out of {0}")] SupplyUnderflow(u64, u64),
+}
+
+// errors occuring when loading or saving accounts in durable storage
+#[derive(Error, Debug)]
+pub enum AccountStorageError {
+    #[error("Invalid account path: {0:?}")] Path(PathError),
+    #[error("Error accessing durable storage: {0:?}")] Runtime(RuntimeError),
+    #[error("Invalid value stored at {0:?}")] InvalidValue(OwnedPath),
+    #[error("Unable to encode account: {0}")] Encoding(#[from] BinError),
+}
+
+// errors occuring when checking the supply of tickets against account balances
+#[derive(Error, Debug)]
+pub enum SupplyError {
+    #[error("{0}")] Storage(#[from] AccountStorageError),
+    #[error("Unexpected path step in durable storage: {0}")] InvalidStep(String),
+    #[error("Supply of ticket {ticket:?} is {supply}, but accounts hold {held}")] Mismatch {
+        ticket: StringTicketHash,
+        supply: u64,
+        held: u128,
+    },
 }
 
 /* Account only content counter */
@@ -74,6 +423,7 @@ pub enum AccountError {
 pub struct Account {
     balance: BTreeMap<StringTicketHash, u64>,
     counter: i64,
+    public_key: Option<BlsPublicKey>,
 }
 
 impl Account {
@@ -87,6 +437,22 @@ impl Account {
         self.counter
     }
 
+    // The public key of the account, once revealed
+    pub fn public_key(&self) -> Option<&BlsPublicKey> {
+        self.public_key.as_ref()
+    }
+
+    // Link the revealed public key to the account
+    pub fn link_public_key(&mut self, pk: BlsPublicKey) -> Result<(), AccountError> {
+        match &self.public_key {
+            Some(linked) if linked != &pk => Err(AccountError::PublicKeyMismatch(linked.clone())),
+            _ => {
+                self.public_key = Some(pk);
+                Ok(())
+            }
+        }
+    }
+
     // Add ticket
 
     pub fn add_ticket(&mut self, hash: StringTicketHash, amount: u64) -> Result<(), AccountError> {
@@ -99,4 +465,713 @@ impl Account {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Remove ticket, dropping the entry once the balance reaches zero
+    pub fn remove_ticket(
+        &mut self,
+        hash: &StringTicketHash,
+        amount: u64
+    ) -> Result<(), AccountError> {
+        let ticket_balance = self.balance(hash);
+
+        match ticket_balance.checked_sub(amount) {
+            None => Err(AccountError::InsufficientBalance(ticket_balance, amount)),
+            Some(0) => {
+                self.balance.remove(hash);
+                Ok(())
+            }
+            Some(remaining) => {
+                self.balance.insert(hash.clone(), remaining);
+                Ok(())
+            }
+        }
+    }
+
+    // The balance of the ticket held by the account
+    pub fn balance(&self, hash: &StringTicketHash) -> u64 {
+        self.balance.get(hash).copied().unwrap_or_default()
+    }
+
+    // Read the account from durable storage, if it was ever saved
+    fn load<Host: RawRollupCore>(
+        host: &Host,
+        address: &Layer2Tz4Hash
+    ) -> Result<Option<Self>, AccountStorageError> {
+        let path = account_path(address, &[])?;
+
+        match Runtime::store_has(host, &path) {
+            None => Ok(None),
+            Some(ValueType::Subtree) => Self::load_legacy(host, address).map(Some),
+            Some(_) => {
+                let bytes = host::runtime
+                    ::load_value_sized(host, &path)
+                    .map_err(AccountStorageError::Runtime)?;
+
+                Self::decode(bytes.as_slice())
+                    .map(Some)
+                    .ok_or(AccountStorageError::InvalidValue(path))
+            }
+        }
+    }
+
+    // Decode a stored account, by the version of its encoding
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes.split_first()? {
+            (&ACCOUNT_V1_TAG, encoded) =>
+                all_consuming(Account::nom_read)(encoded)
+                    .ok()
+                    .map(|(_, account)| account),
+            _ => None,
+        }
+    }
+
+    // Read an account saved before the binary encoding, with each field under its own path
+    fn load_legacy<Host: RawRollupCore>(
+        host: &Host,
+        address: &Layer2Tz4Hash
+    ) -> Result<Self, AccountStorageError> {
+        let counter_path = account_path(address, &[COUNTER_STEP])?;
+        let counter = i64::from_le_bytes(read_bytes(host, &counter_path)?);
+
+        let public_key_path = account_path(address, &[PUBLIC_KEY_STEP])?;
+        let public_key = match Runtime::store_has(host, &public_key_path) {
+            None => None,
+            Some(_) => {
+                let bytes: [u8; BLS_PUBLIC_KEY_SIZE] = read_bytes(host, &public_key_path)?;
+                let pk = BlsPublicKey::try_from(bytes.as_slice()).map_err(|_| {
+                    AccountStorageError::InvalidValue(public_key_path.clone())
+                })?;
+                Some(pk)
+            }
+        };
+
+        let mut balance = BTreeMap::new();
+        let balances_path = account_path(address, &[BALANCES_STEP])?;
+        if Runtime::store_has(host, &balances_path).is_some() {
+            let num_balances = Runtime::store_count_subkeys(host, &balances_path).map_err(
+                AccountStorageError::Runtime
+            )?;
+
+            for index in 0..num_balances {
+                let subkey = Runtime::store_get_subkey(host, &balances_path, index).map_err(
+                    AccountStorageError::Runtime
+                )?;
+                let invalid = || AccountStorageError::InvalidValue(subkey.clone());
+
+                let step = core::str
+                    ::from_utf8(subkey.as_bytes())
+                    .map_err(|_| invalid())?
+                    .trim_start_matches('/');
+                let hash = StringTicketHash::from_hex(step).ok_or_else(invalid)?;
+
+                let balance_path = account_path(address, &[BALANCES_STEP, step])?;
+                let amount = u64::from_le_bytes(read_bytes(host, &balance_path)?);
+
+                balance.insert(hash, amount);
+            }
+        }
+
+        Ok(Self { balance, counter, public_key })
+    }
+
+    // Write the account to durable storage, replacing any previous version
+    fn save<Host: RawRollupCore>(
+        &self,
+        host: &mut Host,
+        address: &Layer2Tz4Hash
+    ) -> Result<(), AccountStorageError> {
+        let mut bytes = vec![ACCOUNT_V1_TAG];
+        self.bin_write(&mut bytes)?;
+
+        // also removes any fields of the legacy layout
+        host::runtime::save_value_sized(host, &account_path(address, &[])?, bytes.as_slice());
+        Ok(())
+    }
+}
+
+// Path of the account, followed by `steps`
+fn account_path(address: &Layer2Tz4Hash, steps: &[&str]) -> Result<OwnedPath, AccountStorageError> {
+    let mut path = format!("{}/{}", ACCOUNTS_PATH, address.to_b58check());
+    for step in steps {
+        path.push('/');
+        path.push_str(step);
+    }
+
+    OwnedPath::try_from(path.into_bytes()).map_err(AccountStorageError::Path)
+}
+
+// Path of the supply of the ticket
+fn supply_path(hash: &StringTicketHash) -> Result<OwnedPath, AccountStorageError> {
+    let path = format!("{}/{}", SUPPLY_PATH, hash.to_hex());
+
+    OwnedPath::try_from(path.into_bytes()).map_err(AccountStorageError::Path)
+}
+
+// The first step of every path stored under `prefix`
+fn stored_steps<Host: RawRollupCore>(
+    host: &Host,
+    prefix: &str
+) -> Result<BTreeSet<String>, AccountStorageError> {
+    let path = OwnedPath::try_from(prefix.as_bytes().to_vec()).map_err(AccountStorageError::Path)?;
+    if Runtime::store_has(host, &path).is_none() {
+        return Ok(BTreeSet::new());
+    }
+
+    let num_subkeys = Runtime::store_count_subkeys(host, &path).map_err(
+        AccountStorageError::Runtime
+    )?;
+
+    (0..num_subkeys)
+        .map(|index| {
+            let subkey = Runtime::store_get_subkey(host, &path, index).map_err(
+                AccountStorageError::Runtime
+            )?;
+
+            core::str
+                ::from_utf8(subkey.as_bytes())
+                .ok()
+                .and_then(|subkey| subkey.trim_start_matches('/').split('/').next())
+                .map(str::to_string)
+                .ok_or_else(|| AccountStorageError::InvalidValue(subkey.clone()))
+        })
+        .collect()
+}
+
+// Read a value of exactly `N` bytes
+fn read_bytes<Host: RawRollupCore, const N: usize>(
+    host: &Host,
+    path: &OwnedPath
+) -> Result<[u8; N], AccountStorageError> {
+    Runtime::store_read(host, path, 0, N)
+        .map_err(AccountStorageError::Runtime)?
+        .try_into()
+        .map_err(|_| AccountStorageError::InvalidValue(path.clone()))
+}
+
+// Memory stored as JSON, before the binary encoding
+#[derive(Serialize, Deserialize)]
+struct LegacyMemory {
+    #[serde(with = "map_as_entries")]
+    tickets: BTreeMap<StringTicketHash, StringTicket>,
+    // accounts were stored alongside tickets, before being stored separately
+    #[serde(default, with = "map_as_entries")]
+    accounts: BTreeMap<Layer2Tz4Hash, LegacyAccount>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyAccount {
+    #[serde(with = "map_as_entries")]
+    balance: BTreeMap<StringTicketHash, u64>,
+    counter: i64,
+    #[serde(default)]
+    public_key: Option<BlsPublicKey>,
+}
+
+// Migrated tickets and accounts are loaded, so that they are saved under their own path
+impl From<LegacyMemory> for Memory {
+    fn from(legacy: LegacyMemory) -> Self {
+        let accounts: BTreeMap<_, _> = legacy.accounts
+            .into_iter()
+            .map(|(address, LegacyAccount { balance, counter, public_key })| {
+                (address, Account { balance, counter, public_key })
+            })
+            .collect();
+
+        // the supply was not recorded: every ticket was held by a migrated account
+        let mut supply: BTreeMap<StringTicketHash, u64> = BTreeMap::new();
+        for (hash, amount) in accounts.values().flat_map(|account| account.balance.iter()) {
+            let total = supply.entry(hash.clone()).or_default();
+            *total = total.saturating_add(*amount);
+        }
+
+        Self {
+            tickets: legacy.tickets.into_iter().collect(),
+            accounts: Accounts { accounts, supply, undo: None },
+        }
+    }
+}
+
+// Encoding
+has_encoding!(Memory, MEMORY_ENCODING, { Encoding::Custom });
+has_encoding!(Accounts, ACCOUNTS_ENCODING, { Encoding::Custom });
+has_encoding!(Account, ACCOUNT_ENCODING, { Encoding::Custom });
+
+// Tickets and accounts are stored separately: only the version is part of the memory encoding
+impl NomReader for Memory {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        alt((
+            map(tag([MEMORY_V2_TAG]), |_| Memory::default()),
+            // the ticket table was stored in memory, before the ticket registry
+            map(
+                preceded(
+                    tag([MEMORY_V1_TAG]),
+                    dynamic(many0(pair(StringTicketHash::nom_read, StringTicket::nom_read)))
+                ),
+                |tickets| Memory {
+                    tickets: tickets.into_iter().collect(),
+                    accounts: Accounts::default(),
+                }
+            ),
+        ))(input)
+    }
+}
+
+impl BinWriter for Memory {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_byte(&MEMORY_V2_TAG, output);
+        Ok(())
+    }
+}
+
+impl NomReader for Accounts {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map(
+            pair(
+                dynamic(many0(pair(Layer2Tz4Hash::nom_read, Account::nom_read))),
+                dynamic(many0(pair(StringTicketHash::nom_read, be_u64)))
+            ),
+            |(accounts, supply)| Accounts {
+                accounts: accounts.into_iter().collect(),
+                supply: supply.into_iter().collect(),
+                undo: None,
+            }
+        )(input)
+    }
+}
+
+impl BinWriter for Accounts {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::dynamic(|accounts: &BTreeMap<Layer2Tz4Hash, Account>, output| {
+            accounts.iter().try_for_each(|(address, account)| {
+                address.bin_write(output)?;
+                account.bin_write(output)
+            })
+        })(&self.accounts, output)?;
+
+        enc::dynamic(|supply: &BTreeMap<StringTicketHash, u64>, output| {
+            supply.iter().try_for_each(|(hash, amount)| {
+                hash.bin_write(output)?;
+                enc::put_bytes(&amount.to_be_bytes(), output);
+                Ok(())
+            })
+        })(&self.supply, output)
+    }
+}
+
+impl NomReader for Account {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map(
+            tuple((
+                be_i64,
+                alt((
+                    map(tag([OPTION_NONE_TAG]), |_| None),
+                    map(preceded(tag([OPTION_SOME_TAG]), BlsPublicKey::nom_read), Some),
+                )),
+                dynamic(many0(pair(StringTicketHash::nom_read, be_u64))),
+            )),
+            |(counter, public_key, balance)| Account {
+                balance: balance.into_iter().collect(),
+                counter,
+                public_key,
+            }
+        )(input)
+    }
+}
+
+impl BinWriter for Account {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        enc::put_bytes(&self.counter.to_be_bytes(), output);
+
+        match &self.public_key {
+            None => enc::put_byte(&OPTION_NONE_TAG, output),
+            Some(pk) => {
+                enc::put_byte(&OPTION_SOME_TAG, output);
+                pk.bin_write(output)?;
+            }
+        }
+
+        enc::dynamic(|balance: &BTreeMap<StringTicketHash, u64>, output| {
+            balance.iter().try_for_each(|(hash, amount)| {
+                hash.bin_write(output)?;
+                enc::put_bytes(&amount.to_be_bytes(), output);
+                Ok(())
+            })
+        })(&self.balance, output)
+    }
+}
+
+// JSON only allows string keys: maps are serialized as a list of entries instead
+mod map_as_entries {
+    use alloc::collections::BTreeMap;
+    use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+        where K: Serialize, V: Serialize, S: Serializer
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+        where K: Deserialize<'de> + Ord, V: Deserialize<'de>, D: Deserializer<'de>
+    {
+        let entries = Vec::<(K, V)>::deserialize(deserializer)?;
+
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use mock_runtime::host::MockHost;
+
+    use proptest::collection::{ btree_map, vec };
+    use proptest::prelude::*;
+
+    use crate::encoding::{ contract::Contract, public_key_hash::PublicKeyHash };
+    use crate::encoding::bls::tests::key_pair;
+    use crate::encoding::string_ticket::STRING_TICKET_HASH_SIZE;
+
+    fn ticket(contents: &str, amount: u64) -> StringTicket {
+        let creator = Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![1; 20])));
+        StringTicket::new(creator, contents.to_string(), amount)
+    }
+
+    fn ticket_hash(contents: &str) -> StringTicketHash {
+        ticket(contents, 1).identify().unwrap()
+    }
+
+    fn arb_ticket_hash() -> impl Strategy<Value = StringTicketHash> {
+        vec(any::<u8>(), STRING_TICKET_HASH_SIZE).prop_map(|bytes| {
+            StringTicketHash::nom_read(bytes.as_slice()).unwrap().1
+        })
+    }
+
+    fn arb_account() -> impl Strategy<Value = Account> {
+        (
+            btree_map(arb_ticket_hash(), any::<u64>(), 0..8),
+            any::<i64>(),
+            proptest::option::of(any::<u8>().prop_map(|seed| key_pair(seed).1)),
+        ).prop_map(|(balance, counter, public_key)| Account { balance, counter, public_key })
+    }
+
+    fn arb_accounts() -> impl Strategy<Value = Accounts> {
+        (
+            btree_map(vec(any::<u8>(), 20).prop_map(Layer2Tz4Hash), arb_account(), 0..4),
+            btree_map(arb_ticket_hash(), any::<u64>(), 0..4),
+        ).prop_map(|(accounts, supply)| Accounts { accounts, supply, undo: None })
+    }
+
+    fn arb_tickets() -> impl Strategy<Value = BTreeMap<StringTicketHash, StringTicket>> {
+        vec(("[a-z]{0,16}", any::<u64>()), 0..8).prop_map(|tickets| {
+            tickets
+                .into_iter()
+                .map(|(contents, amount)| {
+                    let ticket = ticket(&contents, amount);
+                    (ticket.identify().unwrap(), ticket)
+                })
+                .collect()
+        })
+    }
+
+    // Assert that the balances of every stored account add up to the stored supply of every
+    // ticket
+    pub(crate) fn assert_supply_invariant(host: &MockHost) {
+        let result = Memory::load_memory(host).accounts().check_supply(host);
+
+        assert!(result.is_ok(), "Supply invariant broken: {:?}", result);
+    }
+
+    fn roundtrip<T: NomReader + BinWriter>(value: &T) -> T {
+        let mut bytes = Vec::new();
+        value.bin_write(&mut bytes).unwrap();
+
+        let (remaining, decoded) = T::nom_read(bytes.as_slice()).unwrap();
+        assert!(remaining.is_empty());
+        decoded
+    }
+
+    proptest! {
+        #[test]
+        fn ticket_hash_encode_decode(hash in arb_ticket_hash()) {
+            prop_assert_eq!(&hash, &roundtrip(&hash));
+        }
+
+        #[test]
+        fn account_encode_decode(account in arb_account()) {
+            prop_assert_eq!(&account, &roundtrip(&account));
+        }
+
+        #[test]
+        fn accounts_encode_decode(accounts in arb_accounts()) {
+            prop_assert_eq!(&accounts, &roundtrip(&accounts));
+        }
+
+        #[test]
+        fn memory_v1_migrated(tickets in arb_tickets()) {
+            let mut bytes = vec![MEMORY_V1_TAG];
+            enc::dynamic(|tickets: &BTreeMap<StringTicketHash, StringTicket>, output| {
+                tickets.iter().try_for_each(|(hash, ticket)| {
+                    hash.bin_write(output)?;
+                    ticket.bin_write(output)
+                })
+            })(&tickets, &mut bytes).unwrap();
+
+            let memory = Memory::decode(bytes.as_slice()).unwrap();
+
+            prop_assert_eq!(tickets.into_iter().collect::<TicketRegistry>(), memory.tickets);
+        }
+    }
+
+    #[test]
+    fn memory_encode_decode() {
+        let mut bytes = Vec::new();
+        Memory::default().bin_write(&mut bytes).unwrap();
+
+        assert!(Memory::decode(bytes.as_slice()).is_some());
+    }
+
+    #[test]
+    fn memory_migrated_from_json() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let red = ticket("red", 10);
+        let red_hash = red.identify().unwrap();
+
+        let legacy = LegacyMemory {
+            tickets: [(red_hash.clone(), red)].into_iter().collect(),
+            accounts: [
+                (
+                    address.clone(),
+                    LegacyAccount {
+                        balance: [(red_hash.clone(), 10)].into_iter().collect(),
+                        counter: 2,
+                        public_key: None,
+                    },
+                ),
+            ]
+                .into_iter()
+                .collect(),
+        };
+
+        let mut host = MockHost::default();
+        let json = serde_json::to_vec(&legacy).unwrap();
+        host::runtime::save_value_sized(&mut host, &MEMORY_PATH, json.as_slice());
+
+        // Act
+        Memory::load_memory(&host).save_memory(&mut host);
+
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &address).unwrap();
+
+        memory.tickets_mut().load_ticket(&host, &red_hash).unwrap();
+
+        // Assert
+        let definition = memory.tickets().lookup(&red_hash).unwrap();
+        assert_eq!(ticket("red", 10), definition.ticket(10));
+
+        let account = memory.accounts().account_of(&address).unwrap();
+        assert_eq!(10, account.balance(&red_hash));
+        assert_eq!(2, account.counter());
+
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn account_migrated_from_legacy_layout() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let (_sk, pk) = key_pair(5);
+        let red = ticket_hash("red");
+
+        let mut host = MockHost::default();
+        let mut write = |steps: &[&str], value: &[u8]| {
+            Runtime::store_write(&mut host, &account_path(&address, steps).unwrap(), value, 0)
+                .unwrap();
+        };
+        write(&[COUNTER_STEP], &3_i64.to_le_bytes());
+        write(&[PUBLIC_KEY_STEP], pk.as_bytes());
+        write(&[BALANCES_STEP, &red.to_hex()], &7_u64.to_le_bytes());
+
+        // Act
+        let legacy = Account::load(&host, &address).unwrap().unwrap();
+        legacy.save(&mut host, &address).unwrap();
+
+        let migrated = Account::load(&host, &address).unwrap().unwrap();
+
+        // Assert
+        assert_eq!(legacy, migrated);
+        assert_eq!(3, migrated.counter());
+        assert_eq!(Some(&pk), migrated.public_key());
+        assert_eq!(7, migrated.balance(&red));
+
+        let counter_path = account_path(&address, &[COUNTER_STEP]).unwrap();
+        assert!(Runtime::store_has(&host, &counter_path).is_none());
+    }
+
+    #[test]
+    fn account_save_load_roundtrip() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let (_sk, pk) = key_pair(5);
+
+        let mut account = Account::default();
+        account.add_ticket(ticket_hash("red"), 10).unwrap();
+        account.add_ticket(ticket_hash("blue"), 3).unwrap();
+        account.link_public_key(pk).unwrap();
+        account.increment_counter();
+
+        let mut memory = Memory::default();
+        memory.accounts_mut().add_account(address.clone(), account.clone()).unwrap();
+
+        let mut host = MockHost::default();
+
+        // Act
+        memory.save_memory(&mut host);
+
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &address).unwrap();
+
+        // Assert
+        assert_eq!(Some(&account), memory.accounts().account_of(&address));
+    }
+
+    #[test]
+    fn account_save_drops_emptied_balance() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let mut host = MockHost::default();
+
+        let mut account = Account::default();
+        account.add_ticket(ticket_hash("red"), 10).unwrap();
+        account.add_ticket(ticket_hash("blue"), 3).unwrap();
+        account.save(&mut host, &address).unwrap();
+
+        // Act
+        account.remove_ticket(&ticket_hash("blue"), 3).unwrap();
+        account.save(&mut host, &address).unwrap();
+
+        // Assert
+        let loaded = Account::load(&host, &address).unwrap().unwrap();
+        assert_eq!(account, loaded);
+        assert_eq!(0, loaded.balance(&ticket_hash("blue")));
+    }
+
+    #[test]
+    fn load_account_only_loads_requested() {
+        // Arrange
+        let first = Layer2Tz4Hash(vec![1; 20]);
+        let second = Layer2Tz4Hash(vec![2; 20]);
+        let unknown = Layer2Tz4Hash(vec![3; 20]);
+
+        let mut memory = Memory::default();
+        memory.accounts_mut().add_account(first.clone(), Account::default()).unwrap();
+        memory.accounts_mut().add_account(second.clone(), Account::default()).unwrap();
+
+        let mut host = MockHost::default();
+        memory.save_memory(&mut host);
+
+        // Act
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_account(&host, &first).unwrap();
+        memory.accounts_mut().load_account(&host, &unknown).unwrap();
+
+        // Assert
+        assert!(memory.accounts().account_of(&first).is_some());
+        assert!(memory.accounts().account_of(&second).is_none());
+        assert!(memory.accounts().account_of(&unknown).is_none());
+    }
+
+    #[test]
+    fn supply_saved_with_accounts() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let red = ticket_hash("red");
+
+        let mut memory = Memory::default();
+        let accounts = memory.accounts_mut();
+        accounts.account_or_default_mut(&address).add_ticket(red.clone(), 10).unwrap();
+        accounts.mint(&red, 10).unwrap();
+
+        let mut host = MockHost::default();
+
+        // Act
+        memory.save_memory(&mut host);
+
+        let mut memory = Memory::load_memory(&host);
+        memory.accounts_mut().load_supply(&host, &red).unwrap();
+
+        // Assert
+        assert_eq!(10, memory.accounts().supply(&red));
+        assert_supply_invariant(&host);
+    }
+
+    #[test]
+    fn supply_burn_more_than_minted() {
+        // Arrange
+        let red = ticket_hash("red");
+        let mut accounts = Accounts::default();
+        accounts.mint(&red, 3).unwrap();
+
+        // Act
+        let result = accounts.burn(&red, 4);
+
+        // Assert
+        assert!(matches!(result, Err(AccountError::SupplyUnderflow(3, 4))));
+        assert_eq!(3, accounts.supply(&red));
+    }
+
+    #[test]
+    fn atomically_restores_changed_accounts() {
+        // Arrange
+        let existing = Layer2Tz4Hash(vec![5; 20]);
+        let created = Layer2Tz4Hash(vec![6; 20]);
+        let red = ticket_hash("red");
+        let blue = ticket_hash("blue");
+
+        let mut accounts = Accounts::default();
+        accounts.account_or_default_mut(&existing).add_ticket(red.clone(), 10).unwrap();
+        accounts.mint(&red, 10).unwrap();
+        let before = accounts.clone();
+
+        // Act
+        let result = accounts.atomically(|accounts| {
+            accounts.account_of_mut(&existing).unwrap().remove_ticket(&red, 4)?;
+            accounts.account_or_default_mut(&created).add_ticket(blue.clone(), 3)?;
+            accounts.mint(&blue, 3)?;
+
+            /* a nested call that succeeds is still undone by the enclosing call */
+            accounts.atomically(|accounts| accounts.burn(&red, 4))?;
+
+            accounts.burn(&blue, 4)
+        });
+
+        // Assert
+        assert!(matches!(result, Err(AccountError::SupplyUnderflow(3, 4))));
+        assert_eq!(before, accounts);
+    }
+
+    #[test]
+    fn check_supply_detects_mismatch() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![5; 20]);
+        let red = ticket_hash("red");
+
+        let mut memory = Memory::default();
+        let accounts = memory.accounts_mut();
+        accounts.account_or_default_mut(&address).add_ticket(red.clone(), 10).unwrap();
+        accounts.mint(&red, 7).unwrap();
+
+        let mut host = MockHost::default();
+        memory.save_memory(&mut host);
+
+        // Act
+        let result = Memory::load_memory(&host).accounts().check_supply(&host);
+
+        // Assert
+        assert!(
+            matches!(result, Err(SupplyError::Mismatch { supply: 7, held: 10, .. }))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+/* Outbox messages, written by the kernel to be executed on layer 1 */
+
+use host::rollup_core::RawRollupCore;
+use host::runtime::{ Runtime, RuntimeError };
+use tezos_encoding::enc::{ self, BinError, BinResult, BinWriter };
+use thiserror::Error;
+
+use crate::encoding::{
+    contract::Contract,
+    entrypoint::Entrypoint,
+    string_ticket::StringTicketRepr,
+};
+
+const ATOMIC_TRANSACTION_BATCH_TAG: u8 = 0;
+
+/// A transaction to a layer 1 contract, carrying a ticket as parameter.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OutboxMessageTransaction {
+    /// The ticket sent to the destination.
+    pub parameters: StringTicketRepr,
+    /// The layer 1 contract receiving the ticket.
+    pub destination: Contract,
+    /// The entrypoint of the destination called with the ticket.
+    pub entrypoint: Entrypoint,
+}
+
+/// Message written to the outbox, executable on layer 1 once cemented.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OutboxMessage {
+    /// A batch of transactions, which are all applied - or none are.
+    AtomicTransactionBatch(Vec<OutboxMessageTransaction>),
+}
+
+impl BinWriter for OutboxMessageTransaction {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        self.parameters.bin_write(output)?;
+        self.destination.bin_write(output)?;
+        self.entrypoint.bin_write(output)
+    }
+}
+
+impl BinWriter for OutboxMessage {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        match self {
+            Self::AtomicTransactionBatch(transactions) => {
+                enc::put_byte(&ATOMIC_TRANSACTION_BATCH_TAG, output);
+                enc::dynamic(|transactions: &Vec<OutboxMessageTransaction>, output: &mut Vec<u8>| {
+                    transactions.iter().try_for_each(|transaction| transaction.bin_write(output))
+                })(transactions, output)
+            }
+        }
+    }
+}
+
+/// Errors that may occur when writing a message to the outbox.
+#[derive(Error, Debug)]
+pub enum OutboxError {
+    /// Issue occurred serializing the outbox message.
+    #[error("Unable to serialize outbox message: {0}")]
+    Serialization(#[from] BinError),
+
+    /// Issue occurred writing the outbox message.
+    #[error("Unable to write outbox message: {0:?}")]
+    Write(RuntimeError),
+}
+
+/// Serialize `message`, and write it to the outbox.
+pub fn write_outbox_message<Host: RawRollupCore>(
+    host: &mut Host,
+    message: &OutboxMessage
+) -> Result<(), OutboxError> {
+    let mut encoded = Vec::new();
+    message.bin_write(&mut encoded)?;
+
+    Runtime::write_output(host, encoded.as_slice()).map_err(OutboxError::Write)
+}
@@ -0,0 +1,213 @@
+/* Reassemble slot data chunks into complete external messages.
+
+   The data of a slot is a dynamically-sized external message: a 4-byte big-endian
+   length, followed by the message itself. As it may not fit in a single input, its
+   chunks are accumulated in durable storage, keyed by the (level, id) of the slot:
+
+   /tx/slots/<level>/<id>/size    - number of bytes received so far
+   /tx/slots/<level>/<id>/payload - bytes received so far
+*/
+
+use host::path::{ OwnedPath, PathError };
+use host::rollup_core::{ RawRollupCore, MAX_FILE_CHUNK_SIZE };
+use host::runtime::{ Runtime, RuntimeError };
+use thiserror::Error;
+
+const SIZE_PREFIX_LENGTH: usize = 4;
+
+/// Errors that may occur when reassembling slot data.
+#[derive(Error, Debug)]
+pub enum SlotError {
+    /// The slot data has an invalid path.
+    #[error("Invalid slot path: {0:?}")]
+    Path(PathError),
+
+    /// Issue occurred accessing the slot data in durable storage.
+    #[error("Error accessing durable storage: {0:?}")]
+    Runtime(RuntimeError),
+
+    /// More bytes were received than the size prefix announced.
+    #[error("Slot data announced {expected} bytes, but {received} were received")]
+    TooManyBytes {
+        /// Size announced by the prefix of the slot data.
+        expected: usize,
+        /// Bytes received so far.
+        received: usize,
+    },
+}
+
+/// Append `chunk` to the data of slot (`level`, `id`).
+///
+/// Returns the external message once every chunk of the slot has been received, after
+/// which the slot data is removed from durable storage.
+pub fn reassemble_slot_chunk<Host: RawRollupCore>(
+    host: &mut Host,
+    level: i32,
+    id: i32,
+    chunk: &[u8]
+) -> Result<Option<Vec<u8>>, SlotError> {
+    let data_path = slot_path(level, id, None)?;
+    let size_path = slot_path(level, id, Some("size"))?;
+    let payload_path = slot_path(level, id, Some("payload"))?;
+
+    let received = match Runtime::store_has(host, &size_path) {
+        None => 0,
+        Some(_) => {
+            let size = Runtime::store_read(host, &size_path, 0, 4).map_err(SlotError::Runtime)?;
+            u32::from_le_bytes(size.try_into().unwrap_or_default()) as usize
+        }
+    };
+
+    Runtime::store_write(host, &payload_path, chunk, received).map_err(SlotError::Runtime)?;
+    let received = received + chunk.len();
+
+    if received < SIZE_PREFIX_LENGTH {
+        write_size(host, &size_path, received)?;
+        return Ok(None);
+    }
+
+    let prefix = Runtime::store_read(host, &payload_path, 0, SIZE_PREFIX_LENGTH).map_err(
+        SlotError::Runtime
+    )?;
+    let expected = SIZE_PREFIX_LENGTH +
+        (u32::from_be_bytes(prefix.try_into().unwrap_or_default()) as usize);
+
+    if received < expected {
+        write_size(host, &size_path, received)?;
+        return Ok(None);
+    }
+
+    // the slot is complete - or invalid: either way it is no longer needed
+    let payload = read_payload(host, &payload_path, received);
+    Runtime::store_delete(host, &data_path).map_err(SlotError::Runtime)?;
+
+    if received > expected {
+        return Err(SlotError::TooManyBytes { expected, received });
+    }
+
+    let mut message = payload?;
+    message.drain(..SIZE_PREFIX_LENGTH);
+    Ok(Some(message))
+}
+
+fn slot_path(level: i32, id: i32, field: Option<&str>) -> Result<OwnedPath, SlotError> {
+    let path = match field {
+        Some(field) => format!("/tx/slots/{}/{}/{}", level, id, field),
+        None => format!("/tx/slots/{}/{}", level, id),
+    };
+
+    OwnedPath::try_from(path.into_bytes()).map_err(SlotError::Path)
+}
+
+fn write_size<Host: RawRollupCore>(
+    host: &mut Host,
+    path: &OwnedPath,
+    size: usize
+) -> Result<(), SlotError> {
+    Runtime::store_write(host, path, &(size as u32).to_le_bytes(), 0).map_err(SlotError::Runtime)
+}
+
+fn read_payload<Host: RawRollupCore>(
+    host: &Host,
+    path: &OwnedPath,
+    size: usize
+) -> Result<Vec<u8>, SlotError> {
+    let mut payload = Vec::with_capacity(size);
+
+    while payload.len() < size {
+        let bytes = Runtime::store_read(host, path, payload.len(), MAX_FILE_CHUNK_SIZE).map_err(
+            SlotError::Runtime
+        )?;
+
+        if bytes.is_empty() {
+            break;
+        }
+        payload.extend_from_slice(bytes.as_slice());
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_runtime::host::MockHost;
+
+    fn slot_data(message: &[u8]) -> Vec<u8> {
+        let mut data = (message.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn reassemble_single_chunk() {
+        // Arrange
+        let mut host = MockHost::default();
+        let message = b"a small message".to_vec();
+
+        // Act
+        let result = reassemble_slot_chunk(&mut host, 1, 0, slot_data(&message).as_slice());
+
+        // Assert
+        assert_eq!(Some(message), result.unwrap());
+
+        let state = host.into_inner();
+        assert!(!state.store.list_paths().any(|p| p.starts_with("/durable/tx/slots")));
+    }
+
+    #[test]
+    fn reassemble_across_chunks() {
+        // Arrange
+        let mut host = MockHost::default();
+        let message: Vec<u8> = (0..10_000).map(|i| (i % 256) as u8).collect();
+        let data = slot_data(&message);
+
+        // Act
+        let mut results: Vec<_> = data
+            .chunks(MAX_FILE_CHUNK_SIZE)
+            .map(|chunk| reassemble_slot_chunk(&mut host, 3, 2, chunk).unwrap())
+            .collect();
+
+        // Assert
+        let last = results.pop().unwrap();
+        assert!(results.iter().all(Option::is_none));
+        assert_eq!(Some(message), last);
+    }
+
+    #[test]
+    fn reassemble_keyed_by_level_and_id() {
+        // Arrange
+        let mut host = MockHost::default();
+        let first = slot_data(b"first message");
+        let second = slot_data(b"second message");
+
+        // Act
+        let first_start = reassemble_slot_chunk(&mut host, 1, 0, &first[..6]).unwrap();
+        let second_start = reassemble_slot_chunk(&mut host, 1, 1, &second[..2]).unwrap();
+        let second_end = reassemble_slot_chunk(&mut host, 1, 1, &second[2..]).unwrap();
+        let first_end = reassemble_slot_chunk(&mut host, 1, 0, &first[6..]).unwrap();
+
+        // Assert
+        assert_eq!(None, first_start);
+        assert_eq!(None, second_start);
+        assert_eq!(Some(b"second message".to_vec()), second_end);
+        assert_eq!(Some(b"first message".to_vec()), first_end);
+    }
+
+    #[test]
+    fn reassemble_too_many_bytes() {
+        // Arrange
+        let mut host = MockHost::default();
+        let mut data = slot_data(b"message");
+        data.extend_from_slice(b"trailing");
+
+        // Act
+        let result = reassemble_slot_chunk(&mut host, 1, 0, data.as_slice());
+
+        // Assert
+        assert!(matches!(result, Err(SlotError::TooManyBytes { expected: 11, received: 19 })));
+
+        let state = host.into_inner();
+        assert!(!state.store.list_paths().any(|p| p.starts_with("/durable/tx/slots")));
+    }
+}
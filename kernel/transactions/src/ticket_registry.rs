@@ -0,0 +1,250 @@
+/* Registry of every ticket deposited into the rollup, keyed by its hash.
+
+   Accounts only hold the hash of a ticket: the registry records its creator and contents,
+   so that the ticket can be rebuilt when it leaves the rollup. A ticket is registered
+   on its first deposit, and is stored under its own path:
+
+   /tx/tickets/<ticket-hash>
+*/
+
+use alloc::collections::BTreeMap;
+use host::path::{ OwnedPath, PathError };
+use host::rollup_core::RawRollupCore;
+use host::runtime::{ Runtime, RuntimeError };
+use nom::combinator::{ all_consuming, map };
+use nom::sequence::pair;
+use thiserror::Error;
+use tezos_encoding::enc::{ self, BinError, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ self as nom_read, NomReader, NomResult };
+
+use crate::encoding::contract::Contract;
+use crate::encoding::string_ticket::{ StringTicket, StringTicketHash, TrustlessTicketIdentity };
+
+const TICKETS_PATH: &str = "/tx/tickets";
+
+/// Errors that may occur when loading or saving registered tickets.
+#[derive(Error, Debug)]
+pub enum TicketRegistryError {
+    /// The ticket has an invalid path.
+    #[error("Invalid ticket path: {0:?}")]
+    Path(PathError),
+
+    /// Issue occurred accessing the ticket in durable storage.
+    #[error("Error accessing durable storage: {0:?}")]
+    Runtime(RuntimeError),
+
+    /// The stored ticket could not be decoded.
+    #[error("Invalid ticket stored at {0:?}")]
+    InvalidValue(OwnedPath),
+
+    /// The ticket could not be encoded.
+    #[error("Unable to encode ticket: {0}")]
+    Encoding(#[from] BinError),
+}
+
+/// The creator and contents of a registered ticket - everything but its amount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TicketDefinition {
+    creator: Contract,
+    contents: String,
+}
+
+impl TicketDefinition {
+    /// The layer 1 contract that created the ticket.
+    pub fn creator(&self) -> &Contract {
+        &self.creator
+    }
+
+    /// The contents of the ticket.
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Rebuild the ticket, holding `amount`.
+    pub fn ticket(&self, amount: u64) -> StringTicket {
+        StringTicket::new(self.creator.clone(), self.contents.clone(), amount)
+    }
+}
+
+impl From<&StringTicket> for TicketDefinition {
+    fn from(ticket: &StringTicket) -> Self {
+        Self {
+            creator: ticket.creator().clone(),
+            contents: ticket.contents().to_string(),
+        }
+    }
+}
+
+/// The registered tickets, loaded from durable storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TicketRegistry(BTreeMap<StringTicketHash, TicketDefinition>);
+
+impl TicketRegistry {
+    /// Load the ticket identified by `hash` from durable storage, unless it is already
+    /// loaded.
+    ///
+    /// Must be called before looking up the ticket.
+    pub fn load_ticket<Host: RawRollupCore>(
+        &mut self,
+        host: &Host,
+        hash: &StringTicketHash
+    ) -> Result<(), TicketRegistryError> {
+        if self.0.contains_key(hash) {
+            return Ok(());
+        }
+
+        let path = ticket_path(hash)?;
+        if Runtime::store_has(host, &path).is_none() {
+            return Ok(());
+        }
+
+        let bytes = host::runtime
+            ::load_value_sized(host, &path)
+            .map_err(TicketRegistryError::Runtime)?;
+        let (_, definition) = all_consuming(TicketDefinition::nom_read)(bytes.as_slice()).map_err(
+            |_| TicketRegistryError::InvalidValue(path)
+        )?;
+
+        self.0.insert(hash.clone(), definition);
+        Ok(())
+    }
+
+    /// Register the ticket, if it is not already known.
+    pub fn register(&mut self, id_proof: &TrustlessTicketIdentity) {
+        self.0
+            .entry(id_proof.identify().clone())
+            .or_insert_with(|| id_proof.ticket().into());
+    }
+
+    /// The ticket identified by `hash`, if it is registered and loaded.
+    pub fn lookup(&self, hash: &StringTicketHash) -> Option<&TicketDefinition> {
+        self.0.get(hash)
+    }
+
+    /// Write every newly registered ticket to durable storage.
+    ///
+    /// Registered tickets never change, so tickets already stored are not rewritten.
+    pub fn save_tickets<Host: RawRollupCore>(
+        &self,
+        host: &mut Host
+    ) -> Result<(), TicketRegistryError> {
+        for (hash, definition) in self.0.iter() {
+            let path = ticket_path(hash)?;
+            if Runtime::store_has(host, &path).is_some() {
+                continue;
+            }
+
+            let mut bytes = Vec::new();
+            definition.bin_write(&mut bytes)?;
+            host::runtime::save_value_sized(host, &path, bytes.as_slice());
+        }
+        Ok(())
+    }
+}
+
+// Tickets migrated from a previous layout are registered directly
+impl FromIterator<(StringTicketHash, StringTicket)> for TicketRegistry {
+    fn from_iter<T: IntoIterator<Item = (StringTicketHash, StringTicket)>>(iter: T) -> Self {
+        Self(
+            iter
+                .into_iter()
+                .map(|(hash, ticket)| (hash, TicketDefinition::from(&ticket)))
+                .collect()
+        )
+    }
+}
+
+fn ticket_path(hash: &StringTicketHash) -> Result<OwnedPath, TicketRegistryError> {
+    let path = format!("{}/{}", TICKETS_PATH, hash.to_hex());
+
+    OwnedPath::try_from(path.into_bytes()).map_err(TicketRegistryError::Path)
+}
+
+// Encoding
+has_encoding!(TicketDefinition, TICKET_DEFINITION_ENCODING, { Encoding::Custom });
+
+impl NomReader for TicketDefinition {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        map(pair(Contract::nom_read, nom_read::string), |(creator, contents)| {
+            TicketDefinition { creator, contents }
+        })(input)
+    }
+}
+
+impl BinWriter for TicketDefinition {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        self.creator.bin_write(output)?;
+        enc::string(&self.contents, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use mock_runtime::host::MockHost;
+
+    use crate::encoding::public_key_hash::PublicKeyHash;
+
+    fn ticket(contents: &str, amount: u64) -> StringTicket {
+        let creator = Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![1; 20])));
+        StringTicket::new(creator, contents.to_string(), amount)
+    }
+
+    #[test]
+    fn registry_save_load_roundtrip() {
+        // Arrange
+        let id_proof = ticket("red", 5).identify_trustless().unwrap();
+        let hash = id_proof.identify().clone();
+
+        let mut registry = TicketRegistry::default();
+        registry.register(&id_proof);
+
+        let mut host = MockHost::default();
+
+        // Act
+        registry.save_tickets(&mut host).unwrap();
+
+        let mut loaded = TicketRegistry::default();
+        loaded.load_ticket(&host, &hash).unwrap();
+
+        // Assert
+        let definition = loaded.lookup(&hash).unwrap();
+        assert_eq!("red", definition.contents());
+        assert_eq!(ticket("red", 3), definition.ticket(3));
+    }
+
+    #[test]
+    fn registry_keeps_first_registration() {
+        // Arrange
+        let first = ticket("red", 5).identify_trustless().unwrap();
+        let second = ticket("red", 7).identify_trustless().unwrap();
+
+        let mut registry = TicketRegistry::default();
+
+        // Act
+        registry.register(&first);
+        registry.register(&second);
+
+        // Assert
+        assert_eq!(1, registry.0.len());
+        let definition = registry.lookup(first.identify()).unwrap();
+        assert_eq!(&TicketDefinition::from(first.ticket()), definition);
+    }
+
+    #[test]
+    fn registry_unknown_ticket() {
+        // Arrange
+        let hash = ticket("red", 5).identify().unwrap();
+        let host = MockHost::default();
+        let mut registry = TicketRegistry::default();
+
+        // Act
+        registry.load_ticket(&host, &hash).unwrap();
+
+        // Assert
+        assert_eq!(None, registry.lookup(&hash));
+    }
+}
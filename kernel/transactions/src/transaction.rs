@@ -0,0 +1,569 @@
+/* Apply the transactions of an external batch, recording a receipt for every operation */
+
+use core::cmp::Ordering;
+use crypto::blake2b::Blake2bError;
+use crypto::hash::Layer2Tz4Hash;
+use host::path::OwnedPath;
+use host::rollup_core::RawRollupCore;
+use host::runtime::Runtime;
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{ map, value };
+use nom::sequence::preceded;
+use tezos_encoding::enc::{ self, BinResult, BinWriter };
+use tezos_encoding::encoding::{ Encoding, HasEncoding };
+use tezos_encoding::has_encoding;
+use tezos_encoding::nom::{ self as nom_read, NomReader, NomResult };
+use thiserror::Error;
+use debug::debug_msg;
+
+use crate::{
+    encoding::string_ticket::{ identify_repr, TicketHashError },
+    inbox::{ v1::{ Operation, OperationContent, ParsedBatch }, Signer },
+    memory::{ AccountError, AccountStorageError, Accounts, Memory },
+    outbox::{ write_outbox_message, OutboxError, OutboxMessage, OutboxMessageTransaction },
+    ticket_registry::{ TicketRegistry, TicketRegistryError },
+    transfer::{ transfer, TransferError },
+    withdrawal::{ withdraw, WithdrawalError },
+};
+
+const APPLIED_TAG: u8 = 0;
+const BACKTRACKED_TAG: u8 = 1;
+const SKIPPED_TAG: u8 = 2;
+const FAILED_TAG: u8 = 3;
+
+/// Outcome of an operation, stored once its transaction has been processed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationReceipt {
+    /// The operation was applied.
+    Applied,
+    /// The operation succeeded, but a later operation of the transaction failed.
+    Backtracked,
+    /// The operation was not attempted, as an earlier operation failed.
+    Skipped,
+    /// The operation failed, with the given error.
+    Failed(String),
+}
+
+has_encoding!(OperationReceipt, OPERATION_RECEIPT_ENCODING, { Encoding::Custom });
+
+impl NomReader for OperationReceipt {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        alt((
+            value(OperationReceipt::Applied, tag([APPLIED_TAG])),
+            value(OperationReceipt::Backtracked, tag([BACKTRACKED_TAG])),
+            value(OperationReceipt::Skipped, tag([SKIPPED_TAG])),
+            map(preceded(tag([FAILED_TAG]), nom_read::string), OperationReceipt::Failed),
+        ))(input)
+    }
+}
+
+impl BinWriter for OperationReceipt {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        match self {
+            Self::Applied => enc::put_byte(&APPLIED_TAG, output),
+            Self::Backtracked => enc::put_byte(&BACKTRACKED_TAG, output),
+            Self::Skipped => enc::put_byte(&SKIPPED_TAG, output),
+            Self::Failed(error) => {
+                enc::put_byte(&FAILED_TAG, output);
+                return enc::string(error, output);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors that may occur when applying an operation.
+#[derive(Error, Debug)]
+pub enum OperationError {
+    /// The signer of the operation has no account.
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Layer2Tz4Hash),
+
+    /// Issue occurred linking the revealed public key to the signer account.
+    #[error("{0}")]
+    AccountError(#[from] AccountError),
+
+    /// Issue occurred hashing the revealed public key.
+    #[error("Unable to hash public key: {0}")]
+    Hashing(#[from] Blake2bError),
+
+    /// Issue occurred loading an account from durable storage.
+    #[error("{0}")]
+    Storage(#[from] AccountStorageError),
+
+    /// Issue occurred hashing a ticket of the operation.
+    #[error("Error hashing ticket contents: {0}")]
+    TicketHash(#[from] TicketHashError),
+
+    /// Issue occurred loading a ticket from the ticket registry.
+    #[error("{0}")]
+    TicketRegistry(#[from] TicketRegistryError),
+
+    /// The operation counter does not match the counter of the signer.
+    #[error("Invalid counter for account {address}: expected {expected}, got {given}")]
+    InvalidCounter {
+        /// The signer of the operation.
+        address: Layer2Tz4Hash,
+        /// The current counter of the account.
+        expected: i64,
+        /// The counter of the operation.
+        given: i64,
+    },
+
+    /// Issue occurred transferring a ticket.
+    #[error("{0}")]
+    Transfer(#[from] TransferError),
+
+    /// Issue occurred withdrawing a ticket.
+    #[error("{0}")]
+    Withdrawal(#[from] WithdrawalError),
+
+    /// Issue occurred writing the withdrawals of the transaction to the outbox.
+    #[error("{0}")]
+    Outbox(#[from] OutboxError),
+}
+
+/// Apply every transaction of the batch, storing the receipts of its operations.
+///
+/// The aggregated signature of the batch is verified first: if it is invalid, no
+/// operation is applied. Receipts are stored under
+/// `/tx/receipts/<level>/<id>/<transaction>/<operation>`.
+pub fn process_batch<Host: RawRollupCore>(
+    host: &mut Host,
+    memory: &mut Memory,
+    level: i32,
+    id: i32,
+    batch: ParsedBatch
+) {
+    if let Err(err) = load_batch_state(host, memory, &batch) {
+        debug_msg!(Host, "Unable to load state of batch {} at level {}: {}", id, level, err);
+        return;
+    }
+
+    #[cfg(not(feature = "tx-kernel-no-sig-verif"))]
+    if let Err(err) = batch.verify_signature(memory.accounts()) {
+        debug_msg!(Host, "Rejecting batch {} at level {}: {}", id, level, err);
+        return;
+    }
+
+    for (index, transaction) in batch.transactions.into_iter().enumerate() {
+        let receipts = apply_transaction(host, memory, transaction.into_operations().collect());
+
+        store_receipts(host, level, id, index, receipts.as_slice());
+    }
+}
+
+/// Apply the operations of a transaction atomically: either every operation is
+/// applied, or none are.
+///
+/// The withdrawals of the transaction are written to the outbox as a single batch,
+/// once every operation has succeeded.
+///
+/// The counter of each operation that passed its counter check is consumed even if the
+/// transaction fails, so that a failed transaction cannot be replayed later.
+pub fn apply_transaction<Host: RawRollupCore>(
+    host: &mut Host,
+    memory: &mut Memory,
+    operations: Vec<Operation>
+) -> Vec<OperationReceipt> {
+    let num_operations = operations.len();
+
+    let (tickets, accounts) = memory.tickets_and_accounts_mut();
+    let mut consumed = Vec::new();
+
+    let result = accounts.atomically(|accounts| {
+        let mut withdrawals = Vec::new();
+
+        for (index, operation) in operations.into_iter().enumerate() {
+            apply_operation(accounts, tickets, operation, &mut withdrawals, &mut consumed)
+                .map_err(|e| (index, e))?;
+        }
+
+        if !withdrawals.is_empty() {
+            // writing the outbox is the last step of the transaction
+            let message = OutboxMessage::AtomicTransactionBatch(withdrawals);
+            write_outbox_message(host, &message).map_err(|e| (num_operations - 1, e.into()))?;
+        }
+
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => vec![OperationReceipt::Applied; num_operations],
+        Err((failed, error)) => {
+            debug_msg!(Host, "Transaction failed at operation {}: {}", failed, error);
+
+            /* the rollback restored the counters - consume them again */
+            for address in consumed.iter() {
+                if let Some(account) = accounts.account_of_mut(address) {
+                    account.increment_counter();
+                }
+            }
+
+            (0..num_operations)
+                .map(|index| match index.cmp(&failed) {
+                    Ordering::Less => OperationReceipt::Backtracked,
+                    Ordering::Equal => OperationReceipt::Failed(error.to_string()),
+                    Ordering::Greater => OperationReceipt::Skipped,
+                })
+                .collect()
+        }
+    }
+}
+
+// Load every account the batch may touch - its signers, and the receivers of its
+// transfers - and every ticket it transfers or withdraws
+fn load_batch_state<Host: RawRollupCore>(
+    host: &Host,
+    memory: &mut Memory,
+    batch: &ParsedBatch
+) -> Result<(), OperationError> {
+    for operation in batch.transactions.iter().flat_map(|t| t.operations()) {
+        memory.accounts_mut().load_account(host, &operation.signer.address()?)?;
+
+        for content in operation.contents.iter() {
+            let ticket = match content {
+                OperationContent::Transfer(transfer) => {
+                    memory.accounts_mut().load_account(host, &transfer.destination)?;
+                    &transfer.ticket
+                }
+                OperationContent::Withdrawal(withdrawal) => &withdrawal.ticket,
+            };
+
+            let hash = identify_repr(ticket)?;
+            memory.tickets_mut().load_ticket(host, &hash)?;
+            memory.accounts_mut().load_supply(host, &hash)?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_operation(
+    accounts: &mut Accounts,
+    tickets: &TicketRegistry,
+    operation: Operation,
+    withdrawals: &mut Vec<OutboxMessageTransaction>,
+    consumed: &mut Vec<Layer2Tz4Hash>
+) -> Result<(), OperationError> {
+    let Operation { signer, counter, contents } = operation;
+    let address = signer.address()?;
+
+    let account = accounts
+        .account_of_mut(&address)
+        .ok_or_else(|| OperationError::AccountNotFound(address.clone()))?;
+
+    if account.counter() != counter {
+        return Err(OperationError::InvalidCounter {
+            expected: account.counter(),
+            given: counter,
+            address,
+        });
+    }
+
+    if let Signer::BlsPublicKey(pk) = signer {
+        account.link_public_key(pk)?;
+    }
+
+    account.increment_counter();
+    consumed.push(address.clone());
+
+    for content in contents {
+        match content {
+            OperationContent::Transfer(t) => transfer(accounts, tickets, &address, t)?,
+            OperationContent::Withdrawal(w) =>
+                withdrawals.push(withdraw(accounts, tickets, &address, w)?),
+        }
+    }
+
+    Ok(())
+}
+
+fn receipt_path(level: i32, id: i32, transaction: usize, operation: usize) -> OwnedPath {
+    let path = format!("/tx/receipts/{}/{}/{}/{}", level, id, transaction, operation);
+
+    OwnedPath::try_from(path.into_bytes()).expect("receipt path is a valid path")
+}
+
+fn store_receipts<Host: RawRollupCore>(
+    host: &mut Host,
+    level: i32,
+    id: i32,
+    transaction: usize,
+    receipts: &[OperationReceipt]
+) {
+    for (operation, receipt) in receipts.iter().enumerate() {
+        let path = receipt_path(level, id, transaction, operation);
+
+        let mut encoded = Vec::new();
+        let stored =
+            receipt.bin_write(&mut encoded).is_ok() &&
+            Runtime::store_write(host, &path, encoded.as_slice(), 0).is_ok();
+
+        if !stored {
+            debug_msg!(Host, "Unable to store receipt {:?} at {:?}", receipt, path);
+        }
+    }
+}
+
+/// Read back the receipt of an operation, if it has been stored.
+pub fn read_receipt<Host: RawRollupCore>(
+    host: &Host,
+    level: i32,
+    id: i32,
+    transaction: usize,
+    operation: usize
+) -> Option<OperationReceipt> {
+    use host::rollup_core::MAX_FILE_CHUNK_SIZE;
+
+    let path = receipt_path(level, id, transaction, operation);
+    let bytes = Runtime::store_read(host, &path, 0, MAX_FILE_CHUNK_SIZE).ok()?;
+
+    OperationReceipt::nom_read(bytes.as_slice())
+        .ok()
+        .map(|(_, receipt)| receipt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use mock_runtime::host::MockHost;
+
+    use crate::encoding::bls::tests::key_pair;
+    use crate::encoding::{
+        contract::Contract,
+        entrypoint::Entrypoint,
+        public_key_hash::PublicKeyHash,
+        string_ticket::{ StringTicket, StringTicketHash },
+    };
+    use crate::memory::Account;
+
+    fn contract_of(byte: u8) -> Contract {
+        Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![byte; 20])))
+    }
+
+    fn ticket(amount: u64) -> StringTicket {
+        StringTicket::new(contract_of(1), "Hello, Ticket!".to_string(), amount)
+    }
+
+    fn ticket_hash() -> StringTicketHash {
+        ticket(0).identify().unwrap()
+    }
+
+    fn memory_with_balance(address: &Layer2Tz4Hash, amount: u64) -> Memory {
+        let mut account = Account::default();
+        account.add_ticket(ticket_hash(), amount).unwrap();
+
+        let mut memory = Memory::default();
+        memory.accounts_mut().add_account(address.clone(), account).unwrap();
+        memory.accounts_mut().mint(&ticket_hash(), amount).unwrap();
+        memory.add_ticket(ticket(amount).identify_trustless().unwrap());
+        memory
+    }
+
+    fn operation(
+        signer: &Layer2Tz4Hash,
+        counter: i64,
+        contents: Vec<OperationContent>
+    ) -> Operation {
+        Operation {
+            signer: Signer::Layer2Address(signer.clone()),
+            counter,
+            contents,
+        }
+    }
+
+    fn balance_of(memory: &Memory, address: &Layer2Tz4Hash) -> u64 {
+        memory
+            .accounts()
+            .account_of(address)
+            .map(|account| account.balance(&ticket_hash()))
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn apply_transaction_transfers_ticket() {
+        // Arrange
+        let sender = Layer2Tz4Hash(vec![1; 20]);
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+        let mut memory = memory_with_balance(&sender, 10);
+        let mut host = MockHost::default();
+
+        let operations = vec![
+            operation(&sender, 0, vec![OperationContent::transfer(receiver.clone(), ticket(3))]),
+            operation(&sender, 1, vec![OperationContent::transfer(receiver.clone(), ticket(2))])
+        ];
+
+        // Act
+        let receipts = apply_transaction(&mut host, &mut memory, operations);
+
+        // Assert
+        assert_eq!(vec![OperationReceipt::Applied; 2], receipts);
+        assert_eq!(5, balance_of(&memory, &sender));
+        assert_eq!(5, balance_of(&memory, &receiver));
+        assert_eq!(2, memory.accounts().account_of(&sender).unwrap().counter());
+    }
+
+    #[test]
+    fn apply_transaction_reveals_public_key() {
+        // Arrange
+        let (_sk, pk) = key_pair(1);
+        let sender = pk.hash().unwrap();
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+        let mut memory = memory_with_balance(&sender, 10);
+        let mut host = MockHost::default();
+
+        let operations = vec![Operation {
+            signer: Signer::BlsPublicKey(pk.clone()),
+            counter: 0,
+            contents: vec![OperationContent::transfer(receiver.clone(), ticket(3))],
+        }];
+
+        // Act
+        let receipts = apply_transaction(&mut host, &mut memory, operations);
+
+        // Assert
+        assert_eq!(vec![OperationReceipt::Applied], receipts);
+        assert_eq!(7, balance_of(&memory, &sender));
+
+        let account = memory.accounts().account_of(&sender).unwrap();
+        assert_eq!(Some(&pk), account.public_key());
+    }
+
+    #[test]
+    fn apply_transaction_invalid_counter() {
+        // Arrange
+        let sender = Layer2Tz4Hash(vec![1; 20]);
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+        let mut memory = memory_with_balance(&sender, 10);
+        let mut host = MockHost::default();
+
+        let operations = vec![
+            operation(&sender, 0, vec![OperationContent::transfer(receiver.clone(), ticket(3))]),
+            operation(&sender, 5, vec![OperationContent::transfer(receiver.clone(), ticket(3))])
+        ];
+
+        // Act
+        let receipts = apply_transaction(&mut host, &mut memory, operations);
+
+        // Assert
+        assert!(
+            matches!(
+                receipts.as_slice(),
+                [OperationReceipt::Backtracked, OperationReceipt::Failed(_)]
+            )
+        );
+        assert_eq!(10, balance_of(&memory, &sender));
+        assert_eq!(0, balance_of(&memory, &receiver));
+        assert_eq!(1, memory.accounts().account_of(&sender).unwrap().counter());
+    }
+
+    #[test]
+    fn apply_transaction_is_atomic() {
+        // Arrange
+        let sender = Layer2Tz4Hash(vec![1; 20]);
+        let receiver = Layer2Tz4Hash(vec![2; 20]);
+        let mut memory = memory_with_balance(&sender, 10);
+        let mut host = MockHost::default();
+
+        let operations = vec![
+            operation(
+                &sender,
+                0,
+                vec![
+                    OperationContent::withdrawal(contract_of(3), ticket(1), Entrypoint::default())
+                ]
+            ),
+            operation(&sender, 1, vec![OperationContent::transfer(receiver.clone(), ticket(4))]),
+            operation(&sender, 2, vec![OperationContent::transfer(receiver.clone(), ticket(6))]),
+            operation(&sender, 3, vec![OperationContent::transfer(receiver.clone(), ticket(1))])
+        ];
+
+        // Act
+        let receipts = apply_transaction(&mut host, &mut memory, operations);
+
+        // Assert
+        assert!(
+            matches!(
+                receipts.as_slice(),
+                [
+                    OperationReceipt::Backtracked,
+                    OperationReceipt::Backtracked,
+                    OperationReceipt::Failed(_),
+                    OperationReceipt::Skipped,
+                ]
+            )
+        );
+        assert_eq!(10, balance_of(&memory, &sender));
+        assert_eq!(0, balance_of(&memory, &receiver));
+        assert_eq!(3, memory.accounts().account_of(&sender).unwrap().counter());
+        assert_eq!(10, memory.accounts().supply(&ticket_hash()));
+
+        let state = host.into_inner();
+        assert!(!state.store.has_entry("/output/0/0"));
+    }
+
+    #[test]
+    fn apply_transaction_writes_withdrawals_as_one_batch() {
+        // Arrange
+        let sender = Layer2Tz4Hash(vec![1; 20]);
+        let mut memory = memory_with_balance(&sender, 10);
+        let mut host = MockHost::default();
+
+        let withdraw_to = |contract| {
+            OperationContent::withdrawal(contract_of(contract), ticket(5), Entrypoint::default())
+        };
+        let operations = vec![operation(&sender, 0, vec![withdraw_to(3), withdraw_to(4)])];
+
+        // Act
+        let receipts = apply_transaction(&mut host, &mut memory, operations);
+
+        // Assert
+        assert_eq!(vec![OperationReceipt::Applied], receipts);
+        assert_eq!(0, balance_of(&memory, &sender));
+        assert_eq!(0, memory.accounts().supply(&ticket_hash()));
+
+        let expected = OutboxMessage::AtomicTransactionBatch(
+            vec![
+                OutboxMessageTransaction {
+                    parameters: ticket(5).into(),
+                    destination: contract_of(3),
+                    entrypoint: Entrypoint::default(),
+                },
+                OutboxMessageTransaction {
+                    parameters: ticket(5).into(),
+                    destination: contract_of(4),
+                    entrypoint: Entrypoint::default(),
+                }
+            ]
+        );
+        let mut expected_bytes = Vec::new();
+        expected.bin_write(&mut expected_bytes).unwrap();
+
+        let state = host.into_inner();
+        assert_eq!(expected_bytes, state.store.get_value::<Vec<u8>>("/output/0/0"));
+        assert!(!state.store.has_entry("/output/0/1"));
+    }
+
+    #[test]
+    fn store_receipts_read_receipt_roundtrip() {
+        // Arrange
+        let mut host = MockHost::default();
+        let receipts = vec![
+            OperationReceipt::Backtracked,
+            OperationReceipt::Failed("Something went wrong".to_string()),
+            OperationReceipt::Skipped
+        ];
+
+        // Act
+        store_receipts(&mut host, 5, 2, 1, receipts.as_slice());
+
+        // Assert
+        for (index, receipt) in receipts.into_iter().enumerate() {
+            assert_eq!(Some(receipt), read_receipt(&host, 5, 2, 1, index));
+        }
+        assert_eq!(None, read_receipt(&host, 5, 2, 1, 3));
+        assert_eq!(None, read_receipt(&host, 5, 2, 0, 0));
+    }
+}
@@ -0,0 +1,69 @@
+use crypto::hash::Layer2Tz4Hash;
+use thiserror::Error;
+
+use crate::{
+    encoding::string_ticket::{
+        StringTicket,
+        StringTicketHash,
+        TicketConversionError,
+        TicketHashError,
+    },
+    inbox::v1::OperationTransfer,
+    memory::{ AccountError, Accounts },
+    ticket_registry::TicketRegistry,
+};
+
+// Transfer tickets between layer 2 accounts
+
+/// Errors that may occur when transferring a ticket between accounts.
+#[derive(Error, Debug)]
+pub enum TransferError {
+    /// Issue occurred while handling the sender or receiver account.
+    #[error("{0}")]
+    AccountError(#[from] AccountError),
+
+    /// The sending account does not exist.
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Layer2Tz4Hash),
+
+    /// The transferred ticket is not a valid string ticket.
+    #[error("Invalid ticket: {0}")]
+    TicketConversion(#[from] TicketConversionError),
+
+    /// Issue occurred hashing ticket.
+    #[error("Error hashing ticket contents: {0}")]
+    TicketHash(#[from] TicketHashError),
+
+    /// The transferred ticket was never deposited into the rollup.
+    #[error("Ticket {0:?} is not registered")]
+    UnknownTicket(StringTicketHash),
+}
+
+/// Move the ticket from `source` to the destination account of the transfer.
+///
+/// The destination account is created if it does not already exist. The ticket must be
+/// registered, and loaded in `tickets`.
+pub fn transfer(
+    accounts: &mut Accounts,
+    tickets: &TicketRegistry,
+    source: &Layer2Tz4Hash,
+    transfer: OperationTransfer
+) -> Result<(), TransferError> {
+    let OperationTransfer { destination, ticket } = transfer;
+
+    let ticket = StringTicket::try_from(ticket)?;
+    let ticket_hash = ticket.identify()?;
+
+    if tickets.lookup(&ticket_hash).is_none() {
+        return Err(TransferError::UnknownTicket(ticket_hash));
+    }
+
+    accounts
+        .account_of_mut(source)
+        .ok_or_else(|| TransferError::AccountNotFound(source.clone()))?
+        .remove_ticket(&ticket_hash, ticket.amount())?;
+
+    accounts.account_or_default_mut(&destination).add_ticket(ticket_hash, ticket.amount())?;
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use crypto::hash::Layer2Tz4Hash;
+use host::rollup_core::RawRollupCore;
+use thiserror::Error;
+use debug::debug_msg;
+
+use crate::{
+    encoding::string_ticket::{
+        StringTicket,
+        StringTicketHash,
+        TicketConversionError,
+        TicketHashError,
+    },
+    inbox::v1::OperationWithdrawal,
+    memory::{ AccountError, Accounts, Memory },
+    outbox::{ write_outbox_message, OutboxError, OutboxMessage, OutboxMessageTransaction },
+    ticket_registry::TicketRegistry,
+};
+
+// Withdraw tickets from the kernel state, back to layer 1
+
+/// Errors that may occur when withdrawing a ticket from an account.
+#[derive(Error, Debug)]
+pub enum WithdrawalError {
+    /// Issue occurred while handling the withdrawing account.
+    #[error("{0}")]
+    AccountError(#[from] AccountError),
+
+    /// The withdrawing account does not exist.
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Layer2Tz4Hash),
+
+    /// The withdrawn ticket is not a valid string ticket.
+    #[error("Invalid ticket: {0}")]
+    TicketConversion(#[from] TicketConversionError),
+
+    /// Issue occurred hashing ticket.
+    #[error("Error hashing ticket contents: {0}")]
+    TicketHash(#[from] TicketHashError),
+
+    /// Issue occurred writing the outbox message.
+    #[error("{0}")]
+    Outbox(#[from] OutboxError),
+
+    /// The withdrawn ticket was never deposited into the rollup.
+    #[error("Ticket {0:?} is not registered")]
+    UnknownTicket(StringTicketHash),
+}
+
+/// Debit the ticket from `account_address`, returning the outbox transaction that
+/// sends it to its layer 1 destination.
+///
+/// The transaction must be written to the outbox by the caller. The ticket sent back to
+/// layer 1 is rebuilt from its registration, which must be loaded in `tickets`; its supply
+/// must be loaded in `accounts`.
+pub fn withdraw(
+    accounts: &mut Accounts,
+    tickets: &TicketRegistry,
+    account_address: &Layer2Tz4Hash,
+    withdrawal: OperationWithdrawal
+) -> Result<OutboxMessageTransaction, WithdrawalError> {
+    let OperationWithdrawal { destination, ticket, entrypoint } = withdrawal;
+
+    let ticket = StringTicket::try_from(ticket)?;
+    let ticket_hash = ticket.identify()?;
+
+    let registered = tickets
+        .lookup(&ticket_hash)
+        .ok_or_else(|| WithdrawalError::UnknownTicket(ticket_hash.clone()))?;
+
+    accounts
+        .account_of_mut(account_address)
+        .ok_or_else(|| WithdrawalError::AccountNotFound(account_address.clone()))?
+        .remove_ticket(&ticket_hash, ticket.amount())?;
+
+    // the ticket leaves the rollup
+    accounts.burn(&ticket_hash, ticket.amount())?;
+
+    Ok(OutboxMessageTransaction {
+        parameters: registered.ticket(ticket.amount()).into(),
+        destination,
+        entrypoint,
+    })
+}
+
+/// Debit the ticket from `account_address`, and send it to its layer 1 destination
+/// as an outbox transaction.
+pub fn withdraw_ticket<Host: RawRollupCore>(
+    host: &mut Host,
+    memory: &mut Memory,
+    account_address: &Layer2Tz4Hash,
+    withdrawal: OperationWithdrawal
+) -> Result<(), WithdrawalError> {
+    debug_msg!(Host, "Withdrawing {:#?} from account {:?}", withdrawal, account_address);
+
+    let (tickets, accounts) = memory.tickets_and_accounts_mut();
+
+    accounts.atomically(|accounts| {
+        let transaction = withdraw(accounts, tickets, account_address, withdrawal)?;
+
+        // the ticket only leaves the rollup if the outbox message is written
+        let message = OutboxMessage::AtomicTransactionBatch(vec![transaction]);
+        write_outbox_message(host, &message)?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::hash::ContractTz1Hash;
+    use mock_runtime::host::MockHost;
+    use tezos_encoding::enc::BinWriter;
+
+    use crate::encoding::{
+        contract::Contract,
+        entrypoint::Entrypoint,
+        public_key_hash::PublicKeyHash,
+    };
+    use crate::memory::Account;
+
+    const TICKET_CONTENTS: &str = "Hello, Ticket!";
+
+    fn contract_of(byte: u8) -> Contract {
+        Contract::Implicit(PublicKeyHash::Ed25519(ContractTz1Hash(vec![byte; 20])))
+    }
+
+    fn ticket(amount: u64) -> StringTicket {
+        StringTicket::new(contract_of(1), TICKET_CONTENTS.to_string(), amount)
+    }
+
+    fn memory_with_balance(address: &Layer2Tz4Hash, amount: u64) -> Memory {
+        let hash = ticket(amount).identify().unwrap();
+        let mut account = Account::default();
+        account.add_ticket(hash.clone(), amount).unwrap();
+
+        let mut memory = Memory::default();
+        memory.accounts_mut().add_account(address.clone(), account).unwrap();
+        memory.accounts_mut().mint(&hash, amount).unwrap();
+        memory.add_ticket(ticket(amount).identify_trustless().unwrap());
+        memory
+    }
+
+    fn withdrawal(amount: u64) -> OperationWithdrawal {
+        OperationWithdrawal {
+            destination: contract_of(2),
+            ticket: ticket(amount).into(),
+            entrypoint: Entrypoint::default(),
+        }
+    }
+
+    fn expected_output(amount: u64) -> Vec<u8> {
+        let message = OutboxMessage::AtomicTransactionBatch(
+            vec![OutboxMessageTransaction {
+                parameters: ticket(amount).into(),
+                destination: contract_of(2),
+                entrypoint: Entrypoint::default(),
+            }]
+        );
+
+        let mut bytes = Vec::new();
+        message.bin_write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn withdraw_ticket_writes_outbox_message() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![7; 20]);
+        let mut memory = memory_with_balance(&address, 10);
+        let mut host = MockHost::default();
+
+        // Act
+        let result = withdraw_ticket(&mut host, &mut memory, &address, withdrawal(4));
+
+        // Assert
+        assert!(result.is_ok());
+
+        let hash = ticket(0).identify().unwrap();
+        let account = memory.accounts().account_of(&address).unwrap();
+        assert_eq!(6, account.balance(&hash));
+        assert_eq!(6, memory.accounts().supply(&hash));
+
+        let state = host.into_inner();
+        assert_eq!(expected_output(4), state.store.get_value::<Vec<u8>>("/output/0/0"));
+        assert!(!state.store.has_entry("/output/0/1"));
+    }
+
+    #[test]
+    fn withdraw_ticket_outputs_each_withdrawal() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![7; 20]);
+        let mut memory = memory_with_balance(&address, 10);
+        let mut host = MockHost::default();
+
+        // Act
+        withdraw_ticket(&mut host, &mut memory, &address, withdrawal(3)).unwrap();
+        withdraw_ticket(&mut host, &mut memory, &address, withdrawal(7)).unwrap();
+
+        // Assert
+        let hash = ticket(0).identify().unwrap();
+        let account = memory.accounts().account_of(&address).unwrap();
+        assert_eq!(0, account.balance(&hash));
+
+        let state = host.into_inner();
+        assert_eq!(expected_output(3), state.store.get_value::<Vec<u8>>("/output/0/0"));
+        assert_eq!(expected_output(7), state.store.get_value::<Vec<u8>>("/output/0/1"));
+    }
+
+    #[test]
+    fn withdraw_ticket_insufficient_balance() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![7; 20]);
+        let mut memory = memory_with_balance(&address, 10);
+        let mut host = MockHost::default();
+
+        // Act
+        let result = withdraw_ticket(&mut host, &mut memory, &address, withdrawal(11));
+
+        // Assert
+        assert!(
+            matches!(
+                result,
+                Err(WithdrawalError::AccountError(AccountError::InsufficientBalance(10, 11)))
+            )
+        );
+
+        let hash = ticket(0).identify().unwrap();
+        let account = memory.accounts().account_of(&address).unwrap();
+        assert_eq!(10, account.balance(&hash));
+
+        let state = host.into_inner();
+        assert!(!state.store.has_entry("/output/0/0"));
+    }
+
+    #[test]
+    fn withdraw_ticket_unknown_account() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![7; 20]);
+        let mut memory = Memory::default();
+        memory.add_ticket(ticket(1).identify_trustless().unwrap());
+        let mut host = MockHost::default();
+
+        // Act
+        let result = withdraw_ticket(&mut host, &mut memory, &address, withdrawal(1));
+
+        // Assert
+        assert!(matches!(result, Err(WithdrawalError::AccountNotFound(_))));
+
+        let state = host.into_inner();
+        assert!(!state.store.has_entry("/output/0/0"));
+    }
+
+    #[test]
+    fn withdraw_ticket_unknown_ticket() {
+        // Arrange
+        let address = Layer2Tz4Hash(vec![7; 20]);
+        let mut account = Account::default();
+        account.add_ticket(ticket(0).identify().unwrap(), 10).unwrap();
+
+        let mut memory = Memory::default();
+        memory.accounts_mut().add_account(address.clone(), account).unwrap();
+
+        let mut host = MockHost::default();
+
+        // Act
+        let result = withdraw_ticket(&mut host, &mut memory, &address, withdrawal(1));
+
+        // Assert
+        assert!(matches!(result, Err(WithdrawalError::UnknownTicket(_))));
+
+        let state = host.into_inner();
+        assert!(!state.store.has_entry("/output/0/0"));
+    }
+}